@@ -0,0 +1,338 @@
+// Pixel adjustments applied to a rendered RGBA page before it's encoded.
+
+use crate::encode::RgbaView;
+
+// A block of pixels, right and bottom edges exclusive.
+#[derive(Clone, Copy)]
+pub struct PixelRect {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+// Flips lightness while keeping hue and saturation, so black text on white becomes
+// white on black but a red heading stays red. Pixels inside `keep`, e.g. photos, are
+// left alone.
+pub fn invert_lightness(pixels: &mut [u8], width: u32, keep: &[PixelRect]) {
+    if width == 0 {
+        return;
+    }
+    for (y, row) in pixels.chunks_exact_mut(width as usize * 4).enumerate() {
+        let y = y as u32;
+        let spans: Vec<&PixelRect> = keep
+            .iter()
+            .filter(|rect| rect.top <= y && y < rect.bottom)
+            .collect();
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let x = x as u32;
+            if spans.iter().any(|rect| rect.left <= x && x < rect.right) {
+                continue;
+            }
+            let max = pixel[0].max(pixel[1]).max(pixel[2]) as i32;
+            let min = pixel[0].min(pixel[1]).min(pixel[2]) as i32;
+            // Maps the channel range [min, max] onto [255 - max, 255 - min]
+            let shift = 255 - max - min;
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as i32 + shift) as u8;
+            }
+        }
+    }
+}
+
+// Tone corrections for faint or muddy scans, all off by default.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Adjustments {
+    // Input levels: darker tones become black, lighter ones white
+    pub black_point: u8,
+    pub white_point: u8,
+    // Above 1 lightens midtones, below 1 darkens them
+    pub gamma: f32,
+    // -1 to 1; 0 leaves the image as it is
+    pub contrast: f32,
+    pub brightness: f32,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            black_point: 0,
+            white_point: 255,
+            gamma: 1.0,
+            contrast: 0.0,
+            brightness: 0.0,
+        }
+    }
+}
+
+impl Adjustments {
+    // Output value for every input value of a channel.
+    fn table(&self) -> [u8; 256] {
+        let black = f32::from(self.black_point);
+        let range = (f32::from(self.white_point) - black).max(1.0);
+        let gamma = if self.gamma > 0.0 { self.gamma } else { 1.0 };
+        let contrast = 1.0 + self.contrast.clamp(-1.0, 1.0);
+        let mut table = [0u8; 256];
+        for (value, out) in table.iter_mut().enumerate() {
+            let level = ((value as f32 - black) / range).clamp(0.0, 1.0);
+            let tone = level.powf(1.0 / gamma);
+            let tone = (tone - 0.5) * contrast + 0.5 + self.brightness.clamp(-1.0, 1.0);
+            *out = (tone.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        table
+    }
+
+    pub fn apply(&self, pixels: &mut [u8]) {
+        let table = self.table();
+        for pixel in pixels.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = table[*channel as usize];
+            }
+        }
+    }
+}
+
+// Unsharp mask for pages that come out soft after downscaling.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Sharpen {
+    // How much of the detail is added back; 1 doubles it
+    pub amount: f32,
+    // Blur radius (sigma) in pixels that decides what counts as detail
+    pub radius: f32,
+    // Differences up to this are left alone, so flat areas don't turn grainy
+    pub threshold: u8,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Sharpen {
+            amount: 0.5,
+            radius: 1.0,
+            threshold: 0,
+        }
+    }
+}
+
+impl Sharpen {
+    pub fn apply(&self, pixels: &mut [u8], width: u32, height: u32) {
+        if self.amount <= 0.0 || self.radius <= 0.0 {
+            return;
+        }
+        let Some(view) = RgbaView::from_raw(width, height, &*pixels) else {
+            return;
+        };
+        let blurred = image::imageops::blur(&view, self.radius);
+        for (pixel, blurred) in pixels.chunks_exact_mut(4).zip(blurred.pixels()) {
+            for (channel, &soft) in pixel[..3].iter_mut().zip(&blurred.0[..3]) {
+                let detail = *channel as f32 - soft as f32;
+                if detail.abs() > f32::from(self.threshold) {
+                    *channel = (*channel as f32 + detail * self.amount).clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+// Share of the page cut off each side, e.g. scanner edges or wide margins, as
+// fractions of the page's width and height.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Crop {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Crop {
+    // Left and top edge and size of what's kept of a `width` by `height` page; at
+    // least a pixel stays.
+    fn kept(&self, width: u64, height: u64) -> (u64, u64, u64, u64) {
+        let cut = |share: f32, side: u64| (share.clamp(0.0, 1.0) * side as f32) as u64;
+        let kept = |start: u64, end: u64, side: u64| {
+            let size = side.saturating_sub(start + end).max(1).min(side);
+            (start.min(side - size), size)
+        };
+        let (left, kept_width) = kept(cut(self.left, width), cut(self.right, width), width);
+        let (top, kept_height) = kept(cut(self.top, height), cut(self.bottom, height), height);
+        (left, top, kept_width, kept_height)
+    }
+
+    pub fn cropped_size(&self, width: u64, height: u64) -> (u64, u64) {
+        let (_, _, width, height) = self.kept(width, height);
+        (width, height)
+    }
+
+    // Copies the kept part of `page` into `out`.
+    pub fn apply(&self, page: &RgbaView, out: &mut Vec<u8>) -> (u32, u32) {
+        let (page_width, page_height) = page.dimensions();
+        let (left, top, width, height) = self.kept(page_width.into(), page_height.into());
+        let pixels: &[u8] = page.as_raw();
+        out.clear();
+        for y in top..top + height {
+            let start = ((y * u64::from(page_width) + left) * 4) as usize;
+            out.extend_from_slice(&pixels[start..start + width as usize * 4]);
+        }
+        (width as u32, height as u32)
+    }
+}
+
+// Margin, outline and drop shadow around each page, so page images on white web pages
+// keep a visible edge. Sizes are in output pixels.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Border {
+    pub padding: u32,
+    pub background: [u8; 3],
+    // 0 for no outline
+    pub width: u32,
+    pub color: [u8; 3],
+    // Offset of a soft shadow below and right of the page, within the padding; 0 for none
+    pub shadow: u32,
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Border {
+            padding: 24,
+            background: [255, 255, 255],
+            width: 1,
+            color: [200, 200, 200],
+            shadow: 0,
+        }
+    }
+}
+
+// How dark the shadow gets right under the page.
+const SHADOW_OPACITY: f32 = 0.35;
+
+impl Border {
+    pub fn framed_size(&self, width: u64, height: u64) -> (u64, u64) {
+        let padding = u64::from(self.padding) * 2;
+        (width + padding, height + padding)
+    }
+
+    // Draws `page` onto `out`, which is resized to the framed size.
+    pub fn apply(&self, page: &RgbaView, out: &mut Vec<u8>) -> (u32, u32) {
+        let (page_width, page_height) = page.dimensions();
+        let (width, height) = self.framed_size(page_width.into(), page_height.into());
+        let (width, height) = (width as u32, height as u32);
+        let [red, green, blue] = self.background;
+        out.clear();
+        out.extend(
+            std::iter::repeat_n([red, green, blue, 255], width as usize * height as usize)
+                .flatten(),
+        );
+
+        let padding = self.padding;
+        let (left, top) = (padding as i64, padding as i64);
+        let (right, bottom) = (left + page_width as i64, top + page_height as i64);
+        let shadow = self.shadow.min(padding) as i64;
+        let outline = self.width.min(padding) as i64;
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let index = (y as usize * width as usize + x as usize) * 4;
+                let pixel = &mut out[index..index + 4];
+                if (left..right).contains(&x) && (top..bottom).contains(&y) {
+                    let source = page.get_pixel((x - left) as u32, (y - top) as u32).0;
+                    pixel.copy_from_slice(&source);
+                    continue;
+                }
+                let outside = (left - x)
+                    .max(x - right + 1)
+                    .max(top - y)
+                    .max(y - bottom + 1);
+                if outside <= outline {
+                    let [red, green, blue] = self.color;
+                    pixel.copy_from_slice(&[red, green, blue, 255]);
+                    continue;
+                }
+                if shadow > 0 {
+                    // Distance outside the page shifted by the shadow offset, fading out
+                    // over the same distance
+                    let distance = (left + shadow - x)
+                        .max(x - right - shadow + 1)
+                        .max(top + shadow - y)
+                        .max(y - bottom - shadow + 1)
+                        .max(0);
+                    if distance < shadow {
+                        let darken = SHADOW_OPACITY * (1.0 - distance as f32 / shadow as f32);
+                        for channel in &mut pixel[..3] {
+                            *channel = (*channel as f32 * (1.0 - darken)) as u8;
+                        }
+                    }
+                }
+            }
+        }
+        (width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_stretch_between_black_and_white_points() {
+        let mut pixels = vec![40, 125, 210, 77];
+        Adjustments::default().apply(&mut pixels);
+        assert_eq!(pixels, [40, 125, 210, 77]);
+        let levels = Adjustments {
+            black_point: 50,
+            white_point: 200,
+            ..Default::default()
+        };
+        levels.apply(&mut pixels);
+        // Alpha is left alone
+        assert_eq!(pixels, [0, 128, 255, 77]);
+        let brightest = Adjustments {
+            brightness: 1.0,
+            ..Default::default()
+        };
+        brightest.apply(&mut pixels);
+        assert_eq!(pixels, [255, 255, 255, 77]);
+    }
+
+    #[test]
+    fn sharpen_steepens_edges_past_the_threshold() {
+        // Left half mid grey, right half light grey
+        let page = |x: u32| match x < 8 {
+            true => [100, 100, 100, 255],
+            false => [200, 200, 200, 255],
+        };
+        let pixels: Vec<u8> = (0..16 * 16).flat_map(|i| page(i % 16)).collect();
+        let mut sharpened = pixels.clone();
+        Sharpen::default().apply(&mut sharpened, 16, 16);
+        let at = |pixels: &[u8], x: usize| pixels[(8 * 16 + x) * 4];
+        assert!(at(&sharpened, 7) < 100);
+        assert!(at(&sharpened, 8) > 200);
+        assert_eq!(at(&sharpened, 0), 100);
+        let mut untouched = pixels.clone();
+        Sharpen {
+            threshold: 255,
+            ..Default::default()
+        }
+        .apply(&mut untouched, 16, 16);
+        assert_eq!(untouched, pixels);
+    }
+
+    #[test]
+    fn border_frames_the_page() {
+        let page = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]));
+        let border = Border {
+            padding: 2,
+            width: 1,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(border.apply(&crate::encode::view(&page), &mut out), (6, 6));
+        let at = |x: usize, y: usize| &out[(y * 6 + x) * 4..(y * 6 + x) * 4 + 4];
+        assert_eq!(at(0, 0), [255, 255, 255, 255]);
+        assert_eq!(at(1, 1), [200, 200, 200, 255]);
+        assert_eq!(at(2, 2), [255, 0, 0, 255]);
+        assert_eq!(at(3, 3), [255, 0, 0, 255]);
+        assert_eq!(at(4, 2), [200, 200, 200, 255]);
+    }
+}
@@ -0,0 +1,88 @@
+// Conversion of rendered pages to CMYK for print shops that don't take RGB files.
+
+use crate::encode::{self, RgbaView};
+use crate::error::ConvertError;
+use moxcms::{ColorProfile, DataColorSpace, Layout, Transform8BitExecutor, TransformOptions};
+use std::path::Path;
+
+pub struct CmykConversion {
+    // Through the printer's ICC output profile, e.g. FOGRA39 or GRACoL; without one,
+    // the textbook formula with all the grey going to black ink
+    icc: Option<(Vec<u8>, Box<Transform8BitExecutor>)>,
+    // What the conversion went through, so cached pages of another profile aren't reused
+    label: String,
+}
+
+impl CmykConversion {
+    pub fn load(profile_path: Option<&str>) -> Result<Self, ConvertError> {
+        let Some(path) = profile_path else {
+            return Ok(CmykConversion {
+                icc: None,
+                label: "simple".to_string(),
+            });
+        };
+        let invalid = |message: String| ConvertError::InvalidColorProfile {
+            path: path.to_string(),
+            message,
+        };
+        let bytes = std::fs::read(path).map_err(|e| ConvertError::io(Path::new(path), e))?;
+        let profile = ColorProfile::new_from_slice(&bytes).map_err(|e| invalid(e.to_string()))?;
+        if profile.color_space != DataColorSpace::Cmyk {
+            return Err(invalid("not a CMYK profile".to_string()));
+        }
+        // Moxcms holds four-channel colour in the RGBA layout
+        let transform = ColorProfile::new_srgb()
+            .create_transform_8bit(
+                Layout::Rgb,
+                &profile,
+                Layout::Rgba,
+                TransformOptions::default(),
+            )
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(CmykConversion {
+            icc: Some((bytes, transform)),
+            label: path.to_string(),
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    // The ICC profile to embed, so the shop knows what the numbers mean.
+    pub fn profile(&self) -> Option<&[u8]> {
+        self.icc.as_ref().map(|(bytes, _)| bytes.as_slice())
+    }
+
+    // CMYK samples of the page, with transparency flattened onto white paper first.
+    pub fn convert(&self, page: &RgbaView) -> Result<Vec<u8>, ConvertError> {
+        let rgb = encode::flatten_onto_white(page);
+        match &self.icc {
+            Some((_, transform)) => {
+                let mut cmyk = vec![0; rgb.len() / 3 * 4];
+                transform
+                    .transform(&rgb, &mut cmyk)
+                    .map_err(|e| ConvertError::EncodeFailed {
+                        message: e.to_string(),
+                    })?;
+                Ok(cmyk)
+            }
+            None => Ok(rgb.chunks_exact(3).flat_map(simple).collect()),
+        }
+    }
+}
+
+fn simple(rgb: &[u8]) -> [u8; 4] {
+    let [red, green, blue] = [rgb[0], rgb[1], rgb[2]].map(|c| f32::from(c) / 255.0);
+    let black = 1.0 - red.max(green).max(blue);
+    if black >= 1.0 {
+        return [0, 0, 0, 255];
+    }
+    let ink = |c: f32| ((1.0 - c - black) / (1.0 - black) * 255.0).round() as u8;
+    [
+        ink(red),
+        ink(green),
+        ink(blue),
+        (black * 255.0).round() as u8,
+    ]
+}
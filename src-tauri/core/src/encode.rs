@@ -0,0 +1,349 @@
+use crate::cmyk::CmykConversion;
+use crate::error::ConvertError;
+use crate::metadata;
+use image::{ExtendedColorType, ImageEncoder, RgbaImage};
+use std::io::{Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy)]
+pub struct EncodeSettings<'a> {
+    pub ext: &'static str,
+    pub quality: u8,
+    pub lossless: bool,
+    // Upper bound for lossy outputs; quality is lowered until the page fits
+    pub max_bytes: Option<u64>,
+    // EXIF (a TIFF structure) embedded in the output
+    pub exif: Option<&'a [u8]>,
+    // Make sure no metadata at all ends up in the output
+    pub strip_metadata: bool,
+    // TIFFs are written in CMYK through this rather than in RGBA
+    pub cmyk: Option<&'a CmykConversion>,
+    // 16 bits per channel for PNG and RGBA TIFF, so tone adjustments further down the
+    // line don't band
+    pub sixteen_bit: bool,
+}
+
+impl EncodeSettings<'_> {
+    fn is_lossy(&self) -> bool {
+        self.ext == "jpg" || (self.ext == "webp" && !self.lossless)
+    }
+
+    fn with_quality(self, quality: u8) -> Self {
+        Self { quality, ..self }
+    }
+}
+
+// RGBA pixels borrowed from wherever they were rendered, so encoding never needs its
+// own copy of the page.
+pub type RgbaView<'a> = image::ImageBuffer<image::Rgba<u8>, &'a [u8]>;
+
+pub fn view(image: &RgbaImage) -> RgbaView<'_> {
+    RgbaView::from_raw(image.width(), image.height(), image.as_raw().as_slice())
+        .expect("an RgbaImage holds exactly its own pixels")
+}
+
+// 8-bit samples scaled to the full 16-bit range, in the byte order `to_bytes` gives.
+pub fn widen(samples: &[u8], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&sample| to_bytes(u16::from(sample) * 257))
+        .collect()
+}
+
+// RGB samples of the page with transparency flattened onto white paper, for outputs
+// without an alpha channel.
+pub(crate) fn flatten_onto_white(page: &RgbaView) -> Vec<u8> {
+    page.pixels()
+        .flat_map(|pixel| {
+            let [red, green, blue, alpha] = pixel.0.map(u32::from);
+            [red, green, blue].map(|c| ((c * alpha + 255 * (255 - alpha)) / 255) as u8)
+        })
+        .collect()
+}
+
+pub fn output_extension(format: &str) -> &'static str {
+    match format.to_lowercase().as_str() {
+        "png" => "png",
+        "webp" => "webp",
+        "tif" | "tiff" => "tiff",
+        "svg" => "svg",
+        _ => "jpg",
+    }
+}
+
+pub fn encode_image<W: Write + Seek>(
+    image: &RgbaView,
+    writer: &mut W,
+    settings: &EncodeSettings,
+) -> Result<(), ConvertError> {
+    let (width, height) = image.dimensions();
+    let exif_failed = |e: image::error::UnsupportedError| ConvertError::EncodeFailed {
+        message: e.to_string(),
+    };
+    match settings.ext {
+        // JPEG has no alpha channel
+        "jpg" => {
+            let mut encoder =
+                image::codecs::jpeg::JpegEncoder::new_with_quality(writer, settings.quality);
+            if let Some(exif) = settings.exif {
+                encoder
+                    .set_exif_metadata(exif.to_vec())
+                    .map_err(exif_failed)?;
+            }
+            let rgb = flatten_onto_white(image);
+            encoder
+                .write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+                .map_err(ConvertError::encode)
+        }
+        "webp" => {
+            let encoder = webp::Encoder::from_rgba(image.as_raw(), width, height);
+            let memory = if settings.lossless {
+                encoder.encode_lossless()
+            } else {
+                encoder.encode(f32::from(settings.quality))
+            };
+            let written = match settings.exif {
+                Some(exif) => {
+                    writer.write_all(&metadata::webp_with_exif(&memory, exif, width, height))
+                }
+                None => writer.write_all(&memory),
+            };
+            written.map_err(|e| ConvertError::EncodeFailed {
+                message: e.to_string(),
+            })
+        }
+        "tiff" => write_tiff(image, writer, settings.cmyk, settings.sixteen_bit),
+        _ => {
+            let mut encoder = image::codecs::png::PngEncoder::new(writer);
+            if let Some(exif) = settings.exif {
+                encoder
+                    .set_exif_metadata(exif.to_vec())
+                    .map_err(exif_failed)?;
+            }
+            if settings.sixteen_bit {
+                let samples = widen(image.as_raw(), u16::to_ne_bytes);
+                return encoder
+                    .write_image(&samples, width, height, ExtendedColorType::Rgba16)
+                    .map_err(ConvertError::encode);
+            }
+            encoder
+                .write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+                .map_err(ConvertError::encode)
+        }
+    }
+}
+
+fn write_tiff<W: Write + Seek>(
+    image: &RgbaView,
+    writer: &mut W,
+    cmyk: Option<&CmykConversion>,
+    sixteen_bit: bool,
+) -> Result<(), ConvertError> {
+    use tiff::encoder::{colortype, TiffEncoder};
+    let (width, height) = image.dimensions();
+    let tiff_failed = |e: tiff::TiffError| ConvertError::EncodeFailed {
+        message: e.to_string(),
+    };
+    let mut encoder = TiffEncoder::new(writer).map_err(tiff_failed)?;
+    let Some(cmyk) = cmyk else {
+        if sixteen_bit {
+            let samples: Vec<u16> = image.as_raw().iter().map(|&s| u16::from(s) * 257).collect();
+            return encoder
+                .write_image::<colortype::RGBA16>(width, height, &samples)
+                .map_err(tiff_failed);
+        }
+        return encoder
+            .write_image::<colortype::RGBA8>(width, height, image.as_raw())
+            .map_err(tiff_failed);
+    };
+    let samples = cmyk.convert(image)?;
+    let mut tiff = encoder
+        .new_image::<colortype::CMYK8>(width, height)
+        .map_err(tiff_failed)?;
+    if let Some(profile) = cmyk.profile() {
+        tiff.encoder()
+            .write_tag(tiff::tags::Tag::IccProfile, profile)
+            .map_err(tiff_failed)?;
+    }
+    tiff.write_data(&samples).map_err(tiff_failed)
+}
+
+fn write_image(
+    image: &RgbaView,
+    path: &Path,
+    settings: &EncodeSettings,
+) -> Result<(), ConvertError> {
+    let file = std::fs::File::create(path).map_err(|e| ConvertError::io(path, e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    encode_image(image, &mut writer, settings)?;
+    let file = writer
+        .into_inner()
+        .map_err(|e| ConvertError::io(path, e.into_error()))?;
+    file.sync_all().map_err(|e| ConvertError::io(path, e))
+}
+
+// Binary-searches the highest quality (up to the configured one) whose encoding
+// fits within `max_bytes`.
+fn encode_within(
+    image: &RgbaView,
+    settings: &EncodeSettings,
+    max_bytes: u64,
+) -> Result<Vec<u8>, ConvertError> {
+    let (mut lo, mut hi) = (1u8, settings.quality.max(1));
+    let mut best = None;
+    while lo <= hi {
+        let mid = lo + (hi - lo) / 2;
+        let mut buffer = Cursor::new(Vec::new());
+        encode_image(image, &mut buffer, &settings.with_quality(mid))?;
+        let bytes = buffer.into_inner();
+        if bytes.len() as u64 <= max_bytes {
+            best = Some(bytes);
+            lo = mid + 1;
+        } else if mid == 1 {
+            break;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    best.ok_or_else(|| ConvertError::EncodeFailed {
+        message: format!("Page does not fit in {} bytes even at quality 1", max_bytes),
+    })
+}
+
+fn write_bytes(path: &Path, bytes: &[u8]) -> Result<(), ConvertError> {
+    let mut file = std::fs::File::create(path).map_err(|e| ConvertError::io(path, e))?;
+    file.write_all(bytes)
+        .map_err(|e| ConvertError::io(path, e))?;
+    file.sync_all().map_err(|e| ConvertError::io(path, e))
+}
+
+// Encodes into memory, honouring the per-page size limit like `save_image` does.
+pub fn encode_to_vec(image: &RgbaView, settings: &EncodeSettings) -> Result<Vec<u8>, ConvertError> {
+    let bytes = match settings.max_bytes.filter(|_| settings.is_lossy()) {
+        Some(limit) => encode_within(image, settings, limit)?,
+        None => {
+            let mut buffer = Cursor::new(Vec::new());
+            encode_image(image, &mut buffer, settings)?;
+            buffer.into_inner()
+        }
+    };
+    Ok(match settings.strip_metadata {
+        true => metadata::strip(bytes, settings.ext),
+        false => bytes,
+    })
+}
+
+// Hidden sibling an output is written to before being renamed into place.
+pub fn part_path(out_path: &Path) -> PathBuf {
+    let file_name = out_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    out_path.with_file_name(format!(".{}.part", file_name))
+}
+
+// Writes into a hidden sibling file and renames it into place, so an interrupted
+// run never leaves a truncated image under the final name.
+fn write_atomically(
+    out_path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), ConvertError>,
+) -> Result<(), ConvertError> {
+    let tmp_path = part_path(out_path);
+
+    let res = write(&tmp_path).and_then(|_| {
+        std::fs::rename(&tmp_path, out_path).map_err(|e| ConvertError::io(out_path, e))
+    });
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+pub fn save_image(
+    image: &RgbaView,
+    out_path: &Path,
+    settings: &EncodeSettings,
+) -> Result<(), ConvertError> {
+    write_atomically(out_path, |tmp_path| {
+        match settings.max_bytes.filter(|_| settings.is_lossy()) {
+            // Stripping works on the encoded bytes
+            _ if settings.strip_metadata => {
+                encode_to_vec(image, settings).and_then(|bytes| write_bytes(tmp_path, &bytes))
+            }
+            Some(limit) => encode_within(image, settings, limit)
+                .and_then(|bytes| write_bytes(tmp_path, &bytes)),
+            None => write_image(image, tmp_path, settings),
+        }
+    })
+}
+
+// Saves already encoded output, e.g. a page taken from the render cache.
+pub fn save_bytes(bytes: &[u8], out_path: &Path) -> Result<(), ConvertError> {
+    write_atomically(out_path, |tmp_path| write_bytes(tmp_path, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(ext: &'static str) -> EncodeSettings<'static> {
+        EncodeSettings {
+            ext,
+            quality: 90,
+            lossless: false,
+            max_bytes: None,
+            exif: None,
+            strip_metadata: false,
+            cmyk: None,
+            sixteen_bit: false,
+        }
+    }
+
+    #[test]
+    fn encodes_jpeg_with_transparency_on_white() {
+        // Left half opaque red, right half fully transparent
+        let page = RgbaImage::from_fn(16, 16, |x, _| match x < 8 {
+            true => image::Rgba([255, 0, 0, 255]),
+            false => image::Rgba([0, 0, 0, 0]),
+        });
+        let bytes = encode_to_vec(&view(&page), &settings("jpg")).unwrap();
+        let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::Jpeg)
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(decoded.dimensions(), (16, 16));
+        let [r, g, b] = decoded.get_pixel(2, 8).0;
+        assert!(r > 230 && g < 30 && b < 30, "{:?}", (r, g, b));
+        let [r, g, b] = decoded.get_pixel(13, 8).0;
+        assert!(r > 230 && g > 230 && b > 230, "{:?}", (r, g, b));
+    }
+
+    #[test]
+    fn flattens_partial_transparency() {
+        let page = RgbaImage::from_raw(2, 1, vec![0, 0, 0, 128, 10, 20, 30, 255]).unwrap();
+        assert_eq!(
+            flatten_onto_white(&view(&page)),
+            vec![127, 127, 127, 10, 20, 30]
+        );
+    }
+
+    #[test]
+    fn fails_when_no_quality_fits() {
+        // Noise barely compresses, so no JPEG of it gets near 100 bytes
+        let page = RgbaImage::from_fn(64, 64, |x, y| {
+            let v = ((x * 7919 + y * 104729) ^ (x * y * 31)) as u8;
+            image::Rgba([v, v.wrapping_mul(3), v.wrapping_mul(7), 255])
+        });
+        let result = encode_within(&view(&page), &settings("jpg"), 100);
+        assert!(matches!(result, Err(ConvertError::EncodeFailed { .. })));
+    }
+
+    #[test]
+    fn keeps_configured_quality_when_it_fits() {
+        let page = RgbaImage::from_pixel(32, 32, image::Rgba([40, 120, 200, 255]));
+        let settings = settings("jpg");
+        let mut full = Cursor::new(Vec::new());
+        encode_image(&view(&page), &mut full, &settings).unwrap();
+        let fitted = encode_within(&view(&page), &settings, 1 << 20).unwrap();
+        assert_eq!(fitted, full.into_inner());
+    }
+}
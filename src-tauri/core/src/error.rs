@@ -0,0 +1,227 @@
+use crate::i18n;
+use pdfium_render::prelude::{PdfiumError, PdfiumInternalError};
+use std::fmt;
+use std::path::Path;
+
+// Serialized as `{ "code": "pdf_open", "context": { ... } }` so the frontend can
+// branch on the code and localize the message itself.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "code", content = "context", rename_all = "snake_case")]
+pub enum ConvertError {
+    LibraryLoad { message: String },
+    PdfOpen { path: String, message: String },
+    PasswordRequired { path: String },
+    InvalidPageRange { range: String },
+    InvalidOption { option: String, reason: Reason },
+    InvalidPattern { pattern: String, message: String },
+    InvalidJobSpec { path: String, message: String },
+    InvalidDeepLink { url: String, message: String },
+    RenderFailed { page: usize, message: String },
+    EncodeFailed { message: String },
+    IoFailed { path: String, message: String },
+    DownloadFailed { url: String, message: String },
+    HookFailed { path: String, message: String },
+    WebhookFailed { url: String, message: String },
+    OutputExists { path: String },
+    UnknownJob { job_id: u64 },
+    UnknownHistoryEntry { id: u64 },
+    UnknownPreset { name: String },
+    QueueBusy,
+    Cancelled,
+    WatchFailed { path: String, message: String },
+    NoSavedSettings,
+    MemoryLimitExceeded { needed_mb: u64, limit_mb: u64 },
+    ClipboardFailed { message: String },
+    PrintFailed { message: String },
+    LayoutFailed { message: String },
+    InvalidColorProfile { path: String, message: String },
+    BackendUnavailable { backend: String, message: String },
+    Internal { message: String },
+}
+
+// Why an option was rejected. Serialized as `{ "reason": "not_positive", ... }` with
+// its parameters, so each one has a message key of its own: `invalid_option.<reason>`.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Reason {
+    NotPositive { value: String },
+    OutOfRange { value: String, max: String },
+    NothingLeft,
+    MupdfConflict { with: String },
+    SvgConflict { with: String },
+    PopplerConflict { with: String },
+    UnknownPaperSize { size: String },
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NotPositive { value } => write!(f, "{} is not above 0", value),
+            Reason::OutOfRange { value, max } => {
+                write!(f, "{} is outside 0 to {}", value, max)
+            }
+            Reason::NothingLeft => write!(f, "leaves nothing of the page"),
+            Reason::MupdfConflict { with } => {
+                write!(f, "MuPDF can't be combined with {}", with)
+            }
+            Reason::SvgConflict { with } => write!(f, "SVG can't be combined with {}", with),
+            Reason::PopplerConflict { with } => {
+                write!(f, "poppler can't be combined with {}", with)
+            }
+            Reason::UnknownPaperSize { size } => write!(f, "unknown paper size '{}'", size),
+        }
+    }
+}
+
+impl ConvertError {
+    pub fn pdf_open(path: &str, err: PdfiumError) -> Self {
+        match err {
+            PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+                ConvertError::PasswordRequired { path: path.into() }
+            }
+            err => ConvertError::PdfOpen {
+                path: path.into(),
+                message: err.to_string(),
+            },
+        }
+    }
+
+    pub fn io(path: &Path, err: std::io::Error) -> Self {
+        ConvertError::IoFailed {
+            path: path.to_string_lossy().to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn encode(err: image::ImageError) -> Self {
+        ConvertError::EncodeFailed {
+            message: err.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConvertError::LibraryLoad { .. } => "library_load",
+            ConvertError::PdfOpen { .. } => "pdf_open",
+            ConvertError::PasswordRequired { .. } => "password_required",
+            ConvertError::InvalidPageRange { .. } => "invalid_page_range",
+            ConvertError::InvalidOption { .. } => "invalid_option",
+            ConvertError::InvalidPattern { .. } => "invalid_pattern",
+            ConvertError::InvalidJobSpec { .. } => "invalid_job_spec",
+            ConvertError::InvalidDeepLink { .. } => "invalid_deep_link",
+            ConvertError::RenderFailed { .. } => "render_failed",
+            ConvertError::EncodeFailed { .. } => "encode_failed",
+            ConvertError::IoFailed { .. } => "io_failed",
+            ConvertError::DownloadFailed { .. } => "download_failed",
+            ConvertError::HookFailed { .. } => "hook_failed",
+            ConvertError::WebhookFailed { .. } => "webhook_failed",
+            ConvertError::OutputExists { .. } => "output_exists",
+            ConvertError::UnknownJob { .. } => "unknown_job",
+            ConvertError::UnknownHistoryEntry { .. } => "unknown_history_entry",
+            ConvertError::UnknownPreset { .. } => "unknown_preset",
+            ConvertError::QueueBusy => "queue_busy",
+            ConvertError::Cancelled => "cancelled",
+            ConvertError::WatchFailed { .. } => "watch_failed",
+            ConvertError::NoSavedSettings => "no_saved_settings",
+            ConvertError::MemoryLimitExceeded { .. } => "memory_limit_exceeded",
+            ConvertError::ClipboardFailed { .. } => "clipboard_failed",
+            ConvertError::PrintFailed { .. } => "print_failed",
+            ConvertError::LayoutFailed { .. } => "layout_failed",
+            ConvertError::InvalidColorProfile { .. } => "invalid_color_profile",
+            ConvertError::BackendUnavailable { .. } => "backend_unavailable",
+            ConvertError::Internal { .. } => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::LibraryLoad { message } => write!(
+                f,
+                "Failed to load PDFium library: {}. \n\nTips: \n1. Install libpdfium (e.g., 'sudo apt install libpdfium-dev' on Linux). \n2. Or download the shared library from GitHub and place it next to the app executable. \n3. Or set PDFIUM_LIBRARY_PATH to the library file or its folder.",
+                message
+            ),
+            ConvertError::PdfOpen { message, .. } => write!(f, "Load PDF error: {}", message),
+            ConvertError::PasswordRequired { .. } => write!(f, "PDF is password protected"),
+            ConvertError::InvalidPageRange { .. } => write!(f, "No valid pages selected in range"),
+            ConvertError::InvalidOption { option, reason } => {
+                write!(f, "Invalid {}: {}", option, reason)
+            }
+            ConvertError::InvalidPattern { pattern, message } => {
+                write!(f, "Invalid pattern '{}': {}", pattern, message)
+            }
+            ConvertError::InvalidJobSpec { path, message } => {
+                write!(f, "Invalid job file {}: {}", path, message)
+            }
+            ConvertError::InvalidDeepLink { url, message } => {
+                write!(f, "Invalid link {}: {}", url, message)
+            }
+            ConvertError::RenderFailed { page, message } => {
+                write!(f, "Render error on page {}: {}", page, message)
+            }
+            ConvertError::EncodeFailed { message } => write!(f, "Encode error: {}", message),
+            ConvertError::IoFailed { path, message } => write!(f, "Save error: {}: {}", path, message),
+            ConvertError::DownloadFailed { url, message } => {
+                write!(f, "Download error: {}: {}", url, message)
+            }
+            ConvertError::HookFailed { path, message } => {
+                write!(f, "Post-processing command failed for {}: {}", path, message)
+            }
+            ConvertError::WebhookFailed { url, message } => {
+                write!(f, "Webhook {} failed: {}", url, message)
+            }
+            ConvertError::OutputExists { path } => write!(f, "Output already exists: {}", path),
+            ConvertError::UnknownJob { job_id } => write!(f, "Unknown job: {}", job_id),
+            ConvertError::UnknownHistoryEntry { id } => write!(f, "Unknown history entry: {}", id),
+            ConvertError::UnknownPreset { name } => write!(f, "Unknown preset: {}", name),
+            ConvertError::QueueBusy => write!(f, "The queue is already being processed"),
+            ConvertError::Cancelled => write!(f, "Cancelled"),
+            ConvertError::WatchFailed { path, message } => {
+                write!(f, "Cannot watch folder {}: {}", path, message)
+            }
+            ConvertError::NoSavedSettings => write!(f, "No saved settings to convert with"),
+            ConvertError::MemoryLimitExceeded {
+                needed_mb,
+                limit_mb,
+            } => write!(
+                f,
+                "Rendering needs about {} MB, over the {} MB memory limit; lower the scale or raise the limit",
+                needed_mb, limit_mb
+            ),
+            ConvertError::ClipboardFailed { message } => {
+                write!(f, "Cannot copy to the clipboard: {}", message)
+            }
+            ConvertError::PrintFailed { message } => write!(f, "Cannot print: {}", message),
+            ConvertError::LayoutFailed { message } => {
+                write!(f, "Cannot lay out pages: {}", message)
+            }
+            ConvertError::InvalidColorProfile { path, message } => {
+                write!(f, "Invalid color profile {}: {}", path, message)
+            }
+            ConvertError::BackendUnavailable { backend, message } => {
+                write!(f, "Cannot render with {}: {}", backend, message)
+            }
+            ConvertError::Internal { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+// What commands return to the frontend: the typed error, whose code and context are a
+// message key and its parameters, plus the message in the configured locale for
+// callers that don't localize themselves.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ErrorPayload {
+    #[serde(flatten)]
+    pub error: ConvertError,
+    pub message: String,
+}
+
+impl From<ConvertError> for ErrorPayload {
+    fn from(error: ConvertError) -> Self {
+        let message = i18n::error_message(&error);
+        ErrorPayload { error, message }
+    }
+}
@@ -0,0 +1,59 @@
+// Running the command-line renderers MuPDF and poppler come with, for what pdfium
+// can't do.
+
+use crate::error::ConvertError;
+use std::process::Command;
+
+// A tool's version, e.g. "1.23.10"; `None` when it isn't on the PATH. Both MuPDF and
+// poppler print "<tool> version <number>", to stderr.
+pub fn version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("-v").output().ok()?;
+    let text = [output.stdout, output.stderr].concat();
+    String::from_utf8_lossy(&text)
+        .split_whitespace()
+        .skip_while(|word| *word != "version")
+        .nth(1)
+        .map(str::to_string)
+}
+
+// Runs a renderer for a page (0-based) and returns what it wrote to stdout.
+pub fn run(command: &mut Command, page_index: usize) -> Result<Vec<u8>, ConvertError> {
+    let failed = |message: String| ConvertError::RenderFailed {
+        page: page_index + 1,
+        message,
+    };
+    let output = command.output().map_err(|e| failed(e.to_string()))?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(failed(if stderr.is_empty() {
+        output.status.to_string()
+    } else {
+        stderr
+    }))
+}
+
+// The size and RGB samples of a binary PPM, which both tools write.
+pub fn ppm(bytes: &[u8]) -> Option<(usize, usize, &[u8])> {
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while fields.len() < 4 {
+        while bytes.get(i)?.is_ascii_whitespace() {
+            i += 1;
+        }
+        let start = i;
+        while !bytes.get(i)?.is_ascii_whitespace() {
+            i += 1;
+        }
+        fields.push(&bytes[start..i]);
+    }
+    let number = |field: &[u8]| std::str::from_utf8(field).ok()?.parse::<usize>().ok();
+    if fields[0] != b"P6" || number(fields[3]) != Some(255) {
+        return None;
+    }
+    let (width, height) = (number(fields[1])?, number(fields[2])?);
+    // A single whitespace byte ends the header
+    let samples = bytes.get(i + 1..)?;
+    (samples.len() >= width * height * 3).then_some((width, height, samples))
+}
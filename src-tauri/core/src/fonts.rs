@@ -0,0 +1,444 @@
+use pdfium_render::prelude::{PdfDocument, Pdfium, FPDF_BOOL, FPDF_SYSFONTINFO};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{c_char, c_int, c_uchar, c_uint, c_ulong, c_void, CStr, CString};
+use std::path::Path;
+
+// Fonts every PDF reader must supply itself; pdfium ships replacements for them.
+const STANDARD_FONTS: [&str; 14] = [
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+// Drops the `ABCDEF+` tag that marks a subset.
+fn base_name(name: &str) -> &str {
+    match name.split_once('+') {
+        Some((tag, rest)) if tag.len() == 6 && tag.chars().all(|c| c.is_ascii_uppercase()) => rest,
+        _ => name,
+    }
+}
+
+// Fonts used on the given pages that the document doesn't embed and that aren't
+// standard, so pdfium renders them with whatever system font it substitutes.
+pub fn missing_fonts(document: &PdfDocument, page_indices: &[usize]) -> Vec<String> {
+    let mut missing = BTreeSet::new();
+    for &page_index in page_indices {
+        let Ok(page) = document.pages().get(page_index as u16) else {
+            continue;
+        };
+        for font in page.fonts() {
+            if font.is_embedded().unwrap_or(true) {
+                continue;
+            }
+            let name = font.name();
+            let name = base_name(&name);
+            if !name.is_empty() && !STANDARD_FONTS.contains(&name) {
+                missing.insert(name.to_string());
+            }
+        }
+    }
+    missing.into_iter().collect()
+}
+
+// Replacement fonts for documents that don't embed theirs.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FontSubstitution {
+    // e.g. "Helvetica" -> "Arial"; matches PDF font names starting with the key
+    #[serde(default)]
+    pub map: BTreeMap<String, String>,
+    // TrueType/OpenType files offered to pdfium besides the system fonts
+    #[serde(default)]
+    pub fonts_dir: Option<String>,
+}
+
+impl FontSubstitution {
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty() && self.fonts_dir.is_none()
+    }
+}
+
+// Lowercase letters and digits only, so "Arial,Bold", "Arial-Bold" and "arial bold"
+// compare equal.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// The map with normalized names, longest first so the most specific name wins.
+fn normalized_map(substitution: &FontSubstitution) -> Vec<(String, String)> {
+    let mut map: Vec<(String, String)> = substitution
+        .map
+        .iter()
+        .map(|(name, replacement)| (normalize(name), replacement.clone()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect();
+    map.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    map
+}
+
+struct ExtraFont {
+    names: Vec<String>, // normalized family and PostScript names
+    face: CString,
+    bold: bool,
+    italic: bool,
+    data: Vec<u8>,
+}
+
+fn load_extra_fonts(dir: &Path) -> Vec<ExtraFont> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let data = std::fs::read(entry.path()).ok()?;
+            let face = ttf_parser::Face::parse(&data, 0).ok()?;
+            let names: Vec<String> = face
+                .names()
+                .into_iter()
+                .filter(|name| {
+                    [
+                        ttf_parser::name_id::FAMILY,
+                        ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+                        ttf_parser::name_id::POST_SCRIPT_NAME,
+                    ]
+                    .contains(&name.name_id)
+                })
+                .filter_map(|name| name.to_string())
+                .collect();
+            let family = names.first()?.clone();
+            Some(ExtraFont {
+                names: names.iter().map(|name| normalize(name)).collect(),
+                face: CString::new(family).ok()?,
+                bold: face.is_bold(),
+                italic: face.is_italic(),
+                data,
+            })
+        })
+        .collect()
+}
+
+// Wraps pdfium's own font lookup: names are rewritten through the map first, and fonts
+// from the extra directory win over system fonts of the same name. `base` comes first
+// so pdfium's `pThis` points at the whole struct.
+#[repr(C)]
+struct SubstitutingFontInfo {
+    base: FPDF_SYSFONTINFO,
+    system: *mut FPDF_SYSFONTINFO,
+    map: Vec<(String, String)>, // normalized name, replacement; longest first
+    extra: Vec<ExtraFont>,
+}
+
+impl SubstitutingFontInfo {
+    unsafe fn from_this<'a>(this: *mut FPDF_SYSFONTINFO) -> &'a SubstitutingFontInfo {
+        &*(this as *const SubstitutingFontInfo)
+    }
+
+    fn substitute(&self, face: &str) -> String {
+        let normalized = normalize(face);
+        self.map
+            .iter()
+            .find(|(name, _)| normalized.starts_with(name.as_str()))
+            .map_or_else(|| face.to_string(), |(_, replacement)| replacement.clone())
+    }
+
+    fn extra_font(&self, face: &str, bold: bool, italic: bool) -> Option<&ExtraFont> {
+        let face = normalize(face);
+        let candidates: Vec<&ExtraFont> = self
+            .extra
+            .iter()
+            .filter(|font| {
+                font.names
+                    .iter()
+                    .any(|name| face.starts_with(name.as_str()))
+            })
+            .collect();
+        candidates
+            .iter()
+            .find(|font| font.bold == bold && font.italic == italic)
+            .or_else(|| candidates.iter().find(|font| font.bold == bold))
+            .or(candidates.first())
+            .copied()
+    }
+
+    fn own(&self, font: *mut c_void) -> Option<&ExtraFont> {
+        self.extra
+            .iter()
+            .find(|extra| std::ptr::eq(*extra, font as *const ExtraFont))
+    }
+}
+
+unsafe extern "C" fn release(_this: *mut FPDF_SYSFONTINFO) {}
+
+unsafe extern "C" fn enum_fonts(this: *mut FPDF_SYSFONTINFO, mapper: *mut c_void) {
+    let info = SubstitutingFontInfo::from_this(this);
+    if let Some(enum_fonts) = info.system.as_ref().and_then(|system| system.EnumFonts) {
+        enum_fonts(info.system, mapper);
+    }
+}
+
+unsafe extern "C" fn map_font(
+    this: *mut FPDF_SYSFONTINFO,
+    weight: c_int,
+    italic: FPDF_BOOL,
+    charset: c_int,
+    pitch_family: c_int,
+    face: *const c_char,
+    exact: *mut FPDF_BOOL,
+) -> *mut c_void {
+    let info = SubstitutingFontInfo::from_this(this);
+    let original = CStr::from_ptr(face).to_string_lossy();
+    let substituted = info.substitute(&original);
+    if let Some(font) = info.extra_font(&substituted, weight >= 600, italic != 0) {
+        return font as *const ExtraFont as *mut c_void;
+    }
+    let Some(map) = info.system.as_ref().and_then(|system| system.MapFont) else {
+        return std::ptr::null_mut();
+    };
+    if substituted == original {
+        return map(
+            info.system,
+            weight,
+            italic,
+            charset,
+            pitch_family,
+            face,
+            exact,
+        );
+    }
+    match CString::new(substituted) {
+        Ok(face) => map(
+            info.system,
+            weight,
+            italic,
+            charset,
+            pitch_family,
+            face.as_ptr(),
+            exact,
+        ),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn get_font(this: *mut FPDF_SYSFONTINFO, face: *const c_char) -> *mut c_void {
+    let info = SubstitutingFontInfo::from_this(this);
+    let face = CString::new(info.substitute(&CStr::from_ptr(face).to_string_lossy()));
+    let Ok(face) = face else {
+        return std::ptr::null_mut();
+    };
+    if let Some(font) = info.extra_font(&face.to_string_lossy(), false, false) {
+        return font as *const ExtraFont as *mut c_void;
+    }
+    match info.system.as_ref().and_then(|system| system.GetFont) {
+        Some(get_font) => get_font(info.system, face.as_ptr()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+// Copies `data` into pdfium's buffer when it fits; either way returns the size needed.
+unsafe fn copy_out(data: &[u8], buffer: *mut u8, buf_size: c_ulong) -> c_ulong {
+    if !buffer.is_null() && data.len() as c_ulong <= buf_size {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+    }
+    data.len() as c_ulong
+}
+
+unsafe extern "C" fn get_font_data(
+    this: *mut FPDF_SYSFONTINFO,
+    font: *mut c_void,
+    table: c_uint,
+    buffer: *mut c_uchar,
+    buf_size: c_ulong,
+) -> c_ulong {
+    let info = SubstitutingFontInfo::from_this(this);
+    match info.own(font) {
+        Some(extra) if table == 0 => copy_out(&extra.data, buffer, buf_size),
+        Some(extra) => ttf_parser::RawFace::parse(&extra.data, 0)
+            .ok()
+            .and_then(|face| face.table(ttf_parser::Tag(table)))
+            .map_or(0, |data| copy_out(data, buffer, buf_size)),
+        None => match info.system.as_ref().and_then(|system| system.GetFontData) {
+            Some(get_font_data) => get_font_data(info.system, font, table, buffer, buf_size),
+            None => 0,
+        },
+    }
+}
+
+unsafe extern "C" fn get_face_name(
+    this: *mut FPDF_SYSFONTINFO,
+    font: *mut c_void,
+    buffer: *mut c_char,
+    buf_size: c_ulong,
+) -> c_ulong {
+    let info = SubstitutingFontInfo::from_this(this);
+    match info.own(font) {
+        Some(extra) => copy_out(extra.face.as_bytes_with_nul(), buffer.cast(), buf_size),
+        None => match info.system.as_ref().and_then(|system| system.GetFaceName) {
+            Some(get_face_name) => get_face_name(info.system, font, buffer, buf_size),
+            None => 0,
+        },
+    }
+}
+
+unsafe extern "C" fn get_font_charset(this: *mut FPDF_SYSFONTINFO, font: *mut c_void) -> c_int {
+    let info = SubstitutingFontInfo::from_this(this);
+    match info.own(font) {
+        Some(_) => 0, // ANSI
+        None => match info
+            .system
+            .as_ref()
+            .and_then(|system| system.GetFontCharset)
+        {
+            Some(get_font_charset) => get_font_charset(info.system, font),
+            None => 0,
+        },
+    }
+}
+
+unsafe extern "C" fn delete_font(this: *mut FPDF_SYSFONTINFO, font: *mut c_void) {
+    let info = SubstitutingFontInfo::from_this(this);
+    if info.own(font).is_none() {
+        if let Some(delete_font) = info.system.as_ref().and_then(|system| system.DeleteFont) {
+            delete_font(info.system, font);
+        }
+    }
+}
+
+// Hooks the substitutions into pdfium's font lookup. Must run before documents are
+// loaded; the hook lives as long as the process, like the pdfium binding itself.
+pub fn install_substitution(pdfium: &Pdfium, substitution: &FontSubstitution) {
+    if substitution.is_empty() {
+        return;
+    }
+    let bindings = pdfium.bindings();
+    let system = bindings.FPDF_GetDefaultSystemFontInfo();
+    let info = Box::new(SubstitutingFontInfo {
+        base: FPDF_SYSFONTINFO {
+            version: unsafe { system.as_ref() }.map_or(1, |system| system.version),
+            Release: Some(release),
+            EnumFonts: Some(enum_fonts),
+            MapFont: Some(map_font),
+            GetFont: Some(get_font),
+            GetFontData: Some(get_font_data),
+            GetFaceName: Some(get_face_name),
+            GetFontCharset: Some(get_font_charset),
+            DeleteFont: Some(delete_font),
+        },
+        system,
+        map: normalized_map(substitution),
+        extra: substitution
+            .fonts_dir
+            .as_deref()
+            .map(|dir| load_extra_fonts(Path::new(dir)))
+            .unwrap_or_default(),
+    });
+    bindings.FPDF_SetSystemFontInfo(Box::into_raw(info).cast());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(map: &[(&str, &str)], extra: Vec<ExtraFont>) -> SubstitutingFontInfo {
+        let substitution = FontSubstitution {
+            map: map
+                .iter()
+                .map(|(name, replacement)| (name.to_string(), replacement.to_string()))
+                .collect(),
+            fonts_dir: None,
+        };
+        SubstitutingFontInfo {
+            base: FPDF_SYSFONTINFO {
+                version: 1,
+                Release: None,
+                EnumFonts: None,
+                MapFont: None,
+                GetFont: None,
+                GetFontData: None,
+                GetFaceName: None,
+                GetFontCharset: None,
+                DeleteFont: None,
+            },
+            system: std::ptr::null_mut(),
+            map: normalized_map(&substitution),
+            extra,
+        }
+    }
+
+    fn extra(name: &str, bold: bool, italic: bool) -> ExtraFont {
+        ExtraFont {
+            names: vec![normalize(name)],
+            face: CString::new(name).unwrap(),
+            bold,
+            italic,
+            data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn strips_subset_tags() {
+        assert_eq!(base_name("ABCDEF+Calibri-Bold"), "Calibri-Bold");
+        assert_eq!(base_name("Calibri"), "Calibri");
+        // Only six uppercase letters make a tag
+        assert_eq!(base_name("abcdef+Calibri"), "abcdef+Calibri");
+        assert_eq!(base_name("ABC+Calibri"), "ABC+Calibri");
+    }
+
+    #[test]
+    fn normalizes_names() {
+        assert_eq!(normalize("Arial,Bold"), "arialbold");
+        assert_eq!(normalize("Arial-Bold"), "arialbold");
+        assert_eq!(normalize("arial bold"), "arialbold");
+        assert_eq!(normalize("Times New Roman PS MT"), "timesnewromanpsmt");
+        assert_eq!(normalize(" -,"), "");
+    }
+
+    #[test]
+    fn substitutes_the_most_specific_name() {
+        let info = info(
+            &[
+                ("Helvetica", "Arial"),
+                ("Helvetica-Narrow", "Arial Narrow"),
+                (" ", "Ignored"),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(info.map.len(), 2);
+        assert_eq!(info.substitute("Helvetica,Bold"), "Arial");
+        assert_eq!(info.substitute("HelveticaNarrow-Bold"), "Arial Narrow");
+        assert_eq!(info.substitute("Courier"), "Courier");
+    }
+
+    #[test]
+    fn picks_the_closest_extra_font() {
+        let info = info(
+            &[],
+            vec![
+                extra("Inter", false, false),
+                extra("Inter", true, false),
+                extra("Inter", true, true),
+            ],
+        );
+        let pick = |face, bold, italic| {
+            info.extra_font(face, bold, italic)
+                .map(|font| (font.bold, font.italic))
+        };
+        assert_eq!(pick("Inter-Bold", true, true), Some((true, true)));
+        assert_eq!(pick("Inter", true, false), Some((true, false)));
+        assert_eq!(pick("Inter", false, true), Some((false, false)));
+        assert_eq!(pick("Roboto", false, false), None);
+    }
+}
@@ -0,0 +1,195 @@
+// Translated text for what the backend shows users: error messages and status texts
+// such as notifications. Each text has a key plus named parameters; errors use their
+// code as the key and their context as the parameters. English falls back to the
+// errors' own `Display` text, so only other languages need templates here.
+
+use crate::error::{ConvertError, Reason};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    En,
+    Vi,
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+impl Locale {
+    // A language tag such as `vi-VN` or a POSIX locale such as `vi_VN.UTF-8`;
+    // languages without translations are `None`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.']).next()?.to_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "vi" => Some(Locale::Vi),
+            _ => None,
+        }
+    }
+
+    // The configured locale, or the system's when none is set or it isn't translated.
+    pub fn resolve(setting: Option<&str>) -> Self {
+        setting
+            .and_then(Locale::from_tag)
+            .or_else(|| {
+                ["LC_ALL", "LC_MESSAGES", "LANG"]
+                    .iter()
+                    .filter_map(|name| std::env::var(name).ok())
+                    .find(|value| !value.is_empty())
+                    .and_then(|value| Locale::from_tag(&value))
+            })
+            .unwrap_or_default()
+    }
+}
+
+// The locale messages are produced in from now on, for the whole process.
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::Vi,
+        _ => Locale::En,
+    }
+}
+
+fn template(locale: Locale, key: &str) -> Option<&'static str> {
+    let template = match (locale, key) {
+        (Locale::En, "notification.finished") => "Conversion finished",
+        (Locale::En, "notification.finished_with_errors") => "Conversion finished with errors",
+        (Locale::En, "notification.aborted") => "Conversion aborted",
+        (Locale::En, "notification.summary") => {
+            "{succeeded} succeeded, {failed} failed, {skipped} skipped"
+        }
+        (Locale::En, "tray.show") => "Show window",
+        (Locale::En, "tray.pause") => "Pause",
+        (Locale::En, "tray.resume") => "Resume",
+        (Locale::En, "tray.cancel") => "Cancel batch",
+        (Locale::En, "tray.open_output") => "Open output folder",
+        (Locale::En, "tray.quit") => "Quit",
+
+        (Locale::Vi, "notification.finished") => "Đã chuyển đổi xong",
+        (Locale::Vi, "notification.finished_with_errors") => "Đã chuyển đổi xong, có lỗi",
+        (Locale::Vi, "notification.aborted") => "Đã dừng chuyển đổi",
+        (Locale::Vi, "notification.summary") => {
+            "{succeeded} thành công, {failed} lỗi, {skipped} bỏ qua"
+        }
+        (Locale::Vi, "tray.show") => "Hiện cửa sổ",
+        (Locale::Vi, "tray.pause") => "Tạm dừng",
+        (Locale::Vi, "tray.resume") => "Tiếp tục",
+        (Locale::Vi, "tray.cancel") => "Hủy đợt chuyển đổi",
+        (Locale::Vi, "tray.open_output") => "Mở thư mục kết quả",
+        (Locale::Vi, "tray.quit") => "Thoát",
+        (Locale::Vi, "library_load") => {
+            "Không tải được thư viện PDFium: {message}. Hãy cài libpdfium, đặt thư viện cạnh \
+             tệp chạy của ứng dụng, hoặc đặt PDFIUM_LIBRARY_PATH."
+        }
+        (Locale::Vi, "pdf_open") => "Lỗi mở PDF: {message}",
+        (Locale::Vi, "password_required") => "PDF được bảo vệ bằng mật khẩu",
+        (Locale::Vi, "invalid_page_range") => "Khoảng trang không chọn được trang nào",
+        (Locale::Vi, "invalid_option") => "{option} không hợp lệ: {reason}",
+        (Locale::Vi, "invalid_option.not_positive") => "{value} không lớn hơn 0",
+        (Locale::Vi, "invalid_option.out_of_range") => "{value} nằm ngoài khoảng 0 đến {max}",
+        (Locale::Vi, "invalid_option.nothing_left") => "không còn lại gì của trang",
+        (Locale::Vi, "invalid_option.mupdf_conflict") => "MuPDF không dùng được cùng {with}",
+        (Locale::Vi, "invalid_option.svg_conflict") => "SVG không dùng được cùng {with}",
+        (Locale::Vi, "invalid_option.poppler_conflict") => "Poppler không dùng được cùng {with}",
+        (Locale::Vi, "invalid_option.unknown_paper_size") => "Khổ giấy '{size}' không xác định",
+        (Locale::Vi, "invalid_pattern") => "Mẫu '{pattern}' không hợp lệ: {message}",
+        (Locale::Vi, "invalid_job_spec") => "Tệp công việc {path} không hợp lệ: {message}",
+        (Locale::Vi, "invalid_deep_link") => "Liên kết {url} không hợp lệ: {message}",
+        (Locale::Vi, "render_failed") => "Lỗi kết xuất trang {page}: {message}",
+        (Locale::Vi, "encode_failed") => "Lỗi mã hóa ảnh: {message}",
+        (Locale::Vi, "io_failed") => "Lỗi lưu tệp: {path}: {message}",
+        (Locale::Vi, "download_failed") => "Lỗi tải xuống: {url}: {message}",
+        (Locale::Vi, "hook_failed") => "Lệnh xử lý sau thất bại với {path}: {message}",
+        (Locale::Vi, "webhook_failed") => "Webhook {url} thất bại: {message}",
+        (Locale::Vi, "output_exists") => "Tệp kết quả đã tồn tại: {path}",
+        (Locale::Vi, "unknown_job") => "Không có công việc: {job_id}",
+        (Locale::Vi, "unknown_history_entry") => "Không có mục lịch sử: {id}",
+        (Locale::Vi, "unknown_preset") => "Không có cấu hình mẫu: {name}",
+        (Locale::Vi, "queue_busy") => "Hàng đợi đang được xử lý",
+        (Locale::Vi, "cancelled") => "Đã huỷ",
+        (Locale::Vi, "watch_failed") => "Không theo dõi được thư mục {path}: {message}",
+        (Locale::Vi, "no_saved_settings") => "Chưa có cài đặt nào được lưu để chuyển đổi",
+        (Locale::Vi, "memory_limit_exceeded") => {
+            "Cần khoảng {needed_mb} MB để kết xuất, vượt giới hạn bộ nhớ {limit_mb} MB; hãy \
+             giảm tỉ lệ hoặc nâng giới hạn"
+        }
+        (Locale::Vi, "clipboard_failed") => "Không sao chép được vào bộ nhớ tạm: {message}",
+        (Locale::Vi, "print_failed") => "Không in được: {message}",
+        (Locale::Vi, "layout_failed") => "Không dàn trang được: {message}",
+        (Locale::Vi, "invalid_color_profile") => "Hồ sơ màu {path} không hợp lệ: {message}",
+        (Locale::Vi, "backend_unavailable") => "Không kết xuất được bằng {backend}: {message}",
+        (Locale::Vi, "internal") => "{message}",
+        _ => return None,
+    };
+    Some(template)
+}
+
+fn fill<K: AsRef<str>>(template: &str, params: &[(K, String)]) -> String {
+    params
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name.as_ref()), value)
+        })
+}
+
+// The text for `key` in the current locale, falling back to English and then to the
+// key itself.
+pub fn text(key: &str, params: &[(&str, String)]) -> String {
+    let template = template(locale(), key)
+        .or_else(|| template(Locale::En, key))
+        .unwrap_or(key);
+    fill(template, params)
+}
+
+// The fields of a serialized object, with values as plain text.
+fn fields(object: serde_json::Value) -> Vec<(String, String)> {
+    let serde_json::Value::Object(object) = object else {
+        return Vec::new();
+    };
+    object
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => (name, value),
+            value => (name, value.to_string()),
+        })
+        .collect()
+}
+
+// The error's parameters: its serialized context with values as plain text.
+pub fn params(error: &ConvertError) -> Vec<(String, String)> {
+    let Ok(serde_json::Value::Object(mut error)) = serde_json::to_value(error) else {
+        return Vec::new();
+    };
+    error.remove("context").map(fields).unwrap_or_default()
+}
+
+// Why an option was rejected, in the current locale.
+fn option_reason(reason: &Reason) -> String {
+    let params = serde_json::to_value(reason).map(fields).unwrap_or_default();
+    let key = params
+        .iter()
+        .find(|(name, _)| name == "reason")
+        .map(|(_, reason)| format!("invalid_option.{}", reason));
+    match key.and_then(|key| template(locale(), &key)) {
+        Some(template) => fill(template, &params),
+        None => reason.to_string(),
+    }
+}
+
+// The error's message in the current locale.
+pub fn error_message(error: &ConvertError) -> String {
+    let Some(template) = template(locale(), error.code()) else {
+        return error.to_string();
+    };
+    let mut params = params(error);
+    if let ConvertError::InvalidOption { reason, .. } = error {
+        params.retain(|(name, _)| name != "reason");
+        params.push(("reason".into(), option_reason(reason)));
+    }
+    fill(template, &params)
+}
@@ -0,0 +1,19 @@
+// Rendering PDF pages and encoding them as images, without any of the app around it:
+// binding pdfium, choosing pages, drawing them and writing the files.
+
+pub mod adjust;
+pub mod cmyk;
+pub mod encode;
+pub mod error;
+pub mod external;
+pub mod fonts;
+pub mod i18n;
+pub mod library;
+pub mod metadata;
+pub mod mupdf;
+pub mod pages;
+pub mod poppler;
+pub mod postprocess;
+pub mod render;
+pub mod stamp;
+pub mod svg;
@@ -0,0 +1,235 @@
+use crate::error::ConvertError;
+use crate::fonts::{self, FontSubstitution};
+use pdfium_render::prelude::Pdfium;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// A libpdfium file, or a directory holding one. Takes precedence over the saved setting.
+const PATH_ENV: &str = "PDFIUM_LIBRARY_PATH";
+
+// The directory fontconfig reads fonts.conf from.
+const FONTS_ENV: &str = "FONTCONFIG_PATH";
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+struct LibrarySettings {
+    library_path: Option<String>,
+    #[serde(default)]
+    fonts_dir: Option<String>,
+    #[serde(default)]
+    font_substitution: FontSubstitution,
+}
+
+// The pdfium binding made at startup, shared by every command. A failed bind is kept
+// too, so commands report the same load error instead of retrying the search. Pdfium
+// is bound at most once per process: dropping a `Pdfium` tears down the library's
+// global state, which a second binding and running batches would still be using.
+pub struct PdfiumLibrary {
+    bound: Mutex<Result<Arc<Pdfium>, ConvertError>>,
+    // Where the bound library was found
+    location: Mutex<Option<String>>,
+    settings: Mutex<LibrarySettings>,
+    store_path: PathBuf,
+    resource_dir: PathBuf,
+}
+
+fn bind_path(path: &Path) -> Result<(Pdfium, String), ConvertError> {
+    let file = if path.is_dir() {
+        Pdfium::pdfium_platform_library_name_at_path(path)
+    } else {
+        path.to_path_buf()
+    };
+    Pdfium::bind_to_library(&file)
+        .map(|bindings| (Pdfium::new(bindings), file.to_string_lossy().to_string()))
+        .map_err(|e| ConvertError::LibraryLoad {
+            message: format!("{}: {}", file.to_string_lossy(), e),
+        })
+}
+
+// An explicit path from the environment or the settings is used as is, so a wrong one
+// is reported rather than quietly replaced. Otherwise the copy bundled with the app
+// or placed next to it is preferred over the system library. Returns the library and
+// where it was found.
+pub fn bind(
+    configured: Option<&Path>,
+    resource_dir: &Path,
+) -> Result<(Pdfium, String), ConvertError> {
+    let bound = match std::env::var_os(PATH_ENV) {
+        Some(path) => bind_path(Path::new(&path)),
+        None => match configured {
+            Some(path) => bind_path(path),
+            None => {
+                let exe_dir = std::env::current_exe()
+                    .ok()
+                    .and_then(|exe| exe.parent().map(PathBuf::from));
+                [
+                    Some(resource_dir.join("binaries")),
+                    exe_dir,
+                    Some(PathBuf::from(".")),
+                ]
+                .into_iter()
+                .flatten()
+                .find_map(|dir| bind_path(&dir).ok())
+                .map_or_else(
+                    || {
+                        let name = Pdfium::pdfium_platform_library_name();
+                        let location = format!("system ({})", name.to_string_lossy());
+                        Pdfium::bind_to_system_library()
+                            .map(|bindings| (Pdfium::new(bindings), location))
+                            .map_err(|e| ConvertError::LibraryLoad {
+                                message: e.to_string(),
+                            })
+                    },
+                    Ok,
+                )
+            }
+        },
+    }?;
+
+    Ok(bound)
+}
+
+// Points fontconfig at the configured fonts directory, or at a `fonts` directory
+// bundled in the resources (holding its own fonts.conf). A FONTCONFIG_PATH the user
+// already set is left alone, as is the system config when neither exists. Must run
+// before the first render, since fontconfig reads its config once.
+pub fn configure_fonts(fonts_dir: Option<&Path>, resource_dir: &Path) -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(FONTS_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let dir = match fonts_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => Some(resource_dir.join("fonts")).filter(|dir| dir.is_dir())?,
+    };
+    std::env::set_var(FONTS_ENV, &dir);
+    Some(dir)
+}
+
+impl PdfiumLibrary {
+    // Binds using the library path saved in `store_path`, if any.
+    pub fn load(store_path: PathBuf, resource_dir: PathBuf) -> Self {
+        let settings: LibrarySettings = std::fs::read(&store_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        configure_fonts(settings.fonts_dir.as_deref().map(Path::new), &resource_dir);
+        let bound = bind(
+            settings.library_path.as_deref().map(Path::new),
+            &resource_dir,
+        );
+        if let Ok((pdfium, _)) = &bound {
+            fonts::install_substitution(pdfium, &settings.font_substitution);
+        }
+        let location = bound.as_ref().ok().map(|(_, location)| location.clone());
+        PdfiumLibrary {
+            bound: Mutex::new(bound.map(|(pdfium, _)| Arc::new(pdfium))),
+            location: Mutex::new(location),
+            settings: Mutex::new(settings),
+            store_path,
+            resource_dir,
+        }
+    }
+
+    pub fn get(&self) -> Result<Arc<Pdfium>, ConvertError> {
+        self.bound.lock().unwrap().clone()
+    }
+
+    // Version of the bound library, or why binding failed.
+    pub fn status(&self) -> Result<String, ConvertError> {
+        self.get()
+            .map(|pdfium| format!("{:?}", pdfium.bindings().version()))
+    }
+
+    pub fn location(&self) -> Option<String> {
+        self.location.lock().unwrap().clone()
+    }
+
+    pub fn configured_path(&self) -> Option<String> {
+        self.settings.lock().unwrap().library_path.clone()
+    }
+
+    pub fn fonts_dir(&self) -> Option<String> {
+        self.settings.lock().unwrap().fonts_dir.clone()
+    }
+
+    pub fn font_substitution(&self) -> FontSubstitution {
+        self.settings.lock().unwrap().font_substitution.clone()
+    }
+
+    fn save(&self, settings: LibrarySettings) -> Result<(), ConvertError> {
+        if let Some(parent) = self.store_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_vec(&settings).map_err(|e| ConvertError::Internal {
+            message: e.to_string(),
+        })?;
+        std::fs::write(&self.store_path, json)
+            .map_err(|e| ConvertError::io(&self.store_path, e))?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    // Sets the library path, or the default search for `None`. Without a library
+    // loaded yet, binds right away and returns its version; the path is only saved
+    // once it binds. With one loaded, the path is saved for the next launch and
+    // `None` is returned, as the loaded library can't be safely let go of.
+    pub fn set_path(&self, library_path: Option<String>) -> Result<Option<String>, ConvertError> {
+        let mut bound = self.bound.lock().unwrap();
+        let settings = LibrarySettings {
+            library_path,
+            ..self.settings.lock().unwrap().clone()
+        };
+        if bound.is_ok() {
+            if let Some(path) = settings.library_path.as_deref().map(Path::new) {
+                std::fs::metadata(path).map_err(|e| ConvertError::io(path, e))?;
+            }
+            self.save(settings)?;
+            return Ok(None);
+        }
+        let (pdfium, location) = bind(
+            settings.library_path.as_deref().map(Path::new),
+            &self.resource_dir,
+        )?;
+        fonts::install_substitution(&pdfium, &settings.font_substitution);
+        self.save(settings)?;
+        let status = format!("{:?}", pdfium.bindings().version());
+        *bound = Ok(Arc::new(pdfium));
+        *self.location.lock().unwrap() = Some(location);
+        Ok(Some(status))
+    }
+
+    // Saved for the next launch: fontconfig has already read its config by now.
+    pub fn set_fonts_dir(&self, fonts_dir: Option<String>) -> Result<(), ConvertError> {
+        if let Some(dir) = fonts_dir.as_deref().map(Path::new) {
+            if !dir.is_dir() {
+                return Err(ConvertError::io(
+                    dir,
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "not a directory"),
+                ));
+            }
+        }
+        let settings = LibrarySettings {
+            fonts_dir,
+            ..self.settings.lock().unwrap().clone()
+        };
+        self.save(settings)
+    }
+
+    // Installed when the library binds, before any document has picked its fonts.
+    // Returns whether that's still to come; once bound, pdfium keeps the fonts it has
+    // mapped, so the change waits for the next launch and `false` is returned.
+    pub fn set_font_substitution(
+        &self,
+        font_substitution: FontSubstitution,
+    ) -> Result<bool, ConvertError> {
+        let bound = self.bound.lock().unwrap();
+        if let Some(dir) = font_substitution.fonts_dir.as_deref().map(Path::new) {
+            std::fs::read_dir(dir).map_err(|e| ConvertError::io(dir, e))?;
+        }
+        let settings = LibrarySettings {
+            font_substitution,
+            ..self.settings.lock().unwrap().clone()
+        };
+        self.save(settings)?;
+        Ok(bound.is_err())
+    }
+}
@@ -0,0 +1,256 @@
+// Metadata of output images: EXIF that keeps a page image traceable to its source
+// document once it's separated from the PDF, or none at all for privacy.
+
+use crate::stamp;
+use pdfium_render::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// TIFF tags used, in the ascending order an IFD lists them.
+const DOCUMENT_NAME: u16 = 0x010D;
+const IMAGE_DESCRIPTION: u16 = 0x010E;
+const X_RESOLUTION: u16 = 0x011A;
+const Y_RESOLUTION: u16 = 0x011B;
+const RESOLUTION_UNIT: u16 = 0x0128;
+const PAGE_NUMBER: u16 = 0x0129;
+const SOFTWARE: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+const ARTIST: u16 = 0x013B;
+
+enum Value<'a> {
+    // Written as UTF-8, which EXIF readers accept in practice
+    Ascii(&'a str),
+    Short(&'a [u16]),
+    Rational(u32, u32),
+}
+
+// A little-endian TIFF structure holding a single IFD, which is what JPEG's APP1,
+// PNG's eXIf and WebP's EXIF chunks carry.
+fn tiff(entries: &[(u16, Value)]) -> Vec<u8> {
+    let ifd_len = 2 + entries.len() * 12 + 4;
+    let data_start = 8 + ifd_len;
+    let mut out = b"II*\0".to_vec();
+    out.extend(8u32.to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    let mut data = Vec::new();
+    for (tag, value) in entries {
+        let (kind, count, mut bytes): (u16, usize, Vec<u8>) = match value {
+            Value::Ascii(text) => {
+                let mut bytes: Vec<u8> = text.bytes().filter(|&b| b != 0).collect();
+                bytes.push(0);
+                (2, bytes.len(), bytes)
+            }
+            Value::Short(values) => (
+                3,
+                values.len(),
+                values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            ),
+            Value::Rational(numerator, denominator) => (
+                5,
+                1,
+                [numerator.to_le_bytes(), denominator.to_le_bytes()].concat(),
+            ),
+        };
+        out.extend(tag.to_le_bytes());
+        out.extend(kind.to_le_bytes());
+        out.extend((count as u32).to_le_bytes());
+        // Values of up to four bytes sit in the entry itself, longer ones after the IFD
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            out.extend(bytes);
+        } else {
+            out.extend(((data_start + data.len()) as u32).to_le_bytes());
+            data.extend(bytes);
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+    out.extend(0u32.to_le_bytes());
+    out.extend(data);
+    out
+}
+
+// Now in UTC, the way EXIF writes dates: YYYY:MM:DD HH:MM:SS.
+fn exif_timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = stamp::civil_date((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// What's known about a document's outputs before any page is rendered.
+pub struct Provenance {
+    source: String,
+    title: Option<String>,
+    author: Option<String>,
+    timestamp: String,
+}
+
+impl Provenance {
+    pub fn of(document: &PdfDocument, source: &str) -> Self {
+        let tag = |tag| {
+            document
+                .metadata()
+                .get(tag)
+                .map(|tag| tag.value().trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Provenance {
+            source: source.to_string(),
+            title: tag(PdfDocumentMetadataTagType::Title),
+            author: tag(PdfDocumentMetadataTagType::Author),
+            timestamp: exif_timestamp(),
+        }
+    }
+
+    // EXIF for one page (1-based) of `total`, rendered at `scale`.
+    pub fn exif(&self, page: usize, total: usize, scale: f32) -> Vec<u8> {
+        let dpi = (scale * 72.0).round() as u32;
+        // TIFF counts pages from zero
+        let page_number = [
+            page.saturating_sub(1).min(u16::MAX as usize) as u16,
+            total.min(u16::MAX as usize) as u16,
+        ];
+        let mut entries = vec![(DOCUMENT_NAME, Value::Ascii(&self.source))];
+        if let Some(title) = &self.title {
+            entries.push((IMAGE_DESCRIPTION, Value::Ascii(title)));
+        }
+        entries.extend([
+            (X_RESOLUTION, Value::Rational(dpi, 1)),
+            (Y_RESOLUTION, Value::Rational(dpi, 1)),
+            // Inches
+            (RESOLUTION_UNIT, Value::Short(&[2])),
+            (PAGE_NUMBER, Value::Short(&page_number)),
+            (SOFTWARE, Value::Ascii("pdf-to-image")),
+            (DATE_TIME, Value::Ascii(&self.timestamp)),
+        ]);
+        if let Some(author) = &self.author {
+            entries.push((ARTIST, Value::Ascii(author)));
+        }
+        tiff(&entries)
+    }
+}
+
+// Adds an EXIF chunk to a WebP file. libwebp writes lossless and opaque images in
+// the simple format, which has no room for metadata, so those get the extended
+// format's VP8X header first.
+pub fn webp_with_exif(webp: &[u8], exif: &[u8], width: u32, height: u32) -> Vec<u8> {
+    const EXIF_FLAG: u8 = 0x08;
+    const ALPHA_FLAG: u8 = 0x10;
+    let Some(chunks) = webp.get(12..).filter(|_| webp.starts_with(b"RIFF")) else {
+        return webp.to_vec();
+    };
+    let mut out = b"RIFF\0\0\0\0WEBP".to_vec();
+    if chunks.starts_with(b"VP8X") {
+        out.extend(chunks);
+        out[20] |= EXIF_FLAG;
+    } else {
+        let flags = match chunks.starts_with(b"VP8L") {
+            true => EXIF_FLAG | ALPHA_FLAG,
+            false => EXIF_FLAG,
+        };
+        out.extend(b"VP8X");
+        out.extend(10u32.to_le_bytes());
+        out.extend([flags, 0, 0, 0]);
+        out.extend(&(width.max(1) - 1).to_le_bytes()[..3]);
+        out.extend(&(height.max(1) - 1).to_le_bytes()[..3]);
+        out.extend(chunks);
+    }
+    out.extend(b"EXIF");
+    out.extend((exif.len() as u32).to_le_bytes());
+    out.extend(exif);
+    if exif.len() % 2 == 1 {
+        out.push(0);
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    out
+}
+
+// Ancillary PNG chunks that carry metadata rather than pixels or colour information.
+const PNG_METADATA: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = bytes.get(..8)?.to_vec();
+    let mut rest = &bytes[8..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        // Length, type, data and CRC
+        let chunk = rest.get(..12 + len)?;
+        if !PNG_METADATA.iter().any(|kind| &chunk[4..8] == *kind) {
+            out.extend(chunk);
+        }
+        rest = &rest[12 + len..];
+    }
+    Some(out)
+}
+
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = bytes.get(..2)?.to_vec();
+    let mut rest = &bytes[2..];
+    loop {
+        let marker = *rest.get(1)?;
+        if rest[0] != 0xFF {
+            return None;
+        }
+        // Entropy-coded data follows the start of scan; nothing after it is metadata
+        if marker == 0xDA {
+            out.extend(rest);
+            return Some(out);
+        }
+        let len = u16::from_be_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+        let segment = rest.get(..2 + len)?;
+        // APP1 to APP15 hold EXIF, XMP and the like, COM free text; APP0 is JFIF
+        if !matches!(marker, 0xE1..=0xEF | 0xFE) {
+            out.extend(segment);
+        }
+        rest = &rest[2 + len..];
+    }
+}
+
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    const EXIF_AND_XMP_FLAGS: u8 = 0x08 | 0x04;
+    let mut out = bytes.get(..12)?.to_vec();
+    let mut rest = &bytes[12..];
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        // Chunks are padded to an even size
+        let chunk = rest.get(..(8 + len + len % 2).min(rest.len()))?;
+        match &chunk[..4] {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let start = out.len();
+                out.extend(chunk);
+                out[start + 8] &= !EXIF_AND_XMP_FLAGS;
+            }
+            _ => out.extend(chunk),
+        }
+        rest = &rest[chunk.len()..];
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+// Drops whatever metadata encoded output carries: PNG text, time and EXIF chunks,
+// JPEG segments other than JFIF's, WebP EXIF and XMP chunks. Output that doesn't parse
+// is returned as it is.
+pub fn strip(bytes: Vec<u8>, ext: &str) -> Vec<u8> {
+    let stripped = match ext {
+        "png" => strip_png(&bytes),
+        "jpg" => strip_jpeg(&bytes),
+        "webp" => strip_webp(&bytes),
+        _ => None,
+    };
+    stripped.unwrap_or(bytes)
+}
@@ -0,0 +1,106 @@
+// A second renderer for documents pdfium draws wrong. MuPDF isn't linked in; its
+// `mutool` command draws each page when it's installed.
+
+use crate::error::ConvertError;
+use crate::external;
+use crate::render::{PageRenderer, RenderSettings};
+use pdfium_render::prelude::PdfPage;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MUTOOL: &str = "mutool";
+
+static NEXT_SOURCE: AtomicU64 = AtomicU64::new(0);
+
+// The installed MuPDF version; `None` without `mutool` on the PATH.
+pub fn version() -> Option<String> {
+    external::version(MUTOOL)
+}
+
+// The document file `mutool` reads.
+pub struct Source {
+    path: PathBuf,
+    // A copy of a document that only exists in memory, deleted on drop
+    temporary: bool,
+}
+
+impl Source {
+    // Fails when `mutool` isn't installed. `bytes` replace the file at `path`, e.g. for
+    // documents that never touched disk.
+    pub fn new(path: &Path, bytes: Option<Vec<u8>>) -> Result<Self, ConvertError> {
+        if version().is_none() {
+            return Err(ConvertError::BackendUnavailable {
+                backend: "mupdf".into(),
+                message: format!("{} was not found on the PATH", MUTOOL),
+            });
+        }
+        let Some(bytes) = bytes else {
+            return Ok(Source {
+                path: path.to_path_buf(),
+                temporary: false,
+            });
+        };
+        let dir = std::env::temp_dir().join("pdf-to-image");
+        std::fs::create_dir_all(&dir).map_err(|e| ConvertError::io(&dir, e))?;
+        let path = dir.join(format!(
+            "mupdf-{}-{}.pdf",
+            std::process::id(),
+            NEXT_SOURCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, bytes).map_err(|e| ConvertError::io(&path, e))?;
+        Ok(Source {
+            path,
+            temporary: true,
+        })
+    }
+}
+
+// Draws pages on white, straight from the file. MuPDF rounds sizes its own way, so a
+// pixel it comes out short is filled in white.
+impl PageRenderer for Source {
+    fn draw(
+        &self,
+        _page: &PdfPage,
+        page_index: usize,
+        width: u32,
+        height: u32,
+        _settings: &RenderSettings,
+        pixels: &mut [u8],
+    ) -> Result<(), ConvertError> {
+        // Without `-o`, the page goes to stdout
+        let output = external::run(
+            Command::new(MUTOOL)
+                .args(["draw", "-q", "-F", "pnm", "-c", "rgb", "-f"])
+                .args(["-w", &width.to_string(), "-h", &height.to_string()])
+                .arg(&self.path)
+                .arg((page_index + 1).to_string()),
+            page_index,
+        )?;
+        let (drawn_width, drawn_height, samples) =
+            external::ppm(&output).ok_or_else(|| ConvertError::RenderFailed {
+                page: page_index + 1,
+                message: "mutool wrote no image".into(),
+            })?;
+        let (width, height) = (width as usize, height as usize);
+        for (y, row) in pixels.chunks_exact_mut(width * 4).take(height).enumerate() {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let rgb = match x < drawn_width && y < drawn_height {
+                    true => &samples[(y * drawn_width + x) * 3..][..3],
+                    false => &[255; 3],
+                };
+                pixel[..3].copy_from_slice(rgb);
+                pixel[3] = 255;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
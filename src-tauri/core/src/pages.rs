@@ -0,0 +1,36 @@
+// Page ranges such as "1-3, 7", as 0-based page indices.
+
+pub fn parse_page_range(range_str: &str, total_pages: u16) -> Vec<usize> {
+    if range_str.trim().is_empty() {
+        return (0..total_pages as usize).collect();
+    }
+
+    let mut pages = Vec::new();
+    for part in range_str.split(',') {
+        let part = part.trim();
+        if part.contains('-') {
+            let bounds: Vec<&str> = part.split('-').collect();
+            if bounds.len() == 2 {
+                if let (Ok(start), Ok(end)) = (
+                    bounds[0].trim().parse::<usize>(),
+                    bounds[1].trim().parse::<usize>(),
+                ) {
+                    let s = start.saturating_sub(1);
+                    let e = end.min(total_pages as usize);
+                    for i in s..e {
+                        pages.push(i);
+                    }
+                }
+            }
+        } else if let Ok(p) = part.parse::<usize>() {
+            if p > 0 && p <= total_pages as usize {
+                pages.push(p - 1);
+            }
+        }
+    }
+
+    // Remote duplicates and sort
+    pages.sort_unstable();
+    pages.dedup();
+    pages
+}
@@ -0,0 +1,64 @@
+// The fallback for machines where no pdfium library can be bound: poppler's
+// `pdftoppm` renders pages and `pdfinfo` counts them, when they're installed. Only
+// plain page exports go this way; whatever works on pdfium's documents is left out.
+
+use crate::error::ConvertError;
+use crate::external;
+use std::process::Command;
+
+const PDFTOPPM: &str = "pdftoppm";
+const PDFINFO: &str = "pdfinfo";
+
+// The installed poppler version; `None` without `pdftoppm` on the PATH.
+pub fn version() -> Option<String> {
+    external::version(PDFTOPPM)
+}
+
+pub fn page_count(path: &str) -> Result<u16, ConvertError> {
+    let failed = |message: String| ConvertError::PdfOpen {
+        path: path.to_string(),
+        message,
+    };
+    let output = Command::new(PDFINFO)
+        .arg(path)
+        .output()
+        .map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:"))
+        .and_then(|count| count.trim().parse().ok())
+        .ok_or_else(|| failed("pdfinfo reported no page count".into()))
+}
+
+// A page (0-based) at `scale` times 72 DPI, as RGBA on white. Pages are framed by
+// their crop box, as pdfium does.
+pub fn render(
+    path: &str,
+    page_index: usize,
+    scale: f32,
+) -> Result<(u32, u32, Vec<u8>), ConvertError> {
+    let page = (page_index + 1).to_string();
+    // Without an output root, the page goes to stdout
+    let output = external::run(
+        Command::new(PDFTOPPM)
+            .args(["-f", &page, "-l", &page, "-cropbox"])
+            .args(["-r", &(scale * 72.0).to_string()])
+            .arg(path),
+        page_index,
+    )?;
+    let (width, height, samples) =
+        external::ppm(&output).ok_or_else(|| ConvertError::RenderFailed {
+            page: page_index + 1,
+            message: "pdftoppm wrote no image".into(),
+        })?;
+    let pixels = samples[..width * height * 3]
+        .chunks_exact(3)
+        .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+        .collect();
+    Ok((width as u32, height as u32, pixels))
+}
@@ -0,0 +1,187 @@
+// Stages run on each page, such as stamps, tone adjustments, dark mode, cropping and
+// borders. Each job registers its own on its `PageBuffer`, and they run in the order
+// they were registered.
+
+use crate::adjust::{self, Adjustments, Border, Crop, PixelRect, Sharpen};
+use crate::encode::RgbaView;
+use crate::error::ConvertError;
+use crate::stamp::Stamper;
+use pdfium_render::prelude::{PdfDocument, PdfPage, PdfiumError};
+
+// A rendered page in RGBA. The buffer is reused from page to page, so it can be longer
+// than the page; only the first `width * height * 4` bytes belong to it.
+#[derive(Default)]
+pub struct PageImage {
+    pixels: Vec<u8>,
+    // Where stages that change the page's size draw the new page
+    spare: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl PageImage {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn len(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..self.len()]
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        let len = self.len();
+        &mut self.pixels[..len]
+    }
+
+    pub fn view(&self) -> Option<RgbaView<'_>> {
+        RgbaView::from_raw(self.width, self.height, self.pixels())
+    }
+
+    pub(crate) fn with_len(bytes: usize) -> Self {
+        PageImage {
+            pixels: vec![0; bytes],
+            ..Default::default()
+        }
+    }
+
+    // Makes room for a `width` by `height` page, growing the buffer if needed.
+    pub(crate) fn reset(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        let len = self.len();
+        if len > self.pixels.len() {
+            self.pixels.resize(len, 0);
+        }
+    }
+
+    // For stages that change the page's size: `draw` gets the page and a buffer to
+    // write the new one into, and returns its size. The two buffers then trade places.
+    pub fn replace_with(&mut self, draw: impl FnOnce(&RgbaView, &mut Vec<u8>) -> (u32, u32)) {
+        let len = self.len();
+        let Some(page) = RgbaView::from_raw(self.width, self.height, &self.pixels[..len]) else {
+            return;
+        };
+        let (width, height) = draw(&page, &mut self.spare);
+        std::mem::swap(&mut self.pixels, &mut self.spare);
+        self.width = width;
+        self.height = height;
+    }
+}
+
+// What a stage knows about the page besides its pixels.
+pub struct PageContext<'a> {
+    pub page_index: usize,
+    // Where the page's images landed, when a stage asked for them
+    pub image_rects: &'a [PixelRect],
+}
+
+pub trait PostProcessor {
+    // Changes the in-memory page before it's drawn, for stages whose content pdfium
+    // should draw along with the page. Nothing is written back to the file.
+    fn prepare<'a>(
+        &self,
+        _document: &PdfDocument<'a>,
+        _page: &mut PdfPage<'a>,
+        _page_index: usize,
+    ) -> Result<(), PdfiumError> {
+        Ok(())
+    }
+
+    fn process(&self, image: &mut PageImage, context: &PageContext) -> Result<(), ConvertError>;
+
+    // Finding the images walks every object on the page, so it's only done for stages
+    // that leave photos alone.
+    fn needs_image_rects(&self) -> bool {
+        false
+    }
+}
+
+// Lets a job register a stage it still uses elsewhere, such as the stamper it also
+// hands to the SVG writer.
+impl<T: PostProcessor + ?Sized> PostProcessor for &T {
+    fn prepare<'a>(
+        &self,
+        document: &PdfDocument<'a>,
+        page: &mut PdfPage<'a>,
+        page_index: usize,
+    ) -> Result<(), PdfiumError> {
+        (**self).prepare(document, page, page_index)
+    }
+
+    fn process(&self, image: &mut PageImage, context: &PageContext) -> Result<(), ConvertError> {
+        (**self).process(image, context)
+    }
+
+    fn needs_image_rects(&self) -> bool {
+        (**self).needs_image_rects()
+    }
+}
+
+// Stamps are added to the page rather than drawn over its pixels, so their text is
+// as crisp as the page's own.
+impl PostProcessor for Stamper {
+    fn prepare<'a>(
+        &self,
+        document: &PdfDocument<'a>,
+        page: &mut PdfPage<'a>,
+        page_index: usize,
+    ) -> Result<(), PdfiumError> {
+        self.stamp(document, page, page_index)
+    }
+
+    fn process(&self, _image: &mut PageImage, _context: &PageContext) -> Result<(), ConvertError> {
+        Ok(())
+    }
+}
+
+impl PostProcessor for Adjustments {
+    fn process(&self, image: &mut PageImage, _context: &PageContext) -> Result<(), ConvertError> {
+        self.apply(image.pixels_mut());
+        Ok(())
+    }
+}
+
+impl PostProcessor for Sharpen {
+    fn process(&self, image: &mut PageImage, _context: &PageContext) -> Result<(), ConvertError> {
+        let (width, height) = (image.width(), image.height());
+        self.apply(image.pixels_mut(), width, height);
+        Ok(())
+    }
+}
+
+// Dark mode; photos keep their colors.
+pub struct InvertLightness;
+
+impl PostProcessor for InvertLightness {
+    fn process(&self, image: &mut PageImage, context: &PageContext) -> Result<(), ConvertError> {
+        let width = image.width();
+        adjust::invert_lightness(image.pixels_mut(), width, context.image_rects);
+        Ok(())
+    }
+
+    fn needs_image_rects(&self) -> bool {
+        true
+    }
+}
+
+impl PostProcessor for Crop {
+    fn process(&self, image: &mut PageImage, _context: &PageContext) -> Result<(), ConvertError> {
+        image.replace_with(|page, out| self.apply(page, out));
+        Ok(())
+    }
+}
+
+impl PostProcessor for Border {
+    fn process(&self, image: &mut PageImage, _context: &PageContext) -> Result<(), ConvertError> {
+        image.replace_with(|page, out| self.apply(page, out));
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use crate::adjust::{Adjustments, Border, Crop, PixelRect, Sharpen};
+use crate::encode::RgbaView;
+use crate::error::ConvertError;
+use crate::postprocess::{InvertLightness, PageContext, PageImage, PostProcessor};
+use pdfium_render::prelude::*;
+
+// The page boundary that frames the rendered image. Trim and bleed boxes cut off
+// printer marks of pre-press PDFs; the media box keeps them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageBox {
+    Media,
+    #[default]
+    Crop,
+    Bleed,
+    Trim,
+}
+
+// The engine that draws pages. MuPDF is there for documents pdfium gets wrong; it only
+// sees the file, so nothing that changes pages in memory works with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Pdfium,
+    Mupdf,
+    // poppler's command-line tools, which plain page exports fall back to when no pdfium
+    // library can be loaded
+    Poppler,
+}
+
+// How a page is drawn, beyond its size.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderSettings {
+    pub page_box: PageBox,
+    // Replaces each page's own /Rotate entry
+    pub rotation: Option<PdfPageRenderRotation>,
+    // Bake form fields into the page first, so filled-in values show even when the
+    // producer left out their appearance streams
+    pub flatten_forms: bool,
+    // Leave out highlights, comments, stamps and other markup; form fields still show
+    pub hide_annotations: bool,
+    // Nothing but the annotations, on a transparent background
+    pub annotations_only: bool,
+    // Drop text objects, e.g. to send diagrams out for translation
+    pub hide_text: bool,
+    pub hide_images: bool,
+    // Sub-pixel antialiased text for screen previews
+    pub lcd_text: bool,
+    // Dark pages for reading apps and OLED screens; photos keep their colors
+    pub invert: bool,
+    pub adjustments: Option<Adjustments>,
+    pub sharpen: Option<Sharpen>,
+    pub crop: Option<Crop>,
+    pub border: Option<Border>,
+    // Pages that would render larger are rendered at a lower scale instead
+    pub max_pixels: Option<u64>,
+}
+
+impl RenderSettings {
+    pub fn removes(&self, object: &PdfPageObject) -> bool {
+        self.annotations_only
+            || match object.object_type() {
+                PdfPageObjectType::Text => self.hide_text,
+                PdfPageObjectType::Image => self.hide_images,
+                _ => false,
+            }
+    }
+
+    fn removes_any(&self) -> bool {
+        self.annotations_only || self.hide_text || self.hide_images
+    }
+
+    // The post-processing stages these settings turn on, in the order they usually
+    // run; jobs register them along with any of their own.
+    pub fn post_processors(&self) -> Vec<Box<dyn PostProcessor>> {
+        let mut stages: Vec<Box<dyn PostProcessor>> = Vec::new();
+        if let Some(adjustments) = self.adjustments {
+            stages.push(Box::new(adjustments));
+        }
+        if let Some(sharpen) = self.sharpen {
+            stages.push(Box::new(sharpen));
+        }
+        if self.invert {
+            stages.push(Box::new(InvertLightness));
+        }
+        if let Some(crop) = self.crop {
+            stages.push(Box::new(crop));
+        }
+        // Last, so the frame itself isn't adjusted or cropped
+        if let Some(border) = self.border {
+            stages.push(Box::new(border));
+        }
+        stages
+    }
+
+    // Pixel size of a `width` by `height` render once the stages above are done.
+    pub fn output_size(&self, width: u64, height: u64) -> (u64, u64) {
+        let (width, height) = match &self.crop {
+            Some(crop) => crop.cropped_size(width, height),
+            None => (width, height),
+        };
+        match &self.border {
+            Some(border) => border.framed_size(width, height),
+            None => (width, height),
+        }
+    }
+}
+
+// Drops top-level content objects from the in-memory page; text or images nested in
+// form XObjects stay. Pdfium renders the parsed objects, so the content stream needn't
+// be regenerated.
+fn remove_content(page: &mut PdfPage, settings: &RenderSettings) -> Result<(), PdfiumError> {
+    page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::Manual);
+    let objects = page.objects_mut();
+    for index in (0..objects.len()).rev() {
+        if settings.removes(&objects.get(index)?) {
+            objects.remove_object_at_index(index)?;
+        }
+    }
+    Ok(())
+}
+
+// Pdfium addresses bitmaps with 32-bit sizes and JPEG caps sides at 65535 pixels, so
+// no page renders larger than this, whatever the configured limit.
+const MAX_SIDE: f32 = 65_535.0;
+const MAX_PIXELS: f32 = (i32::MAX / 4) as f32;
+
+// Pixel size of a page at `scale`, shrunk to fit `settings.max_pixels` and the limits
+// above; the flag tells whether it had to shrink.
+pub fn pixel_size(page: &PdfPage, scale: f32, settings: &RenderSettings) -> (u32, u32, bool) {
+    let (width, height) = (page.width().value * scale, page.height().value * scale);
+    let limit = settings
+        .max_pixels
+        .map_or(MAX_PIXELS, |max| (max as f32).min(MAX_PIXELS));
+    let fit = (MAX_SIDE / width.max(height))
+        .min((limit / (width * height)).sqrt())
+        .min(1.0);
+    // Tiny pages still get a pixel
+    let pixels = |side: f32| (side * fit).max(1.0) as u32;
+    (pixels(width), pixels(height), fit < 1.0)
+}
+
+// Clockwise degrees, rounded down to a quarter turn.
+pub fn rotation_from_degrees(degrees: u16) -> PdfPageRenderRotation {
+    match degrees % 360 / 90 {
+        1 => PdfPageRenderRotation::Degrees90,
+        2 => PdfPageRenderRotation::Degrees180,
+        3 => PdfPageRenderRotation::Degrees270,
+        _ => PdfPageRenderRotation::None,
+    }
+}
+
+// Adjusts the in-memory pages before anything measures or renders them; pdfium
+// recomputes a page's size whenever its boxes or rotation change. Pages are sized and
+// rendered by their crop box, so the chosen box becomes the crop box; pages that don't
+// define it keep theirs.
+pub fn prepare_pages(document: &PdfDocument, page_indices: &[usize], settings: &RenderSettings) {
+    if settings.page_box == PageBox::Crop && settings.rotation.is_none() {
+        return;
+    }
+    for &page_index in page_indices {
+        let Ok(mut page) = document.pages().get(page_index as u16) else {
+            continue;
+        };
+        let boundaries = page.boundaries();
+        let bounds = match settings.page_box {
+            PageBox::Media => boundaries.media().ok(),
+            PageBox::Bleed => boundaries.bleed().ok(),
+            PageBox::Trim => boundaries.trim().ok(),
+            PageBox::Crop => None,
+        };
+        if let Some(bounds) = bounds {
+            let _ = page.boundaries_mut().set_crop(bounds.bounds);
+        }
+        if let Some(rotation) = settings.rotation {
+            page.set_rotation(rotation);
+        }
+    }
+}
+
+// Where the page's images land in the rendered bitmap.
+fn image_rects(page: &PdfPage, config: &PdfRenderConfig) -> Vec<PixelRect> {
+    page.objects()
+        .iter()
+        .filter(|object| object.object_type() == PdfPageObjectType::Image)
+        .filter_map(|object| {
+            let bounds = object.bounds().ok()?;
+            // Opposite corners stay opposite under quarter-turn rotations
+            let (x0, y0) = page
+                .points_to_pixels(bounds.left(), bounds.top(), config)
+                .ok()?;
+            let (x1, y1) = page
+                .points_to_pixels(bounds.right(), bounds.bottom(), config)
+                .ok()?;
+            Some(PixelRect {
+                left: x0.min(x1).max(0) as u32,
+                top: y0.min(y1).max(0) as u32,
+                right: x0.max(x1).max(0) as u32,
+                bottom: y0.max(y1).max(0) as u32,
+            })
+        })
+        .collect()
+}
+
+// Drops every annotation except form fields from the in-memory page.
+pub fn remove_annotations(page: &mut PdfPage) -> Result<(), PdfiumError> {
+    let annotations = page.annotations_mut();
+    for index in (0..annotations.len()).rev() {
+        let annotation = annotations.get(index)?;
+        if annotation.annotation_type() != PdfPageAnnotationType::Widget {
+            annotations.delete_annotation(annotation)?;
+        }
+    }
+    Ok(())
+}
+
+fn render_config(width: i32, height: i32, settings: &RenderSettings) -> PdfRenderConfig {
+    let background = if settings.annotations_only {
+        PdfColor::new(0, 0, 0, 0)
+    } else {
+        PdfColor::WHITE
+    };
+    PdfRenderConfig::new()
+        .set_target_width(width)
+        .set_target_height(height)
+        .set_clear_color(background)
+        .render_annotations(settings.annotations_only || !settings.hide_annotations)
+        .use_lcd_text_rendering(settings.lcd_text)
+}
+
+// The engine-specific part of rendering: turning a page into pixels. Preparing the
+// page before and adjusting the pixels after are the same whichever engine draws.
+pub trait PageRenderer {
+    // Fills `pixels` with the page as `width` by `height` RGBA. `page` is the
+    // in-memory page with content removed as `settings` ask and whatever the stages
+    // added; renderers working from the file alone can't honour those.
+    fn draw(
+        &self,
+        page: &PdfPage,
+        page_index: usize,
+        width: u32,
+        height: u32,
+        settings: &RenderSettings,
+        pixels: &mut [u8],
+    ) -> Result<(), ConvertError>;
+}
+
+pub struct PdfiumRenderer;
+
+impl PageRenderer for PdfiumRenderer {
+    fn draw(
+        &self,
+        page: &PdfPage,
+        page_index: usize,
+        width: u32,
+        height: u32,
+        settings: &RenderSettings,
+        pixels: &mut [u8],
+    ) -> Result<(), ConvertError> {
+        let render_failed = |e: PdfiumError| ConvertError::RenderFailed {
+            page: page_index + 1,
+            message: e.to_string(),
+        };
+        // Pdfium only draws sub-pixel text onto bitmaps without an alpha channel
+        let opaque = settings.lcd_text && !settings.annotations_only;
+        // Safety: the slice holds exactly `width * height` four-byte pixels, and the
+        // bitmap is dropped before the buffer is read. Pdfium picks the stride for
+        // external buffers, which for BGRA and BGRx is `width * 4` with no row
+        // padding.
+        let mut bitmap = unsafe {
+            PdfBitmap::from_bytes(
+                width as i32,
+                height as i32,
+                if opaque {
+                    PdfBitmapFormat::BGRx
+                } else {
+                    PdfBitmapFormat::BGRA
+                },
+                pixels,
+                page.bindings(),
+            )
+        }
+        .map_err(render_failed)?;
+        let config = render_config(width as i32, height as i32, settings);
+        page.render_into_bitmap_with_config(&mut bitmap, &config)
+            .map_err(render_failed)?;
+        drop(bitmap);
+        if opaque {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel[3] = 255;
+            }
+        }
+        Ok(())
+    }
+}
+
+// One pixel buffer sized to the largest selected page of a document. Every page is
+// rendered into it instead of pdfium allocating a fresh bitmap per page.
+pub struct PageBuffer<'s> {
+    image: PageImage,
+    // Whether `image` holds a finished page
+    rendered: bool,
+    renderer: Box<dyn PageRenderer>,
+    // None until the job registers them; the render settings alone add no stage
+    post_processors: Vec<Box<dyn PostProcessor + 's>>,
+}
+
+impl<'s> PageBuffer<'s> {
+    // `vec!` zero-fills through calloc, so a buffer that's never rendered into
+    // costs no real memory.
+    pub fn for_sizes(sizes: impl Iterator<Item = (u64, u64)>) -> Self {
+        let bytes = sizes.map(|(w, h)| w * h * 4).max().unwrap_or(0);
+        PageBuffer {
+            image: PageImage::with_len(bytes as usize),
+            rendered: false,
+            renderer: Box::new(PdfiumRenderer),
+            post_processors: Vec::new(),
+        }
+    }
+
+    // Draws pages with another engine from now on; pdfium still measures them.
+    pub fn use_renderer(&mut self, renderer: Box<dyn PageRenderer>) {
+        self.renderer = renderer;
+    }
+
+    pub fn add_post_processor(&mut self, stage: Box<dyn PostProcessor + 's>) {
+        self.post_processors.push(stage);
+    }
+
+    // The page `render` returned last, for looking at a page before deciding how to
+    // save it without rendering it twice.
+    pub fn last_render(&self) -> Option<RgbaView<'_>> {
+        self.image.view().filter(|_| self.rendered)
+    }
+
+    pub fn render<'a>(
+        &mut self,
+        document: &PdfDocument<'a>,
+        page_index: usize,
+        scale: f32,
+        settings: &RenderSettings,
+    ) -> Result<RgbaView<'_>, ConvertError> {
+        let render_failed = |e: PdfiumError| ConvertError::RenderFailed {
+            page: page_index + 1,
+            message: e.to_string(),
+        };
+        self.rendered = false;
+        let mut page = document
+            .pages()
+            .get(page_index as u16)
+            .map_err(render_failed)?;
+        // Only changes the document in memory; the source file is never saved
+        if settings.removes_any() {
+            remove_content(&mut page, settings).map_err(render_failed)?;
+        }
+        if settings.flatten_forms && !settings.annotations_only {
+            // Flattening bakes annotations into the page too, so hidden ones go first
+            if settings.hide_annotations {
+                remove_annotations(&mut page).map_err(render_failed)?;
+            }
+            page.flatten().map_err(render_failed)?;
+        }
+        for stage in &self.post_processors {
+            stage
+                .prepare(document, &mut page, page_index)
+                .map_err(render_failed)?;
+        }
+        let (width, height, _) = pixel_size(&page, scale, settings);
+        self.image.reset(width, height);
+        self.renderer.draw(
+            &page,
+            page_index,
+            width,
+            height,
+            settings,
+            self.image.pixels_mut(),
+        )?;
+
+        let stages = &self.post_processors;
+        let image_rects = match stages.iter().any(|stage| stage.needs_image_rects()) {
+            true => image_rects(&page, &render_config(width as i32, height as i32, settings)),
+            false => Vec::new(),
+        };
+        let context = PageContext {
+            page_index,
+            image_rects: &image_rects,
+        };
+        for stage in stages {
+            stage.process(&mut self.image, &context)?;
+        }
+
+        // The render config reverses pdfium's byte order, so the pixels are already RGBA
+        // and go to the encoder without another copy
+        self.rendered = true;
+        self.image.view().ok_or_else(|| ConvertError::RenderFailed {
+            page: page_index + 1,
+            message: "Rendered bitmap has an unexpected size".into(),
+        })
+    }
+}
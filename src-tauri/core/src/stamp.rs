@@ -0,0 +1,445 @@
+use crate::error::ConvertError;
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Gap between a stamp and the page edge, in points.
+const MARGIN: f32 = 18.0;
+
+const BUILT_IN_FONTS: [PdfFontBuiltin; 14] = [
+    PdfFontBuiltin::TimesRoman,
+    PdfFontBuiltin::TimesBold,
+    PdfFontBuiltin::TimesItalic,
+    PdfFontBuiltin::TimesBoldItalic,
+    PdfFontBuiltin::Helvetica,
+    PdfFontBuiltin::HelveticaBold,
+    PdfFontBuiltin::HelveticaOblique,
+    PdfFontBuiltin::HelveticaBoldOblique,
+    PdfFontBuiltin::Courier,
+    PdfFontBuiltin::CourierBold,
+    PdfFontBuiltin::CourierOblique,
+    PdfFontBuiltin::CourierBoldOblique,
+    PdfFontBuiltin::Symbol,
+    PdfFontBuiltin::ZapfDingbats,
+];
+
+// Where on the page a stamp goes, as the page is displayed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    TopLeft,
+    Top,
+    TopRight,
+    #[default]
+    Center,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+// Text or a logo drawn over every page, e.g. "DRAFT" for distribution copies.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    pub text: Option<String>,
+    // One of the 14 standard PDF fonts, e.g. "Helvetica-Bold", or a TrueType file
+    pub font: String,
+    // In points, so the mark keeps its size relative to the page at any scale
+    pub size: f32,
+    pub color: [u8; 3],
+    pub opacity: f32,
+    pub position: Position,
+    // Counter-clockwise degrees, e.g. 45 for a diagonal mark
+    pub angle: f32,
+    // PNG logo, drawn below the text when both are set
+    pub image: Option<String>,
+    // Logo width as a share of the page width
+    pub image_width: f32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            text: None,
+            font: "Helvetica-Bold".into(),
+            size: 72.0,
+            color: [128, 128, 128],
+            opacity: 0.3,
+            position: Position::Center,
+            angle: 0.0,
+            image: None,
+            image_width: 0.25,
+        }
+    }
+}
+
+// The page number in a corner of every page, so images separated from their PDF can
+// still be put back in order.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PageNumbers {
+    pub position: Position,
+    // "3 / 120" rather than "3"
+    pub show_total: bool,
+    pub font: String,
+    pub size: f32,
+    pub color: [u8; 3],
+}
+
+impl Default for PageNumbers {
+    fn default() -> Self {
+        PageNumbers {
+            position: Position::BottomRight,
+            show_total: false,
+            font: "Helvetica".into(),
+            size: 10.0,
+            color: [0, 0, 0],
+        }
+    }
+}
+
+// A line of text across the top or bottom of every page, e.g. for audit trails on
+// exported evidence. `{name}`, `{date}`, `{page}` and `{total}` are filled in.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Caption {
+    pub text: String,
+    pub font: String,
+    pub size: f32,
+    pub color: [u8; 3],
+}
+
+impl Default for Caption {
+    fn default() -> Self {
+        Caption {
+            text: String::new(),
+            font: "Helvetica".into(),
+            size: 9.0,
+            color: [64, 64, 64],
+        }
+    }
+}
+
+// Days since 1970-01-01 to a civil (year, month, day), after Howard Hinnant's
+// algorithm.
+pub fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Today's date as YYYY-MM-DD, in UTC.
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    let (year, month, day) = civil_date(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn load_font(document: &mut PdfDocument, name: &str) -> Result<PdfFontToken, ConvertError> {
+    if let Some(font) = BUILT_IN_FONTS
+        .iter()
+        .find(|font| font.to_pdf_font_name().eq_ignore_ascii_case(name))
+    {
+        return Ok(document.fonts_mut().new_built_in(*font));
+    }
+    document
+        .fonts_mut()
+        .load_true_type_from_file(name, false)
+        .map_err(|e| ConvertError::IoFailed {
+            path: name.to_string(),
+            message: e.to_string(),
+        })
+}
+
+fn load_logo(path: &str, opacity: f32) -> Result<DynamicImage, ConvertError> {
+    let mut logo = image::open(Path::new(path))
+        .map_err(|e| ConvertError::IoFailed {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?
+        .into_rgba8();
+    // Baked into the pixels; pdfium doesn't fade images by the object's fill alpha
+    for pixel in logo.pixels_mut() {
+        pixel.0[3] = (f32::from(pixel.0[3]) * opacity) as u8;
+    }
+    Ok(DynamicImage::ImageRgba8(logo))
+}
+
+// The page as displayed: its size after /Rotate, and how to get from there back to
+// the page's own coordinates.
+struct Frame {
+    width: f32,
+    height: f32,
+    rotation: f32,
+    origin: (f32, f32),
+}
+
+impl Frame {
+    fn of(page: &PdfPage) -> Self {
+        let crop = page
+            .boundaries()
+            .crop()
+            .map(|crop| crop.bounds)
+            .unwrap_or_else(|_| PdfRect::new_from_values(0.0, 0.0, 0.0, 0.0));
+        let (left, bottom) = (crop.left().value, crop.bottom().value);
+        let (right, top) = (crop.right().value, crop.top().value);
+        let rotation = match page.rotation() {
+            Ok(PdfPageRenderRotation::Degrees90) => 90.0,
+            Ok(PdfPageRenderRotation::Degrees180) => 180.0,
+            Ok(PdfPageRenderRotation::Degrees270) => 270.0,
+            _ => 0.0,
+        };
+        // Turning the displayed page back counter-clockwise by its rotation lands it on
+        // the page; this is where its bottom-left corner ends up
+        let origin = match rotation as u16 {
+            90 => (right, bottom),
+            180 => (right, top),
+            270 => (left, top),
+            _ => (left, bottom),
+        };
+        Frame {
+            width: page.width().value,
+            height: page.height().value,
+            rotation,
+            origin,
+        }
+    }
+
+    // Centers `object`, turns it by `angle` and moves it to `position`, all as seen on
+    // the displayed page, then maps it onto the page.
+    fn place(
+        &self,
+        object: &mut PdfPageObject,
+        position: Position,
+        angle: f32,
+    ) -> Result<(), PdfiumError> {
+        let bounds = object.bounds()?;
+        let (width, height) = (bounds.width().value, bounds.height().value);
+        object.translate(
+            PdfPoints::new(-bounds.left().value - width / 2.0),
+            PdfPoints::new(-bounds.bottom().value - height / 2.0),
+        )?;
+        object.rotate_counter_clockwise_degrees(angle)?;
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let half_width = (width * cos.abs() + height * sin.abs()) / 2.0;
+        let half_height = (width * sin.abs() + height * cos.abs()) / 2.0;
+        let x = match position {
+            Position::TopLeft | Position::BottomLeft => MARGIN + half_width,
+            Position::TopRight | Position::BottomRight => self.width - MARGIN - half_width,
+            _ => self.width / 2.0,
+        };
+        let y = match position {
+            Position::TopLeft | Position::Top | Position::TopRight => {
+                self.height - MARGIN - half_height
+            }
+            Position::BottomLeft | Position::Bottom | Position::BottomRight => MARGIN + half_height,
+            Position::Center => self.height / 2.0,
+        };
+        object.translate(PdfPoints::new(x), PdfPoints::new(y))?;
+
+        object.rotate_counter_clockwise_degrees(self.rotation)?;
+        object.translate(PdfPoints::new(self.origin.0), PdfPoints::new(self.origin.1))
+    }
+}
+
+// What to stamp, borrowed from the conversion options.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stamps<'a> {
+    pub watermark: Option<&'a Watermark>,
+    pub page_numbers: Option<&'a PageNumbers>,
+    pub header: Option<&'a Caption>,
+    pub footer: Option<&'a Caption>,
+}
+
+// A line of text with its font already loaded into the document.
+struct TextStamp {
+    font: PdfFontToken,
+    size: f32,
+    color: PdfColor,
+    position: Position,
+    angle: f32,
+}
+
+impl TextStamp {
+    fn load(
+        document: &mut PdfDocument,
+        font: &str,
+        size: f32,
+        [red, green, blue]: [u8; 3],
+        opacity: f32,
+        position: Position,
+        angle: f32,
+    ) -> Result<Self, ConvertError> {
+        Ok(TextStamp {
+            font: load_font(document, font)?,
+            size,
+            color: PdfColor::new(red, green, blue, (opacity.clamp(0.0, 1.0) * 255.0) as u8),
+            position,
+            angle,
+        })
+    }
+
+    fn add<'a>(
+        &self,
+        document: &PdfDocument<'a>,
+        page: &mut PdfPage<'a>,
+        frame: &Frame,
+        text: &str,
+    ) -> Result<(), PdfiumError> {
+        let mut object = PdfPageObject::Text(PdfPageTextObject::new(
+            document,
+            text,
+            self.font,
+            PdfPoints::new(self.size),
+        )?);
+        object.set_fill_color(self.color)?;
+        frame.place(&mut object, self.position, self.angle)?;
+        page.objects_mut().add_object(object)?;
+        Ok(())
+    }
+}
+
+// Stamps loaded for one document, so fonts and logos are read once rather than for
+// every page.
+#[derive(Default)]
+pub struct Stamper {
+    watermark: Option<(String, TextStamp)>,
+    logo: Option<(DynamicImage, Watermark)>,
+    page_numbers: Option<(TextStamp, bool)>,
+    captions: Vec<(String, TextStamp)>,
+    // Document name and date for the caption placeholders
+    context: Option<(String, String)>,
+}
+
+impl Stamper {
+    pub fn new(
+        document: &mut PdfDocument,
+        stamps: Stamps,
+        name: &str,
+    ) -> Result<Self, ConvertError> {
+        let mut stamper = Stamper::default();
+        if let Some(watermark) = stamps.watermark {
+            if let Some(text) = &watermark.text {
+                let stamp = TextStamp::load(
+                    document,
+                    &watermark.font,
+                    watermark.size,
+                    watermark.color,
+                    watermark.opacity,
+                    watermark.position,
+                    watermark.angle,
+                )?;
+                stamper.watermark = Some((text.clone(), stamp));
+            }
+            if let Some(path) = &watermark.image {
+                let logo = load_logo(path, watermark.opacity.clamp(0.0, 1.0))?;
+                stamper.logo = Some((logo, watermark.clone()));
+            }
+        }
+        if let Some(numbers) = stamps.page_numbers {
+            let stamp = TextStamp::load(
+                document,
+                &numbers.font,
+                numbers.size,
+                numbers.color,
+                1.0,
+                numbers.position,
+                0.0,
+            )?;
+            stamper.page_numbers = Some((stamp, numbers.show_total));
+        }
+        for (caption, position) in [
+            (stamps.header, Position::Top),
+            (stamps.footer, Position::Bottom),
+        ] {
+            if let Some(caption) = caption.filter(|caption| !caption.text.is_empty()) {
+                let stamp = TextStamp::load(
+                    document,
+                    &caption.font,
+                    caption.size,
+                    caption.color,
+                    1.0,
+                    position,
+                    0.0,
+                )?;
+                stamper.captions.push((caption.text.clone(), stamp));
+            }
+        }
+        if !stamper.captions.is_empty() {
+            stamper.context = Some((name.to_string(), today()));
+        }
+        Ok(stamper)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.watermark.is_none()
+            && self.logo.is_none()
+            && self.page_numbers.is_none()
+            && self.captions.is_empty()
+    }
+
+    // What the captions are filled in with, which the stamp settings alone don't tell.
+    pub fn context(&self) -> Option<&(String, String)> {
+        self.context.as_ref()
+    }
+
+    // Adds the stamps to the in-memory page. They're drawn like any other content but
+    // never written back, so the content stream is left alone.
+    pub fn stamp<'a>(
+        &self,
+        document: &PdfDocument<'a>,
+        page: &mut PdfPage<'a>,
+        page_index: usize,
+    ) -> Result<(), PdfiumError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::Manual);
+        let frame = Frame::of(page);
+        if let Some((logo, watermark)) = &self.logo {
+            let width = PdfPoints::new(frame.width * watermark.image_width);
+            let mut object =
+                PdfPageObject::Image(PdfPageImageObject::new_with_width(document, logo, width)?);
+            frame.place(&mut object, watermark.position, watermark.angle)?;
+            page.objects_mut().add_object(object)?;
+        }
+        if let Some((text, stamp)) = &self.watermark {
+            stamp.add(document, page, &frame, text)?;
+        }
+        let (number, total) = (page_index + 1, document.pages().len());
+        if let Some((stamp, show_total)) = &self.page_numbers {
+            let text = match show_total {
+                true => format!("{} / {}", number, total),
+                false => number.to_string(),
+            };
+            stamp.add(document, page, &frame, &text)?;
+        }
+        if let Some((name, date)) = &self.context {
+            for (template, stamp) in &self.captions {
+                let text = template
+                    .replace("{name}", name)
+                    .replace("{date}", date)
+                    .replace("{page}", &number.to_string())
+                    .replace("{total}", &total.to_string());
+                stamp.add(document, page, &frame, &text)?;
+            }
+        }
+        Ok(())
+    }
+}
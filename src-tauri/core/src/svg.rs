@@ -0,0 +1,314 @@
+// Pages as SVG: paths, text and images written out as vector content instead of
+// pixels. Shadings, clipping paths and annotations aren't carried over, and text keeps
+// its font's family name rather than embedding the font.
+
+use crate::error::ConvertError;
+use crate::render::{self, RenderSettings};
+use crate::stamp::Stamper;
+use pdfium_render::prelude::*;
+use std::fmt::Write;
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // Not allowed in XML 1.0 at all
+            c if c < ' ' && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &b)| group | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+fn matrix(matrix: &PdfMatrix) -> String {
+    format!(
+        "matrix({} {} {} {} {} {})",
+        matrix.a(),
+        matrix.b(),
+        matrix.c(),
+        matrix.d(),
+        matrix.e(),
+        matrix.f()
+    )
+}
+
+// `fill` or `stroke` attributes for a color, with its alpha as opacity.
+fn paint(attribute: &str, color: PdfColor) -> String {
+    let mut paint = format!(
+        " {}=\"#{:02x}{:02x}{:02x}\"",
+        attribute,
+        color.red(),
+        color.green(),
+        color.blue()
+    );
+    if color.alpha() < 255 {
+        let _ = write!(
+            paint,
+            " {}-opacity=\"{}\"",
+            attribute,
+            f32::from(color.alpha()) / 255.0
+        );
+    }
+    paint
+}
+
+// Path data in the object's own coordinates; its matrix goes on the element.
+fn path_data(path: &PdfPagePathObject) -> String {
+    let mut data = String::new();
+    let mut curve = Vec::new();
+    for segment in path.segments().raw().iter() {
+        let (x, y) = (segment.x().value, segment.y().value);
+        match segment.segment_type() {
+            PdfPathSegmentType::MoveTo => {
+                let _ = write!(data, "M{} {}", x, y);
+            }
+            PdfPathSegmentType::LineTo => {
+                let _ = write!(data, "L{} {}", x, y);
+            }
+            // Pdfium hands out both control points and the end point as separate
+            // segments
+            PdfPathSegmentType::BezierTo => {
+                curve.push((x, y));
+                if curve.len() == 3 {
+                    let _ = write!(
+                        data,
+                        "C{} {} {} {} {} {}",
+                        curve[0].0, curve[0].1, curve[1].0, curve[1].1, curve[2].0, curve[2].1
+                    );
+                    curve.clear();
+                }
+            }
+            PdfPathSegmentType::Unknown => {}
+        }
+        if segment.is_close() {
+            data.push('Z');
+        }
+    }
+    data
+}
+
+fn write_path(svg: &mut String, object: &PdfPageObject, path: &PdfPagePathObject) {
+    let data = path_data(path);
+    if data.is_empty() {
+        return;
+    }
+    let mut attributes = match path.fill_mode() {
+        Ok(PdfPathFillMode::EvenOdd) => {
+            paint("fill", object.fill_color().unwrap_or(PdfColor::BLACK)) + " fill-rule=\"evenodd\""
+        }
+        Ok(PdfPathFillMode::Winding) => {
+            paint("fill", object.fill_color().unwrap_or(PdfColor::BLACK))
+        }
+        _ => " fill=\"none\"".to_string(),
+    };
+    if path.is_stroked().unwrap_or(false) {
+        attributes += &paint("stroke", object.stroke_color().unwrap_or(PdfColor::BLACK));
+        // A zero width is the thinnest line the device can draw
+        match object.stroke_width().map_or(1.0, |width| width.value) {
+            0.0 => attributes += " stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"",
+            width => {
+                let _ = write!(attributes, " stroke-width=\"{}\"", width);
+            }
+        }
+        let cap = match object.line_cap() {
+            Ok(PdfPageObjectLineCap::Round) => "round",
+            Ok(PdfPageObjectLineCap::Square) => "square",
+            _ => "butt",
+        };
+        let join = match object.line_join() {
+            Ok(PdfPageObjectLineJoin::Round) => "round",
+            Ok(PdfPageObjectLineJoin::Bevel) => "bevel",
+            _ => "miter",
+        };
+        let _ = write!(
+            attributes,
+            " stroke-linecap=\"{}\" stroke-linejoin=\"{}\"",
+            cap, join
+        );
+        let dashes = object.dash_array().unwrap_or_default();
+        if !dashes.is_empty() {
+            let dashes: Vec<String> = dashes.iter().map(|dash| dash.value.to_string()).collect();
+            let _ = write!(
+                attributes,
+                " stroke-dasharray=\"{}\" stroke-dashoffset=\"{}\"",
+                dashes.join(" "),
+                object.dash_phase().map_or(0.0, |phase| phase.value)
+            );
+        }
+    }
+    let transform = object.matrix().map(|m| matrix(&m)).unwrap_or_default();
+    let _ = writeln!(
+        svg,
+        "<path transform=\"{}\" d=\"{}\"{}/>",
+        transform, data, attributes
+    );
+}
+
+fn write_text(svg: &mut String, object: &PdfPageObject, text: &PdfPageTextObject) {
+    let mode = text.render_mode();
+    if matches!(
+        mode,
+        PdfPageTextRenderMode::Invisible | PdfPageTextRenderMode::InvisibleClipping
+    ) {
+        return;
+    }
+    let content = text.text();
+    if content.trim().is_empty() {
+        return;
+    }
+    let font = text.font();
+    let mut attributes = format!(
+        " font-family=\"{}\" font-size=\"{}\"",
+        escape(&font.family()),
+        text.unscaled_font_size().value
+    );
+    if font.is_bold_reenforced() {
+        attributes += " font-weight=\"bold\"";
+    }
+    if font.is_italic() {
+        attributes += " font-style=\"italic\"";
+    }
+    let color = object.fill_color().unwrap_or(PdfColor::BLACK);
+    attributes += &match mode {
+        PdfPageTextRenderMode::StrokedUnfilled | PdfPageTextRenderMode::StrokedUnfilledClipping => {
+            " fill=\"none\"".to_string() + &paint("stroke", object.stroke_color().unwrap_or(color))
+        }
+        _ => paint("fill", color),
+    };
+    // Glyphs are drawn upwards in PDF and downwards in SVG
+    let transform = object.matrix().map(|m| matrix(&m)).unwrap_or_default();
+    let _ = writeln!(
+        svg,
+        "<text transform=\"{} scale(1 -1)\" xml:space=\"preserve\"{}>{}</text>",
+        transform,
+        attributes,
+        escape(&content)
+    );
+}
+
+// Embedded as a PNG. The raw image leaves out soft masks, but the processed one would
+// bake the object's rotation into the pixels.
+fn write_image(svg: &mut String, object: &PdfPageObject, image: &PdfPageImageObject) {
+    let Ok(pixels) = image.get_raw_image() else {
+        return;
+    };
+    let mut png = std::io::Cursor::new(Vec::new());
+    if pixels.write_to(&mut png, image::ImageFormat::Png).is_err() {
+        return;
+    }
+    // Images fill the unit square of their matrix, first row at the top
+    let transform = object.matrix().map(|m| matrix(&m)).unwrap_or_default();
+    let _ = writeln!(
+        svg,
+        "<image transform=\"{} translate(0 1) scale(1 -1)\" width=\"1\" height=\"1\" \
+         preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>",
+        transform,
+        base64(png.get_ref())
+    );
+}
+
+fn write_objects<'a>(
+    svg: &mut String,
+    objects: impl Iterator<Item = PdfPageObject<'a>>,
+    settings: &RenderSettings,
+) {
+    for object in objects {
+        if settings.removes(&object) {
+            continue;
+        }
+        if let Some(path) = object.as_path_object() {
+            write_path(svg, &object, path);
+        } else if let Some(text) = object.as_text_object() {
+            write_text(svg, &object, text);
+        } else if let Some(image) = object.as_image_object() {
+            write_image(svg, &object, image);
+        } else if let Some(form) = object.as_x_object_form_object() {
+            let transform = object.matrix().map(|m| matrix(&m)).unwrap_or_default();
+            let _ = writeln!(svg, "<g transform=\"{}\">", transform);
+            write_objects(svg, form.iter(), settings);
+            svg.push_str("</g>\n");
+        }
+    }
+}
+
+// The page as an SVG document, sized in pixels at `scale` with a viewBox in points so
+// it stays sharp at any zoom.
+pub fn page<'a>(
+    document: &PdfDocument<'a>,
+    page_index: usize,
+    scale: f32,
+    settings: &RenderSettings,
+    stamper: &Stamper,
+) -> Result<Vec<u8>, ConvertError> {
+    let render_failed = |e: PdfiumError| ConvertError::RenderFailed {
+        page: page_index + 1,
+        message: e.to_string(),
+    };
+    let mut page = document
+        .pages()
+        .get(page_index as u16)
+        .map_err(render_failed)?;
+    if settings.flatten_forms {
+        if settings.hide_annotations {
+            render::remove_annotations(&mut page).map_err(render_failed)?;
+        }
+        page.flatten().map_err(render_failed)?;
+    }
+    stamper
+        .stamp(document, &mut page, page_index)
+        .map_err(render_failed)?;
+    let (width, height, _) = render::pixel_size(&page, scale, settings);
+    let (page_width, page_height) = (page.width().value, page.height().value);
+    let bounds = page.boundaries().bounding().map_err(render_failed)?.bounds;
+    let (x0, y0) = (bounds.left().value, bounds.bottom().value);
+    let (w, h) = (bounds.width().value, bounds.height().value);
+    // PDF space, y up, onto the page as displayed, y down
+    let transform = match page.rotation().unwrap_or(PdfPageRenderRotation::None) {
+        PdfPageRenderRotation::Degrees90 => format!("matrix(0 1 1 0 {} {})", -y0, -x0),
+        PdfPageRenderRotation::Degrees180 => format!("matrix(-1 0 0 1 {} {})", x0 + w, -y0),
+        PdfPageRenderRotation::Degrees270 => {
+            format!("matrix(0 -1 -1 0 {} {})", y0 + h, x0 + w)
+        }
+        PdfPageRenderRotation::None => format!("matrix(1 0 0 -1 {} {})", -x0, y0 + h),
+    };
+
+    let mut svg = String::new();
+    svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\">",
+        width, height, page_width, page_height
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>",
+        page_width, page_height
+    );
+    let _ = writeln!(svg, "<g transform=\"{}\">", transform);
+    write_objects(&mut svg, page.objects().iter(), settings);
+    svg.push_str("</g>\n</svg>\n");
+    Ok(svg.into_bytes())
+}
@@ -0,0 +1,86 @@
+// Files embedded in a PDF, such as the XML invoice data inside ZUGFeRD and Factur-X
+// invoices.
+
+use crate::encode;
+use crate::error::{ConvertError, ErrorPayload};
+use pdfium_render::prelude::*;
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(serde::Serialize)]
+pub(crate) struct Attachment {
+    name: String,
+    size: usize,
+    // Where it was extracted to; `None` when only listing
+    path: Option<String>,
+    error: Option<ErrorPayload>,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct AttachmentReport {
+    pub input_path: String,
+    pub attachments: Vec<Attachment>,
+    pub error: Option<ErrorPayload>,
+}
+
+// The attachment's own name with any directories dropped, so a crafted name can't
+// write outside `dir`.
+fn file_name(name: &str, index: PdfAttachmentIndex) -> String {
+    Path::new(name)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("attachment_{}", index + 1))
+}
+
+// Lists the document's attachments, writing each into `dir` when given.
+pub(crate) fn extract(document: &PdfDocument, dir: Option<&Path>, report: &mut AttachmentReport) {
+    let attachments = document.attachments();
+    if let Some(dir) = dir.filter(|_| !attachments.is_empty()) {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            report.error = Some(ConvertError::io(dir, e).into());
+            return;
+        }
+    }
+    let mut used = HashSet::new();
+    for index in attachments.as_range() {
+        let attachment = match attachments.get(index) {
+            Ok(attachment) => attachment,
+            Err(e) => {
+                report.error = Some(
+                    ConvertError::Internal {
+                        message: e.to_string(),
+                    }
+                    .into(),
+                );
+                continue;
+            }
+        };
+        let name = attachment.name();
+        let mut entry = Attachment {
+            size: attachment.len(),
+            name: name.clone(),
+            path: None,
+            error: None,
+        };
+        if let Some(dir) = dir {
+            let mut file_name = file_name(&name, index);
+            // Attachments in different folders of the PDF can share a name
+            if !used.insert(file_name.clone()) {
+                file_name = format!("{}_{}", index + 1, file_name);
+            }
+            let path = dir.join(file_name);
+            let saved = attachment
+                .save_to_bytes()
+                .map_err(|e| ConvertError::Internal {
+                    message: e.to_string(),
+                })
+                .and_then(|bytes| encode::save_bytes(&bytes, &path));
+            match saved {
+                Ok(()) => entry.path = Some(path.to_string_lossy().to_string()),
+                Err(e) => entry.error = Some(e.into()),
+            }
+        }
+        report.attachments.push(entry);
+    }
+}
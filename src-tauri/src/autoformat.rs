@@ -0,0 +1,48 @@
+// Tells photographic pages from text and line art, so each page can go out in the
+// format that keeps it smallest: PNG for flat colours and sharp edges, a lossy format
+// for photos.
+
+use crate::encode::RgbaView;
+use std::collections::HashSet;
+
+// Pages with no more colours than this compress well losslessly, whatever is on them.
+const PALETTE_COLORS: usize = 256;
+
+// Neighbouring pixels this close but not equal are gradients or grain; text and flat
+// fills are runs of equal pixels broken by sharp edges.
+const SMOOTH_STEP: u8 = 24;
+
+// Share of smooth neighbours from which a page counts as photographic; a photo taking
+// up a tenth of the page already makes a lossless page larger than a lossy one.
+const PHOTO_SHARE: f32 = 0.1;
+
+// Rows looked at, spread evenly over the page, to keep large renders quick.
+const SAMPLE_ROWS: u32 = 512;
+
+pub(crate) fn is_photographic(page: &RgbaView) -> bool {
+    let (width, height) = page.dimensions();
+    if width < 2 || height == 0 {
+        return false;
+    }
+    let mut colors = HashSet::new();
+    let (mut smooth, mut pairs) = (0u64, 0u64);
+    for y in (0..height).step_by((height / SAMPLE_ROWS).max(1) as usize) {
+        for x in 1..width {
+            let (left, pixel) = (page.get_pixel(x - 1, y).0, page.get_pixel(x, y).0);
+            if colors.len() <= PALETTE_COLORS {
+                colors.insert(pixel);
+            }
+            let step = left
+                .iter()
+                .zip(pixel)
+                .map(|(&a, b)| a.abs_diff(b))
+                .max()
+                .unwrap_or(0);
+            if step > 0 && step <= SMOOTH_STEP {
+                smooth += 1;
+            }
+            pairs += 1;
+        }
+    }
+    colors.len() > PALETTE_COLORS && smooth as f32 >= pairs as f32 * PHOTO_SHARE
+}
@@ -0,0 +1,87 @@
+use crate::encode::EncodeSettings;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Encoded pages stored under a hash of the source's contents and every setting that
+// changes the output bytes, so re-exporting an unchanged document is just a copy.
+pub(crate) struct RenderCache {
+    dir: PathBuf,
+}
+
+impl RenderCache {
+    pub fn new(dir: PathBuf) -> Self {
+        RenderCache { dir }
+    }
+
+    pub fn file_hash(path: &Path) -> Option<String> {
+        let mut file = std::fs::File::open(path).ok()?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).ok()?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    pub fn bytes_hash(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    pub fn key(
+        file_hash: &str,
+        page_index: usize,
+        scale: f32,
+        render: &dyn std::fmt::Debug,
+        settings: &EncodeSettings,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(file_hash);
+        // Only whether EXIF is embedded counts: its timestamp changes on every run
+        hasher.update(format!(
+            ":{}:{}:{:?}:{}:{}:{}:{:?}:{:?}:{}:{}:{}",
+            page_index,
+            scale,
+            render,
+            settings.ext,
+            settings.quality,
+            settings.lossless,
+            settings.max_bytes,
+            settings.cmyk.map(|cmyk| cmyk.label()),
+            settings.sixteen_bit,
+            settings.strip_metadata,
+            settings.exif.is_some()
+        ));
+        format!("{}.{}", hex::encode(hasher.finalize()), settings.ext)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.dir.join(key)).ok()
+    }
+
+    // Best effort: a page that can't be cached is simply rendered again next time.
+    pub fn put(&self, key: &str, bytes: &[u8]) {
+        if std::fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        let tmp_path = self.dir.join(format!(".{}.part", key));
+        if std::fs::write(&tmp_path, bytes).is_ok() {
+            let _ = std::fs::rename(&tmp_path, self.dir.join(key));
+        }
+    }
+
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
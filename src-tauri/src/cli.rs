@@ -0,0 +1,452 @@
+use crate::cache::RenderCache;
+use crate::events::EventSink;
+use crate::jobs::JobRegistry;
+use crate::jobspec::{JobSpec, SpecInput};
+use crate::session::SessionLog;
+use crate::{library, run_job, spec_files, BatchTotals};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: pdf-to-image convert [options] <input>...
+       pdf-to-image convert --job <spec.json> [-o <dir>]
+
+Inputs may be PDF files or folders, which are searched recursively.
+
+Options:
+      --job <file>         Run a JSON job file instead of the options below
+  -o, --output <dir>       Output directory (default: current directory)
+  -f, --format <format>    png, jpg, webp, tiff or svg (default: png)
+      --dpi <dpi>          Render resolution (default: 144)
+      --scale <scale>      Render scale relative to 72 DPI; overrides --dpi
+      --page-dpi <pages>=<dpi>
+                           Resolution for some pages, e.g. 1=600; repeatable
+      --max-megapixels <n> Render larger pages at a lower scale to fit
+  -q, --quality <1-100>    JPEG/WebP quality (default: 90)
+  -p, --pages <range>      Pages to convert, e.g. 1-3,7
+      --landscape          Only convert landscape pages
+      --portrait           Only convert portrait pages
+      --paper <sizes>      Only convert pages of these paper sizes, e.g. A3,A2
+      --min-paper <size>   Only convert pages at least this size, e.g. A3 or
+                           420x297 (mm)
+      --max-paper <size>   Only convert pages at most this size
+      --show-layer <name>  Show this layer even if the PDF hides it; repeatable
+      --hide-layer <name>  Hide this layer; repeatable
+      --merge              Stack each document's pages into one image
+      --report <json|csv>  Write a batch report into the output directory
+      --memory-limit <mb>  Cap memory used by rendered pages
+      --background         Run at low priority, leaving CPU for other work
+      --backend <engine>   Render with pdfium (default) or mupdf, which needs
+                           mutool installed
+      --repair             Try to repair PDFs that fail to open
+      --page-box <box>     Page boundary to render: media, crop, bleed or trim
+      --ignore-rotation    Ignore the rotation stored in each page
+      --rotate <degrees>   Show every page at this rotation: 0, 90, 180 or 270
+      --flatten-forms      Draw filled-in form field values into the pages
+      --no-annotations     Leave out highlights, comments and stamps
+      --annotations-only   Render just the annotations on a transparent background
+      --no-text            Leave text out, keeping graphics and images
+      --no-images          Leave images out
+      --lcd-text           Sub-pixel antialiased text for on-screen previews
+      --dark               Light text on dark pages; photos keep their colors
+      --brightness <n>     Lighten (up to 1) or darken (down to -1) pages
+      --contrast <n>       Raise (up to 1) or lower (down to -1) contrast
+      --gamma <n>          Above 1 lightens midtones, below 1 darkens them
+      --sharpen <amount>   Unsharp mask strength, e.g. 0.5
+      --border <pixels>    Pad each page and outline it, e.g. 24
+      --watermark <text>   Stamp the text across every page, e.g. DRAFT
+      --page-numbers       Stamp \"page / total\" in the bottom-right corner
+      --header <text>      Text across the top of every page; {name}, {date},
+                           {page} and {total} are filled in
+      --footer <text>      The same across the bottom
+      --n-up <cols>x<rows> Put several pages on each image, e.g. 2x1 or 2x2
+      --booklet            Impose pages two to a sheet for printing as a booklet
+      --contact-sheet      Export page thumbnails with page numbers instead of pages
+      --duplicates <mode>  Report or skip pages that look like earlier ones
+      --provenance         Record the source file and page in each image's EXIF
+      --strip-metadata     Keep EXIF, XMP and text chunks out of the images
+      --cmyk               Write CMYK TIFFs for print
+      --cmyk-profile <icc> Convert to CMYK through this ICC profile; implies --cmyk
+      --16-bit             16 bits per channel in PNG and TIFF images
+      --smart-format       Save text and line art pages as PNG, photos as --format
+  -h, --help               Show this help";
+
+// Reports file status lines on stderr, leaving stdout for the batch report.
+struct TerminalEvents;
+
+impl EventSink for TerminalEvents {
+    fn send(&self, event: &str, payload: Value) {
+        if event != "file_status" {
+            return;
+        }
+        let field = |name: &str| payload.get(name).and_then(Value::as_str).unwrap_or("");
+        match field("status") {
+            // A page that failed along the way
+            "success" if !field("error").is_empty() => eprintln!(
+                "{}: done -> {} ({})",
+                field("filename"),
+                field("output_path"),
+                field("error")
+            ),
+            "success" => eprintln!("{}: done -> {}", field("filename"), field("output_path")),
+            "error" => eprintln!("{}: {}", field("filename"), field("error")),
+            status => eprintln!("{}: {}", field("filename"), status),
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Option<JobSpec>, String> {
+    let mut inputs = Vec::new();
+    let mut output_dir = None;
+    let mut job = None;
+    let mut dpi = 144.0f32;
+    let mut options = Map::new();
+    options.insert("format".into(), json!("png"));
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        let number = |value: String| {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("Invalid number for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" | "--output" => output_dir = Some(value()?),
+            "--job" => job = Some(value()?),
+            "-f" | "--format" => {
+                options.insert("format".into(), json!(value()?));
+            }
+            "--dpi" => dpi = number(value()?)?,
+            "--scale" => {
+                options.insert("scale".into(), json!(number(value()?)?));
+            }
+            "--page-dpi" => {
+                let value = value()?;
+                let (pages, dpi) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                let scale = number(dpi.to_string())? / 72.0;
+                if let Some(page_scales) = options
+                    .entry("page_scales")
+                    .or_insert_with(|| json!([]))
+                    .as_array_mut()
+                {
+                    page_scales.push(json!({ "pages": pages, "scale": scale }));
+                }
+            }
+            "--max-megapixels" => {
+                options.insert("max_megapixels".into(), json!(number(value()?)?));
+            }
+            "-q" | "--quality" => {
+                let value = value()?;
+                let quality = value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|quality| (1..=100).contains(quality))
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                options.insert("quality".into(), json!(quality));
+            }
+            "--paper" | "--min-paper" | "--max-paper" => {
+                let value = value()?;
+                let (key, value) = match arg.as_str() {
+                    "--paper" => ("sizes", json!(value.split(',').collect::<Vec<_>>())),
+                    _ => (&arg[2..5], json!(value)),
+                };
+                if let Some(filter) = options
+                    .entry("page_size")
+                    .or_insert_with(|| json!({}))
+                    .as_object_mut()
+                {
+                    filter.insert(key.into(), value);
+                }
+            }
+            "--show-layer" | "--hide-layer" => {
+                let name = value()?;
+                if let Some(layers) = options
+                    .entry("layers")
+                    .or_insert_with(|| json!({}))
+                    .as_object_mut()
+                {
+                    layers.insert(name, json!(arg == "--show-layer"));
+                }
+            }
+            "--landscape" | "--portrait" => {
+                options.insert("orientation".into(), json!(arg[2..]));
+            }
+            "-p" | "--pages" => {
+                options.insert("page_range".into(), json!(value()?));
+            }
+            "--merge" => {
+                options.insert("merge".into(), json!(true));
+            }
+            "--memory-limit" => {
+                let value = value()?;
+                let limit = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                options.insert("memory_limit_mb".into(), json!(limit));
+            }
+            "--background" => {
+                options.insert("background_priority".into(), json!(true));
+            }
+            "--backend" => {
+                options.insert("backend".into(), json!(value()?));
+            }
+            "--page-box" => {
+                options.insert("page_box".into(), json!(value()?));
+            }
+            "--ignore-rotation" => {
+                options.insert("ignore_page_rotation".into(), json!(true));
+            }
+            "--rotate" => {
+                options.insert("rotation".into(), json!(number(value()?)? as u16));
+            }
+            "--flatten-forms" => {
+                options.insert("flatten_forms".into(), json!(true));
+            }
+            "--no-annotations" => {
+                options.insert("hide_annotations".into(), json!(true));
+            }
+            "--annotations-only" => {
+                options.insert("annotations_only".into(), json!(true));
+            }
+            "--no-text" => {
+                options.insert("hide_text".into(), json!(true));
+            }
+            "--no-images" => {
+                options.insert("hide_images".into(), json!(true));
+            }
+            "--lcd-text" => {
+                options.insert("lcd_text".into(), json!(true));
+            }
+            "--dark" => {
+                options.insert("dark_mode".into(), json!(true));
+            }
+            "--brightness" | "--contrast" | "--gamma" => {
+                let amount = number(value()?)?;
+                let adjustments = options.entry("adjustments").or_insert_with(|| json!({}));
+                adjustments[&arg[2..]] = json!(amount);
+            }
+            "--sharpen" => {
+                options.insert("sharpen".into(), json!({ "amount": number(value()?)? }));
+            }
+            "--border" => {
+                options.insert(
+                    "border".into(),
+                    json!({ "padding": number(value()?)? as u32 }),
+                );
+            }
+            "--watermark" => {
+                options.insert("watermark".into(), json!({ "text": value()?, "angle": 45 }));
+            }
+            "--page-numbers" => {
+                options.insert("page_numbers".into(), json!({ "show_total": true }));
+            }
+            "--header" | "--footer" => {
+                options.insert(arg[2..].into(), json!({ "text": value()? }));
+            }
+            "--n-up" => {
+                let grid = value()?;
+                let (columns, rows) = grid
+                    .split_once('x')
+                    .and_then(|(c, r)| Some((c.parse::<u8>().ok()?, r.parse::<u8>().ok()?)))
+                    .ok_or_else(|| format!("Invalid grid for {}: {}", arg, grid))?;
+                options.insert("n_up".into(), json!({ "columns": columns, "rows": rows }));
+            }
+            "--booklet" | "--contact-sheet" => {
+                options.insert(arg[2..].replace('-', "_"), json!({}));
+            }
+            "--duplicates" => {
+                options.insert("duplicate_pages".into(), json!(value()?));
+            }
+            "--provenance" => {
+                options.insert("embed_provenance".into(), json!(true));
+            }
+            "--strip-metadata" => {
+                options.insert("strip_metadata".into(), json!(true));
+            }
+            "--smart-format" => {
+                options.insert("smart_format".into(), json!(true));
+            }
+            "--16-bit" => {
+                options.insert("sixteen_bit".into(), json!(true));
+            }
+            "--cmyk" => {
+                options.insert("cmyk".into(), json!(true));
+            }
+            "--cmyk-profile" => {
+                options.insert("cmyk".into(), json!(true));
+                options.insert("cmyk_profile".into(), json!(value()?));
+            }
+            "--repair" => {
+                options.insert("repair".into(), json!(true));
+            }
+            "--report" => {
+                options.insert("report_format".into(), json!(value()?));
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unknown option: {}", flag))
+            }
+            input => inputs.push(input.to_string()),
+        }
+    }
+
+    if let Some(job) = job {
+        let mut spec = JobSpec::load(Path::new(&job)).map_err(|e| e.to_string())?;
+        if let Some(output_dir) = output_dir {
+            spec.output_dir = output_dir;
+        }
+        return Ok(Some(spec));
+    }
+
+    if inputs.is_empty() {
+        return Err("No input files given".into());
+    }
+    options.entry("scale").or_insert_with(|| json!(dpi / 72.0));
+    let options = serde_json::from_value(Value::Object(options)).map_err(|e| e.to_string())?;
+    Ok(Some(JobSpec {
+        inputs: inputs.into_iter().map(SpecInput::Path).collect(),
+        output_dir: output_dir.unwrap_or_else(|| ".".into()),
+        options,
+    }))
+}
+
+// Release builds are GUI programs on Windows and start without a console; this
+// writes to the one the command was run from. Redirected output is left alone.
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// Converts without starting the app or opening a window, so the same binary can run
+// in scripts and on servers.
+pub(crate) fn run(args: &[String]) -> i32 {
+    attach_console();
+    let spec = match parse_args(args) {
+        Ok(Some(spec)) => spec,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return 2;
+        }
+    };
+
+    let resource_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+        .unwrap_or_default();
+    library::configure_fonts(None, &resource_dir);
+    let result = library::bind(None, &resource_dir)
+        .map_err(Into::into)
+        .and_then(|(pdfium, _)| {
+            let files = spec_files(&spec)?;
+            let totals = BatchTotals::count(Some(&pdfium), &files, &spec.options);
+            run_job(
+                &TerminalEvents,
+                Some(&pdfium),
+                &SessionLog::default(),
+                &JobRegistry::default(),
+                &RenderCache::new(
+                    std::env::temp_dir()
+                        .join("pdf-to-image")
+                        .join("render_cache"),
+                ),
+                1,
+                &spec.output_dir,
+                files,
+                totals,
+                &spec.options,
+                &|_| {},
+            )
+        });
+
+    match result {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            i32::from(report.failed > 0 || report.aborted)
+        }
+        Err(e) => {
+            eprintln!("{}", e.message);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<JobSpec>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn parses_options() {
+        let Ok(Some(spec)) = parse(&["a.pdf", "-o", "out", "-q", "80", "--memory-limit", "512"])
+        else {
+            panic!("expected a job");
+        };
+        assert_eq!(spec.inputs.len(), 1);
+        assert_eq!(spec.output_dir, "out");
+        assert_eq!(spec.options.quality, 80);
+        assert_eq!(spec.options.memory_limit_mb, Some(512));
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert_eq!(
+            parse(&["a.pdf", "--bogus"]).err(),
+            Some("Unknown option: --bogus".into())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(
+            parse(&["a.pdf", "-o"]).err(),
+            Some("Missing value for -o".into())
+        );
+        assert_eq!(
+            parse(&["a.pdf", "--memory-limit"]).err(),
+            Some("Missing value for --memory-limit".into())
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_numbers() {
+        for (flag, value) in [
+            ("--quality", "0"),
+            ("--quality", "101"),
+            ("--memory-limit", "0"),
+            ("--memory-limit", "-5"),
+            ("--memory-limit", "lots"),
+        ] {
+            assert_eq!(
+                parse(&["a.pdf", flag, value]).err(),
+                Some(format!("Invalid value for {}: {}", flag, value))
+            );
+        }
+        assert_eq!(
+            parse(&["a.pdf", "--dpi", "high"]).err(),
+            Some("Invalid number for --dpi: high".into())
+        );
+    }
+}
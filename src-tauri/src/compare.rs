@@ -0,0 +1,176 @@
+// Page-by-page visual diff of two revisions of a document.
+
+use crate::encode::{self, EncodeSettings, RgbaView};
+use crate::error::{ConvertError, ErrorPayload};
+use crate::render::{PageBuffer, RenderSettings};
+use image::{Rgba, RgbaImage};
+use pdfium_render::prelude::*;
+use std::path::Path;
+
+// Drawn over pixels that changed.
+const HIGHLIGHT: Rgba<u8> = Rgba([230, 30, 30, 255]);
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct CompareOptions {
+    pub scale: f32,
+    // Channel difference up to which pixels still count as equal, so antialiasing
+    // noise between renderer versions doesn't show up
+    pub tolerance: u8,
+    // Where diff images go; only scores are reported when unset
+    pub output_dir: Option<String>,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            scale: 1.0,
+            tolerance: 16,
+            output_dir: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct PageComparison {
+    page: usize, // 1-based
+    // Share of pixels that match, 1 for identical pages and 0 for a page that only
+    // one of the documents has
+    similarity: f32,
+    // Which document the page is missing from: "old" or "new"
+    missing_from: Option<&'static str>,
+    diff_path: Option<String>,
+    error: Option<ErrorPayload>,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct Comparison {
+    old_pages: usize,
+    new_pages: usize,
+    // Mean over all pages compared
+    similarity: f32,
+    pages: Vec<PageComparison>,
+}
+
+// The new page washed out, with pixels that differ from the old page in red. Pages of
+// different sizes are compared from their top-left corners.
+fn diff_image(old: &RgbaView, new: &RgbaView, tolerance: u8) -> (RgbaImage, f32) {
+    let width = old.width().max(new.width());
+    let height = old.height().max(new.height());
+    let mut changed = 0u64;
+    let diff = RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = |page: &RgbaView| page.get_pixel_checked(x, y).copied();
+        match (pixel(old), pixel(new)) {
+            (Some(a), Some(b))
+                if a.0
+                    .iter()
+                    .zip(b.0)
+                    .all(|(&a, b)| a.abs_diff(b) <= tolerance) =>
+            {
+                let [red, green, blue, _] = b.0.map(|channel| 191 + channel / 4);
+                Rgba([red, green, blue, 255])
+            }
+            _ => {
+                changed += 1;
+                HIGHLIGHT
+            }
+        }
+    });
+    let total = u64::from(width) * u64::from(height);
+    let similarity = match total {
+        0 => 1.0,
+        _ => 1.0 - changed as f32 / total as f32,
+    };
+    (diff, similarity)
+}
+
+pub(crate) fn compare(
+    pdfium: &Pdfium,
+    old_path: &str,
+    new_path: &str,
+    options: &CompareOptions,
+) -> Result<Comparison, ConvertError> {
+    let open = |path: &str| {
+        pdfium
+            .load_pdf_from_file(path, None)
+            .map_err(|e| ConvertError::pdf_open(path, e))
+    };
+    let (old, new) = (open(old_path)?, open(new_path)?);
+    let (old_pages, new_pages) = (old.pages().len() as usize, new.pages().len() as usize);
+    let out_dir = options.output_dir.as_deref().map(Path::new);
+    if let Some(dir) = out_dir {
+        std::fs::create_dir_all(dir).map_err(|e| ConvertError::io(dir, e))?;
+    }
+    let stem = Path::new(new_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let settings = EncodeSettings {
+        ext: "png",
+        quality: 90,
+        lossless: false,
+        max_bytes: None,
+        exif: None,
+        strip_metadata: false,
+        cmyk: None,
+        sixteen_bit: false,
+    };
+
+    let mut old_buffer = PageBuffer::for_sizes(std::iter::empty());
+    let mut new_buffer = PageBuffer::for_sizes(std::iter::empty());
+    let mut pages = Vec::new();
+    for page_index in 0..old_pages.max(new_pages) {
+        let mut page = PageComparison {
+            page: page_index + 1,
+            similarity: 0.0,
+            missing_from: None,
+            diff_path: None,
+            error: None,
+        };
+        if page_index >= old_pages || page_index >= new_pages {
+            page.missing_from = Some(if page_index >= old_pages {
+                "old"
+            } else {
+                "new"
+            });
+            pages.push(page);
+            continue;
+        }
+        let result = old_buffer
+            .render(&old, page_index, options.scale, &RenderSettings::default())
+            .and_then(|old_view| {
+                let new_view = new_buffer.render(
+                    &new,
+                    page_index,
+                    options.scale,
+                    &RenderSettings::default(),
+                )?;
+                Ok(diff_image(&old_view, &new_view, options.tolerance))
+            })
+            .and_then(|(diff, similarity)| {
+                page.similarity = similarity;
+                let Some(dir) = out_dir else {
+                    return Ok(());
+                };
+                let path = dir.join(format!("{}_diff_{}.png", stem, page_index + 1));
+                encode::save_image(&encode::view(&diff), &path, &settings)?;
+                page.diff_path = Some(path.to_string_lossy().to_string());
+                Ok(())
+            });
+        if let Err(e) = result {
+            page.error = Some(e.into());
+        }
+        pages.push(page);
+    }
+
+    let similarity = match pages.len() {
+        0 => 1.0,
+        count => pages.iter().map(|page| page.similarity).sum::<f32>() / count as f32,
+    };
+    Ok(Comparison {
+        old_pages,
+        new_pages,
+        similarity,
+        pages,
+    })
+}
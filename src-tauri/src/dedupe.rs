@@ -0,0 +1,70 @@
+// Spots pages that look the same across a batch, e.g. the terms and conditions page
+// repeated in every templated statement.
+
+use crate::encode::RgbaView;
+use image::imageops::{self, FilterType};
+
+// What happens to a page that looks like one converted before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DuplicatePages {
+    // No detection
+    #[default]
+    Keep,
+    // Written as usual and listed in the report
+    Report,
+    // Listed in the report but not written
+    Skip,
+}
+
+// Side of the grayscale thumbnail a page is hashed from; small enough to ignore
+// antialiasing and re-encoding, large enough to tell pages of running text apart.
+const GRID: u32 = 32;
+
+// Pages whose hashes differ in at most this many of their 1024 bits count as the same.
+const MAX_DISTANCE: u32 = 8;
+
+pub(crate) type PageHash = [u64; (GRID * GRID / 64) as usize];
+
+// Difference hash: each bit tells whether a cell of the thumbnail is brighter than
+// its right neighbour.
+pub(crate) fn page_hash(page: &RgbaView) -> PageHash {
+    let gray = imageops::grayscale(page);
+    let small = imageops::resize(&gray, GRID + 1, GRID, FilterType::Triangle);
+    let mut hash = PageHash::default();
+    for y in 0..GRID {
+        for x in 0..GRID {
+            let bit = (y * GRID + x) as usize;
+            if small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0] {
+                hash[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+    }
+    hash
+}
+
+fn distance(a: &PageHash, b: &PageHash) -> u32 {
+    a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum()
+}
+
+// Hashes of the pages converted so far in a batch, with the output each went to.
+#[derive(Default)]
+pub(crate) struct PageHashes {
+    seen: Vec<(PageHash, String)>,
+}
+
+impl PageHashes {
+    // The output of an earlier page that looks like this one. Pages that don't match
+    // any are remembered under `output`.
+    pub fn check(&mut self, hash: PageHash, output: &str) -> Option<String> {
+        if let Some((_, original)) = self
+            .seen
+            .iter()
+            .find(|(seen, _)| distance(seen, &hash) <= MAX_DISTANCE)
+        {
+            return Some(original.clone());
+        }
+        self.seen.push((hash, output.to_string()));
+        None
+    }
+}
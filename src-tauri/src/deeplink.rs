@@ -0,0 +1,203 @@
+use crate::error::{ConvertError, ErrorPayload};
+use crate::jobs::JobRegistry;
+use crate::settings::SettingsStore;
+use crate::{
+    batch_files, shared_pdfium, spawn_job, tray, ConflictPolicy, ConvertOptions, SourceAction,
+};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, Url};
+
+#[derive(Clone, serde::Serialize)]
+struct DeepLinkFailedPayload {
+    url: String,
+    error: ErrorPayload,
+}
+
+fn invalid(url: &Url, message: impl ToString) -> ConvertError {
+    ConvertError::InvalidDeepLink {
+        url: url.to_string(),
+        message: message.to_string(),
+    }
+}
+
+// A conversion a link asked for, waiting for the user to confirm it in the window.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct DeepLinkRequest {
+    id: u64,
+    url: String,
+    paths: Vec<String>,
+    output_dir: String,
+    #[serde(skip)]
+    options: Option<ConvertOptions>,
+}
+
+// Links nobody has answered yet; they're kept until then, as a link that launches
+// the app arrives before the window listens.
+#[derive(Default)]
+pub(crate) struct PendingDeepLinks {
+    requests: Mutex<(u64, Vec<DeepLinkRequest>)>,
+}
+
+// An existing local file; links from the web mustn't make the app fetch URLs or
+// guess at relative paths.
+fn local_file(url: &Url, path: &str) -> Result<String, ConvertError> {
+    let path = match Url::parse(path) {
+        Ok(input) if input.scheme() == "file" => input
+            .to_file_path()
+            .map_err(|()| invalid(url, format!("`{}` is not a local file", path)))?,
+        // Windows paths parse as a URL with the drive letter as the scheme
+        Ok(input) if input.scheme().len() > 1 => {
+            return Err(invalid(url, format!("`{}` is not a local file", path)));
+        }
+        _ => Path::new(path).to_path_buf(),
+    };
+    if !path.is_absolute() || !path.is_file() {
+        return Err(invalid(
+            url,
+            format!("`{}` is not an existing file", path.display()),
+        ));
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+// `pdftoimage://convert?path=/a.pdf&path=/b.pdf&output=/out&dpi=300&format=jpg`. Anyone
+// can send a link, so only rendering settings can be given and everything else starts
+// from the defaults, never the saved settings: no commands, webhooks, moving sources
+// or overwriting. A missing output folder comes from the last-used settings, or is
+// the first PDF's folder.
+fn parse(
+    url: &Url,
+    settings: &SettingsStore,
+) -> Result<(Vec<String>, String, ConvertOptions), ConvertError> {
+    if url.host_str() != Some("convert") {
+        return Err(invalid(url, "only `convert` is supported"));
+    }
+    let mut options = Map::from_iter([
+        ("format".into(), json!("png")),
+        ("scale".into(), json!(2.0)),
+    ]);
+    let mut paths = Vec::new();
+    let mut output_dir = settings.get().output_dir;
+    let number = |value: &str| value.parse::<f32>().map_err(|e| invalid(url, e));
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "path" => paths.push(local_file(url, &value)?),
+            "output" => output_dir = Some(value.to_string()),
+            "format" => {
+                options.insert("format".into(), json!(value));
+            }
+            "dpi" => {
+                options.insert("scale".into(), json!(number(&value)? / 72.0));
+            }
+            "scale" => {
+                options.insert("scale".into(), json!(number(&value)?));
+            }
+            "quality" => match value.parse::<u8>() {
+                Ok(quality @ 1..=100) => {
+                    options.insert("quality".into(), json!(quality));
+                }
+                _ => return Err(invalid(url, format!("quality `{}` is not 1 to 100", value))),
+            },
+            "pages" => {
+                options.insert("page_range".into(), json!(value));
+            }
+            key => return Err(invalid(url, format!("unknown parameter `{}`", key))),
+        }
+    }
+    if paths.is_empty() {
+        return Err(invalid(url, "no `path` given"));
+    }
+    let output_dir = output_dir
+        .or_else(|| {
+            Path::new(&paths[0])
+                .parent()
+                .map(|dir| dir.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| ".".into());
+    let mut options: ConvertOptions =
+        serde_json::from_value(Value::Object(options)).map_err(|e| invalid(url, e))?;
+    // Also the defaults today; set again so a changed default can't open these up
+    options.webhook_url = None;
+    options.source_action = SourceAction::Keep;
+    options.conflict_policy = ConflictPolicy::Rename;
+    options.validate()?;
+    Ok((paths, output_dir, options))
+}
+
+// Asks the window to confirm the conversion a deep link wants; nothing runs before
+// `answer_deep_link` accepts it. Failures go out as `deep_link_failed`, since there's
+// no caller to return them to.
+pub(crate) fn open(app: &AppHandle, url: &Url) {
+    match parse(url, &app.state::<SettingsStore>()) {
+        Ok((paths, output_dir, options)) => {
+            let pending = app.state::<PendingDeepLinks>();
+            let mut requests = pending.requests.lock().unwrap();
+            requests.0 += 1;
+            let request = DeepLinkRequest {
+                id: requests.0,
+                url: url.to_string(),
+                paths,
+                output_dir,
+                options: Some(options),
+            };
+            requests.1.push(request.clone());
+            drop(requests);
+            tray::show_main_window(app);
+            let _ = app.emit("deep_link_request", request);
+        }
+        Err(error) => {
+            let _ = app.emit(
+                "deep_link_failed",
+                DeepLinkFailedPayload {
+                    url: url.to_string(),
+                    error: error.into(),
+                },
+            );
+        }
+    }
+}
+
+// Links still waiting for an answer, for a window that opened after they arrived.
+#[tauri::command]
+pub(crate) fn pending_deep_links(pending: State<'_, PendingDeepLinks>) -> Vec<DeepLinkRequest> {
+    pending.requests.lock().unwrap().1.clone()
+}
+
+// Starts the conversion of a confirmed link, or drops it. The batch reports like any
+// other, through `batch_finished` or `batch_failed`.
+#[tauri::command]
+pub(crate) fn answer_deep_link(
+    app: AppHandle,
+    pending: State<'_, PendingDeepLinks>,
+    id: u64,
+    accept: bool,
+) -> Result<(), ErrorPayload> {
+    let request = {
+        let mut requests = pending.requests.lock().unwrap();
+        let index = requests.1.iter().position(|request| request.id == id);
+        index.map(|index| requests.1.remove(index))
+    };
+    let Some(DeepLinkRequest {
+        paths,
+        output_dir,
+        options: Some(options),
+        ..
+    }) = request.filter(|_| accept)
+    else {
+        return Ok(());
+    };
+    let pdfium = shared_pdfium(&app)?;
+    let files = batch_files(paths, &output_dir, &options)?;
+    let jobs = app.state::<JobRegistry>();
+    spawn_job(
+        app.clone(),
+        Some(pdfium),
+        jobs.register(&output_dir),
+        output_dir,
+        files,
+        options,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use crate::error::ErrorPayload;
+use crate::library::PdfiumLibrary;
+use crate::mupdf;
+use crate::poppler;
+use std::path::{Path, PathBuf};
+
+// What a "Diagnostics" panel or a bug report needs to know about this machine.
+#[derive(serde::Serialize)]
+pub(crate) struct Environment {
+    app_version: String,
+    os: &'static str,
+    arch: &'static str,
+    pdfium_path: Option<String>,
+    pdfium_version: Option<String>,
+    pdfium_error: Option<ErrorPayload>,
+    // The optional second renderer; `None` when `mutool` isn't installed
+    mupdf_version: Option<String>,
+    // Converts plain page exports when pdfium can't be loaded; `None` when poppler's
+    // `pdftoppm` isn't installed
+    poppler_version: Option<String>,
+    fontconfig_path: Option<String>,
+    font_dirs: Vec<String>,
+    output_dir: Option<String>,
+    free_bytes: Option<u64>,
+}
+
+fn font_dirs() -> Vec<String> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut candidates =
+        match std::env::consts::OS {
+            "windows" => vec![PathBuf::from(
+                std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into()),
+            )
+            .join("Fonts")],
+            "macos" => vec![
+                PathBuf::from("/System/Library/Fonts"),
+                PathBuf::from("/Library/Fonts"),
+            ],
+            _ => vec![
+                PathBuf::from("/usr/share/fonts"),
+                PathBuf::from("/usr/local/share/fonts"),
+            ],
+        };
+    if let Some(home) = home {
+        candidates.push(home.join(".local/share/fonts"));
+        candidates.push(home.join(".fonts"));
+        candidates.push(home.join("Library/Fonts"));
+    }
+    candidates
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .map(|dir| dir.to_string_lossy().to_string())
+        .collect()
+}
+
+// statvfs field widths differ between platforms.
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::statvfs(path.as_ptr(), &mut stat) } == 0;
+    ok.then(|| u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    } != 0;
+    ok.then_some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+// The output folder may not exist yet, so the space is measured on its nearest
+// existing ancestor.
+fn free_space_at(output_dir: &Path) -> Option<u64> {
+    output_dir
+        .ancestors()
+        .find(|dir| dir.exists())
+        .and_then(free_space)
+}
+
+pub(crate) fn check(
+    app_version: String,
+    library: &PdfiumLibrary,
+    output_dir: Option<String>,
+) -> Environment {
+    let status = library.status();
+    Environment {
+        app_version,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        pdfium_path: library.location(),
+        pdfium_version: status.as_ref().ok().cloned(),
+        pdfium_error: status.err().map(Into::into),
+        mupdf_version: mupdf::version(),
+        poppler_version: poppler::version(),
+        fontconfig_path: std::env::var("FONTCONFIG_PATH").ok(),
+        font_dirs: font_dirs(),
+        free_bytes: output_dir
+            .as_deref()
+            .and_then(|dir| free_space_at(Path::new(dir))),
+        output_dir,
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::ConvertError;
+use glob::Pattern;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// A PDF to convert, with the folder it was found in relative to the directory that
+// was dropped; empty for files given directly.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct DiscoveredPdf {
+    pub path: String,
+    pub relative_dir: PathBuf,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortOrder {
+    Name,
+    // `doc2.pdf` before `doc10.pdf`
+    #[default]
+    Natural,
+    Mtime,
+    Size,
+}
+
+// Include/exclude globs applied to PDFs found in folders. A pattern is matched
+// against the file name and against the path relative to the scanned folder.
+pub(crate) struct InputFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl InputFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, ConvertError> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| {
+                    Pattern::new(p).map_err(|e| ConvertError::InvalidPattern {
+                        pattern: p.clone(),
+                        message: e.to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(InputFilter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    fn accepts(&self, relative_path: &Path) -> bool {
+        let name = relative_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+        let matches = |p: &Pattern| p.matches(name) || p.matches_path(relative_path);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+pub(crate) fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
+
+pub(crate) fn scan_folder(root: &Path, filter: &InputFilter) -> Vec<DiscoveredPdf> {
+    WalkDir::new(root)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && is_pdf(entry.path()))
+        .filter(|entry| {
+            entry
+                .path()
+                .strip_prefix(root)
+                .is_ok_and(|relative| filter.accepts(relative))
+        })
+        .map(|entry| DiscoveredPdf {
+            path: entry.path().to_string_lossy().to_string(),
+            relative_dir: entry
+                .path()
+                .parent()
+                .and_then(|dir| dir.strip_prefix(root).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+// Replaces every directory among the inputs with the PDFs found below it.
+pub(crate) fn expand_inputs(input_paths: Vec<String>, filter: &InputFilter) -> Vec<DiscoveredPdf> {
+    input_paths
+        .into_iter()
+        .flat_map(|path| {
+            if Path::new(&path).is_dir() {
+                scan_folder(Path::new(&path), filter)
+            } else {
+                vec![DiscoveredPdf {
+                    path,
+                    relative_dir: PathBuf::new(),
+                }]
+            }
+        })
+        .collect()
+}
+
+// Compares runs of digits by value and everything else case-insensitively.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+pub(crate) fn sort_inputs(pdfs: &mut [DiscoveredPdf], order: SortOrder) {
+    let metadata = |pdf: &DiscoveredPdf| std::fs::metadata(&pdf.path).ok();
+    match order {
+        SortOrder::Name => pdfs.sort_by(|a, b| a.path.cmp(&b.path)),
+        SortOrder::Natural => pdfs.sort_by(|a, b| natural_cmp(&a.path, &b.path)),
+        SortOrder::Mtime => {
+            pdfs.sort_by_cached_key(|pdf| metadata(pdf).and_then(|m| m.modified().ok()))
+        }
+        SortOrder::Size => pdfs.sort_by_cached_key(|pdf| metadata(pdf).map(|m| m.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_name_and_relative_path() {
+        let filter =
+            InputFilter::new(&["invoice-*".into(), "2024/*".into()], &["*draft*".into()]).unwrap();
+        assert!(filter.accepts(Path::new("invoice-7.pdf")));
+        assert!(filter.accepts(Path::new("scans/invoice-7.pdf")));
+        assert!(filter.accepts(Path::new("2024/report.pdf")));
+        assert!(!filter.accepts(Path::new("report.pdf")));
+        assert!(!filter.accepts(Path::new("invoice-draft.pdf")));
+        assert!(matches!(
+            InputFilter::new(&["[".into()], &[]),
+            Err(ConvertError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn sorts_numbers_by_value() {
+        let mut pdfs: Vec<DiscoveredPdf> = ["doc10.pdf", "Doc2.pdf", "doc1.pdf", "doc02b.pdf"]
+            .into_iter()
+            .map(|path| DiscoveredPdf {
+                path: path.into(),
+                relative_dir: PathBuf::new(),
+            })
+            .collect();
+        sort_inputs(&mut pdfs, SortOrder::Natural);
+        let paths: Vec<&str> = pdfs.iter().map(|pdf| pdf.path.as_str()).collect();
+        assert_eq!(paths, ["doc1.pdf", "Doc2.pdf", "doc02b.pdf", "doc10.pdf"]);
+        assert_eq!(natural_cmp("page", "page1"), Ordering::Less);
+        assert_eq!(natural_cmp("a007", "A7"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::error::ConvertError;
+use crate::events::EventSink;
+use crate::jobs::BatchControl;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Emitted at most once per this many bytes.
+const PROGRESS_STEP: u64 = 256 * 1024;
+
+static NEXT_DOWNLOAD: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, serde::Serialize)]
+struct DownloadProgressPayload {
+    url: String,
+    received: u64,
+    total: Option<u64>,
+}
+
+pub(crate) fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+// Every request goes through this, so a stalled server fails the download instead of
+// hanging it.
+pub(crate) fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(15))
+        .timeout_read(Duration::from_secs(60))
+        .build()
+}
+
+pub(crate) fn download_failed(url: &str, message: impl ToString) -> ConvertError {
+    ConvertError::DownloadFailed {
+        url: url.to_string(),
+        message: message.to_string(),
+    }
+}
+
+// Last path segment of the URL, so outputs are named after the remote file.
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() && !name.contains(':') => name.to_string(),
+        _ => "download.pdf".to_string(),
+    }
+}
+
+// Fetches a remote input into a temp directory of its own. Returns `None` for local
+// paths; the caller deletes the downloaded file once it has been converted. Cancelling
+// the file stops the download between chunks and fails it.
+pub(crate) fn fetch_if_remote(
+    events: &dyn EventSink,
+    batch: &BatchControl,
+    input: &str,
+) -> Result<Option<PathBuf>, ConvertError> {
+    if !is_url(input) {
+        return Ok(None);
+    }
+    let response = agent()
+        .get(input)
+        .call()
+        .map_err(|e| download_failed(input, e))?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+
+    let dir = std::env::temp_dir().join("pdf-to-image").join(format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_DOWNLOAD.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| ConvertError::io(&dir, e))?;
+    let path = dir.join(file_name(input));
+    let fetched = fetch_into(events, batch, input, response, total, &path);
+    if fetched.is_err() {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    fetched.map(|()| Some(path))
+}
+
+fn fetch_into(
+    events: &dyn EventSink,
+    batch: &BatchControl,
+    input: &str,
+    response: ureq::Response,
+    total: Option<u64>,
+    path: &Path,
+) -> Result<(), ConvertError> {
+    let mut file = std::fs::File::create(path).map_err(|e| ConvertError::io(path, e))?;
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let (mut received, mut reported) = (0u64, 0u64);
+    loop {
+        if batch.file_cancelled(input) {
+            return Err(download_failed(input, "cancelled"));
+        }
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|e| download_failed(input, e))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])
+            .map_err(|e| ConvertError::io(path, e))?;
+        received += read as u64;
+        if received - reported >= PROGRESS_STEP {
+            reported = received;
+            events.emit(
+                "download_progress",
+                DownloadProgressPayload {
+                    url: input.to_string(),
+                    received,
+                    total,
+                },
+            );
+        }
+    }
+    events.emit(
+        "download_progress",
+        DownloadProgressPayload {
+            url: input.to_string(),
+            received,
+            total,
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+// Where conversion progress goes: the webview when running as an app, the terminal
+// when running headless.
+pub(crate) trait EventSink {
+    fn send(&self, event: &str, payload: serde_json::Value);
+}
+
+impl dyn EventSink + '_ {
+    pub fn emit(&self, event: &str, payload: impl serde::Serialize) {
+        if let Ok(payload) = serde_json::to_value(payload) {
+            self.send(event, payload);
+        }
+    }
+}
+
+impl EventSink for AppHandle {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        let _ = Emitter::emit(self, event, payload);
+    }
+}
+
+// Coalesces frequent events such as per-page progress: anything arriving within
+// `interval` of the last one that went out is dropped, unless it's forced through.
+pub(crate) struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        Throttle {
+            interval,
+            last: None,
+        }
+    }
+
+    pub fn ready(&mut self, force: bool) -> bool {
+        let now = Instant::now();
+        let due = self
+            .last
+            .is_none_or(|last| now.duration_since(last) >= self.interval);
+        if force || due {
+            self.last = Some(now);
+        }
+        force || due
+    }
+}
+
+// Average time per page so far, for the throughput and ETA in progress events. Time
+// spent paused between pages isn't counted.
+#[derive(Default)]
+pub(crate) struct PageRate {
+    busy: Duration,
+    pages: u32,
+    current: Option<Instant>,
+}
+
+impl PageRate {
+    pub fn start_page(&mut self) {
+        self.finish_page();
+        self.current = Some(Instant::now());
+    }
+
+    pub fn finish_page(&mut self) {
+        if let Some(started) = self.current.take() {
+            self.busy += started.elapsed();
+            self.pages += 1;
+        }
+    }
+
+    pub fn pages_per_sec(&self) -> Option<f64> {
+        (self.pages > 0 && !self.busy.is_zero())
+            .then(|| f64::from(self.pages) / self.busy.as_secs_f64())
+    }
+
+    pub fn eta_ms(&self, remaining: usize) -> Option<u64> {
+        self.pages_per_sec()
+            .map(|rate| (remaining as f64 / rate * 1000.0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_drops_events_within_the_interval() {
+        let mut throttle = Throttle::new(Duration::from_secs(3600));
+        assert!(throttle.ready(false));
+        assert!(!throttle.ready(false));
+        assert!(throttle.ready(true));
+        let mut unthrottled = Throttle::new(Duration::ZERO);
+        assert!(unthrottled.ready(false));
+        assert!(unthrottled.ready(false));
+    }
+
+    #[test]
+    fn page_rate_counts_finished_pages() {
+        let mut rate = PageRate::default();
+        assert_eq!(rate.pages_per_sec(), None);
+        assert_eq!(rate.eta_ms(10), None);
+        rate.start_page();
+        rate.start_page();
+        rate.finish_page();
+        rate.finish_page();
+        assert_eq!(rate.pages, 2);
+        let rate = PageRate {
+            busy: Duration::from_secs(2),
+            pages: 4,
+            current: None,
+        };
+        assert_eq!(rate.pages_per_sec(), Some(2.0));
+        assert_eq!(rate.eta_ms(3), Some(1500));
+    }
+}
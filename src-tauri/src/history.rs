@@ -0,0 +1,98 @@
+use crate::error::ConvertError;
+use crate::report::BatchReport;
+use crate::ConvertOptions;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Older batches are dropped beyond this.
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct HistoryEntry {
+    pub id: u64,
+    pub started_at: u64, // unix seconds
+    pub duration_ms: u64,
+    pub output_dir: String,
+    pub options: ConvertOptions,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+// Finished batches, newest first, kept in `store_path` so they can be re-run later.
+pub(crate) struct History {
+    entries: Mutex<Vec<HistoryEntry>>,
+    store_path: PathBuf,
+}
+
+impl History {
+    pub fn load(store_path: PathBuf) -> Self {
+        let entries = std::fs::read(&store_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        History {
+            entries: Mutex::new(entries),
+            store_path,
+        }
+    }
+
+    fn persist(&self, entries: &[HistoryEntry]) {
+        if let Some(parent) = self.store_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_vec(entries) {
+            let _ = std::fs::write(&self.store_path, json);
+        }
+    }
+
+    pub fn list(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: u64) -> Option<HistoryEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.id == id)
+            .cloned()
+    }
+
+    pub fn record(&self, output_dir: &str, options: &ConvertOptions, report: &BatchReport) {
+        let mut entries = self.entries.lock().unwrap();
+        let id = entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+        entries.insert(
+            0,
+            HistoryEntry {
+                id,
+                started_at: report.started_at,
+                duration_ms: report.duration_ms,
+                output_dir: output_dir.to_string(),
+                options: options.clone(),
+                inputs: report.files.iter().map(|f| f.input_path.clone()).collect(),
+                outputs: report
+                    .files
+                    .iter()
+                    .flat_map(|f| f.outputs.iter().cloned())
+                    .collect(),
+                succeeded: report.succeeded,
+                failed: report.failed,
+            },
+        );
+        entries.truncate(MAX_ENTRIES);
+        self.persist(&entries);
+    }
+
+    pub fn clear(&self) -> Result<(), ConvertError> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        match std::fs::remove_file(&self.store_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(ConvertError::io(&self.store_path, e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
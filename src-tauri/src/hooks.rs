@@ -0,0 +1,56 @@
+use crate::error::ConvertError;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+
+// The command from the local settings file. Conversion options never carry one, so
+// neither a job nor a link can make the app run commands.
+static POST_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+pub(crate) fn set_post_command(command: Option<String>) {
+    *POST_COMMAND.lock().unwrap() = command.filter(|command| !command.trim().is_empty());
+}
+
+pub(crate) fn post_command() -> Option<String> {
+    POST_COMMAND.lock().unwrap().clone()
+}
+
+// Quotes a value for the platform shell so paths with spaces survive substitution.
+fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+// Runs the user's command for a freshly written output. `{path}`, `{dir}` and `{name}`
+// are replaced with the quoted output path, its folder and its file name.
+pub(crate) fn run_post_command(template: &str, output: &Path) -> Result<(), ConvertError> {
+    let part =
+        |value: Option<&Path>| shell_quote(&value.map(|v| v.to_string_lossy()).unwrap_or_default());
+    let command = template
+        .replace("{path}", &part(Some(output)))
+        .replace("{dir}", &part(output.parent()))
+        .replace("{name}", &part(output.file_name().map(Path::new)));
+
+    let result = if cfg!(windows) {
+        Command::new("cmd").args(["/C", &command]).output()
+    } else {
+        Command::new("sh").args(["-c", &command]).output()
+    };
+    let failed = |message: String| ConvertError::HookFailed {
+        path: output.to_string_lossy().to_string(),
+        message,
+    };
+    let output = result.map_err(|e| failed(e.to_string()))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(failed(if stderr.is_empty() {
+        output.status.to_string()
+    } else {
+        stderr
+    }))
+}
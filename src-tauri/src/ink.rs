@@ -0,0 +1,92 @@
+// How much of each page is printed on, to find blank and nearly blank pages before
+// choosing a page range.
+
+use crate::encode::RgbaView;
+use crate::error::ErrorPayload;
+use crate::render::{PageBuffer, RenderSettings};
+use pdfium_render::prelude::*;
+
+// Channels above this count as paper; scanner noise and paper tint sit just below white.
+const PAPER_LEVEL: u8 = 230;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct BlankPageOptions {
+    pub page_range: String,
+    // Low resolution is plenty to measure coverage and keeps large documents quick
+    pub scale: f32,
+    // Pages with at most this share of inked pixels count as blank
+    pub max_coverage: f32,
+}
+
+impl Default for BlankPageOptions {
+    fn default() -> Self {
+        BlankPageOptions {
+            page_range: String::new(),
+            scale: 0.5,
+            max_coverage: 0.001,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct PageInk {
+    page: usize, // 1-based
+    // Share of pixels that aren't paper, 0 to 1
+    coverage: f32,
+    blank: bool,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct InkReport {
+    pub input_path: String,
+    pub pages: Vec<PageInk>,
+    pub blank_pages: Vec<usize>, // 1-based
+    pub error: Option<ErrorPayload>,
+}
+
+fn coverage(page: &RgbaView) -> f32 {
+    let total = page.width() as usize * page.height() as usize;
+    if total == 0 {
+        return 0.0;
+    }
+    let inked = page
+        .pixels()
+        .filter(|pixel| pixel.0[3] > 0 && pixel.0[..3].iter().any(|&c| c < PAPER_LEVEL))
+        .count();
+    inked as f32 / total as f32
+}
+
+// Measures the given pages. Pages that fail to render are left out, with the last
+// error kept in the report.
+pub(crate) fn measure(
+    document: &PdfDocument,
+    page_indices: &[usize],
+    options: &BlankPageOptions,
+    report: &mut InkReport,
+) {
+    let mut buffer = PageBuffer::for_sizes(std::iter::empty());
+    for &page_index in page_indices {
+        let rendered = buffer.render(
+            document,
+            page_index,
+            options.scale,
+            &RenderSettings::default(),
+        );
+        match rendered {
+            Ok(page) => {
+                let coverage = coverage(&page);
+                let blank = coverage <= options.max_coverage;
+                if blank {
+                    report.blank_pages.push(page_index + 1);
+                }
+                report.pages.push(PageInk {
+                    page: page_index + 1,
+                    coverage,
+                    blank,
+                });
+            }
+            Err(e) => report.error = Some(e.into()),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::error::{ConvertError, ErrorPayload};
+use crate::layers::{self, Layer};
+use crate::paper;
+use pdfium_render::prelude::{PdfDocument, Pdfium};
+use std::io::Read;
+use std::path::Path;
+
+// Readers accept the header anywhere in the first kilobyte.
+const HEADER_WINDOW: usize = 1024;
+
+#[derive(serde::Serialize)]
+pub(crate) struct FileInspection {
+    path: String,
+    exists: bool,
+    readable: bool,
+    size: u64,
+    pdf_header: bool,
+    encrypted: bool,
+    page_count: Option<u16>,
+    // Scale of the document's units to points; large-format drawings go past 1
+    user_unit: f32,
+    // Each distinct physical size, in order of first appearance
+    page_sizes: Vec<PageSize>,
+    // Named layers that can be shown or hidden, e.g. in CAD drawings and maps
+    layers: Vec<Layer>,
+    // Why the file can't be converted; `None` when it looks fine
+    error: Option<ErrorPayload>,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct PageSize {
+    // Rounded to whole millimetres, as shown, i.e. after rotation
+    width_mm: u32,
+    height_mm: u32,
+    pages: usize,
+}
+
+fn page_sizes(document: &PdfDocument, user_unit: f32) -> Vec<PageSize> {
+    let mut sizes: Vec<PageSize> = Vec::new();
+    for page in document.pages().iter() {
+        let (width, height) = paper::size_mm(&page, user_unit);
+        let (width_mm, height_mm) = (width.round() as u32, height.round() as u32);
+        match sizes
+            .iter_mut()
+            .find(|size| size.width_mm == width_mm && size.height_mm == height_mm)
+        {
+            Some(size) => size.pages += 1,
+            None => sizes.push(PageSize {
+                width_mm,
+                height_mm,
+                pages: 1,
+            }),
+        }
+    }
+    sizes
+}
+
+fn not_a_pdf(path: &str, message: &str) -> Option<ErrorPayload> {
+    Some(
+        ConvertError::PdfOpen {
+            path: path.into(),
+            message: message.into(),
+        }
+        .into(),
+    )
+}
+
+// Cheap checks for a dropped path, so bad entries are flagged before a batch starts.
+// Pdfium is optional: without it only the file itself is looked at.
+pub(crate) fn inspect(pdfium: Option<&Pdfium>, path: &str) -> FileInspection {
+    let mut inspection = FileInspection {
+        path: path.to_string(),
+        exists: false,
+        readable: false,
+        size: 0,
+        pdf_header: false,
+        encrypted: false,
+        page_count: None,
+        user_unit: 1.0,
+        page_sizes: Vec::new(),
+        layers: Vec::new(),
+        error: None,
+    };
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => {
+            inspection.error = not_a_pdf(path, "Not a file");
+            return inspection;
+        }
+        Err(e) => {
+            inspection.error = Some(ConvertError::io(Path::new(path), e).into());
+            return inspection;
+        }
+    };
+    inspection.exists = true;
+    inspection.size = metadata.len();
+
+    let mut head = Vec::with_capacity(HEADER_WINDOW);
+    let opened = std::fs::File::open(path)
+        .and_then(|file| file.take(HEADER_WINDOW as u64).read_to_end(&mut head));
+    if let Err(e) = opened {
+        inspection.error = Some(ConvertError::io(Path::new(path), e).into());
+        return inspection;
+    }
+    inspection.readable = true;
+    if inspection.size == 0 {
+        inspection.error = not_a_pdf(path, "File is empty");
+        return inspection;
+    }
+    inspection.pdf_header = head.windows(5).any(|window| window == b"%PDF-");
+    if !inspection.pdf_header {
+        inspection.error = not_a_pdf(path, "No PDF header");
+        return inspection;
+    }
+
+    if let Some(pdfium) = pdfium {
+        match pdfium.load_pdf_from_file(path, None) {
+            Ok(document) => {
+                inspection.page_count = Some(document.pages().len());
+                if let Ok(bytes) = std::fs::read(path) {
+                    inspection.user_unit = paper::user_unit(&bytes);
+                    inspection.layers = layers::layers(&bytes);
+                }
+                inspection.page_sizes = page_sizes(&document, inspection.user_unit);
+            }
+            Err(e) => {
+                let error = ConvertError::pdf_open(path, e);
+                inspection.encrypted = matches!(error, ConvertError::PasswordRequired { .. });
+                inspection.error = Some(error.into());
+            }
+        }
+    }
+    inspection
+}
@@ -0,0 +1,121 @@
+use crate::download::{agent, download_failed};
+use crate::encode::part_path;
+use crate::error::ConvertError;
+use pdfium_render::prelude::Pdfium;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// The pdfium-binaries build matching the API pdfium-render is compiled against
+// (URL-encoded `chromium/7543` tag).
+const RELEASE: &str = "chromium%2F7543";
+const GITHUB: &str = "https://github.com/bblanchon/pdfium-binaries/releases/download/";
+// A mirror of the release downloads (same layout as `GITHUB`) tried before GitHub,
+// for networks that can't reach it.
+const MIRROR_ENV: &str = "PDFIUM_MIRROR";
+
+// SHA-256 of each archive of `RELEASE`, from the release's published digests. Pinned
+// here rather than fetched, so a compromised release or mirror can't vouch for its
+// own download; update them together with `RELEASE`. Platforms without an entry
+// can't be installed from here.
+const SHA256: &[(&str, &str)] = &[];
+
+fn platform() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("linux-x64"),
+        ("linux", "aarch64") => Some("linux-arm64"),
+        ("macos", "x86_64") => Some("mac-x64"),
+        ("macos", "aarch64") => Some("mac-arm64"),
+        ("windows", "x86_64") => Some("win-x64"),
+        ("windows", "x86") => Some("win-x86"),
+        ("windows", "aarch64") => Some("win-arm64"),
+        _ => None,
+    }
+}
+
+fn pinned_sha256(asset_name: &str) -> Result<&'static str, ConvertError> {
+    SHA256
+        .iter()
+        .find(|(name, _)| *name == asset_name)
+        .map(|(_, sha256)| *sha256)
+        .ok_or_else(|| download_failed(asset_name, "No pinned checksum for this archive"))
+}
+
+fn mirrors() -> Vec<String> {
+    let mirror = std::env::var(MIRROR_ENV)
+        .ok()
+        .filter(|mirror| !mirror.is_empty())
+        .map(|mirror| format!("{}/", mirror.trim_end_matches('/')));
+    mirror.into_iter().chain([GITHUB.to_string()]).collect()
+}
+
+fn fetch_verified(asset_name: &str, expected: &str) -> Result<Vec<u8>, ConvertError> {
+    let agent = agent();
+    let mut last_error = None;
+    for mirror in mirrors() {
+        let url = format!("{}{}/{}", mirror, RELEASE, asset_name);
+        let mut bytes = Vec::new();
+        let fetched = agent
+            .get(&url)
+            .call()
+            .map_err(|e| download_failed(&url, e))
+            .and_then(|response| {
+                response
+                    .into_reader()
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| download_failed(&url, e))
+            });
+        let actual = hex::encode(Sha256::digest(&bytes));
+        match fetched {
+            Ok(_) if actual == expected => return Ok(bytes),
+            Ok(_) => {
+                let message = format!("Checksum mismatch: expected {}, got {}", expected, actual);
+                last_error = Some(download_failed(&url, message));
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| download_failed(asset_name, "No mirror to download from")))
+}
+
+// Downloads the prebuilt pdfium for this OS and architecture, checks it against the
+// pinned SHA-256 and unpacks the library into `dir`. Returns the
+// library's path.
+pub(crate) fn install(dir: &Path) -> Result<PathBuf, ConvertError> {
+    let platform = platform().ok_or_else(|| {
+        let target = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+        download_failed(RELEASE, format!("No prebuilt pdfium for {}", target))
+    })?;
+    let asset_name = format!("pdfium-{}.tgz", platform);
+    let expected = pinned_sha256(&asset_name)?;
+    let archive = fetch_verified(&asset_name, expected)?;
+
+    std::fs::create_dir_all(dir).map_err(|e| ConvertError::io(dir, e))?;
+    let library_name = Pdfium::pdfium_platform_library_name();
+    let target = dir.join(&library_name);
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()));
+    let entries = archive
+        .entries()
+        .map_err(|e| download_failed(&asset_name, e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| download_failed(&asset_name, e))?;
+        let is_library = entry
+            .path()
+            .map(|path| path.file_name() == Some(library_name.as_os_str()))
+            .unwrap_or(false);
+        if !is_library {
+            continue;
+        }
+        // Unpacked beside the target and renamed, so no bind ever sees half a library
+        let tmp_path = part_path(&target);
+        entry
+            .unpack(&tmp_path)
+            .map_err(|e| ConvertError::io(&tmp_path, e))?;
+        std::fs::rename(&tmp_path, &target).map_err(|e| ConvertError::io(&target, e))?;
+        return Ok(target);
+    }
+    Err(download_failed(
+        &asset_name,
+        format!("Archive has no {}", library_name.to_string_lossy()),
+    ))
+}
@@ -0,0 +1,275 @@
+use crate::memory::MemoryBudget;
+use crate::report::FileReport;
+use crate::ConvertOptions;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub(crate) struct JobFile {
+    pub input_path: String,
+    pub output_dir: PathBuf,
+    // 0-based page indices to convert; `None` converts the whole selected range
+    pub pages: Option<Vec<usize>>,
+    // Pages already written by an interrupted run; skipped unless merging
+    pub done_pages: Vec<usize>,
+    // Document contents for inputs that never touched disk; `input_path` is then
+    // just a name
+    pub data: Option<Arc<Vec<u8>>>,
+    // Overrides the batch's page range for this document
+    pub page_range: Option<String>,
+}
+
+struct JobRecord {
+    output_dir: String,
+    options: ConvertOptions,
+    failed: Vec<JobFile>,
+}
+
+// Pausing and cancelling for one running batch, so batches running side by side
+// (a watch folder next to the queue, say) don't stop each other.
+#[derive(Default)]
+pub(crate) struct BatchControl {
+    output_dir: String,
+    cancelled: AtomicBool,
+    cancelled_files: Mutex<HashSet<String>>,
+    paused: AtomicBool,
+}
+
+impl BatchControl {
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Blocks between pages while paused; cancelling the file ends the wait.
+    pub fn wait_while_paused(&self, input_path: &str) {
+        while self.is_paused() && !self.file_cancelled(input_path) {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancel_file(&self, input_path: &str) {
+        self.cancelled_files
+            .lock()
+            .unwrap()
+            .insert(input_path.to_string());
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn file_cancelled(&self, input_path: &str) -> bool {
+        self.cancelled() || self.cancelled_files.lock().unwrap().contains(input_path)
+    }
+}
+
+// A batch that has begun; it ends when this is dropped, however the run ends.
+pub(crate) struct RunningBatch<'a> {
+    jobs: &'a JobRegistry,
+    job_id: u64,
+    control: Arc<BatchControl>,
+}
+
+impl Deref for RunningBatch<'_> {
+    type Target = BatchControl;
+
+    fn deref(&self) -> &BatchControl {
+        &self.control
+    }
+}
+
+impl Drop for RunningBatch<'_> {
+    fn drop(&mut self) {
+        self.jobs.end_batch(self.job_id);
+    }
+}
+
+// Remembers what each batch failed on so it can be retried without rebuilding the queue.
+#[derive(Default)]
+pub(crate) struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, JobRecord>>,
+    // Retries that haven't finished yet, and the job each retries
+    pending_retries: Mutex<HashMap<u64, u64>>,
+    running: Mutex<HashMap<u64, Arc<BatchControl>>>,
+    // Where the most recently finished batch wrote to
+    last_output_dir: Mutex<Option<String>>,
+    memory: MemoryBudget,
+}
+
+impl JobRegistry {
+    // A new job id, with its batch registered under the same lock so that pausing or
+    // cancelling between now and `begin_batch` isn't lost. The job must be begun.
+    pub fn register(&self, output_dir: &str) -> u64 {
+        let mut running = self.running.lock().unwrap();
+        let job_id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        running.insert(
+            job_id,
+            Arc::new(BatchControl {
+                output_dir: output_dir.to_string(),
+                ..Default::default()
+            }),
+        );
+        job_id
+    }
+
+    // A new job for retrying `job_id`, which keeps its own record.
+    pub fn register_retry(&self, job_id: u64, output_dir: &str) -> u64 {
+        let retry_id = self.register(output_dir);
+        self.pending_retries
+            .lock()
+            .unwrap()
+            .insert(retry_id, job_id);
+        retry_id
+    }
+
+    // Begins the batch registered as `job_id`, or one that wasn't registered.
+    pub fn begin_batch(&self, job_id: u64, output_dir: &str) -> RunningBatch<'_> {
+        let control = self
+            .running
+            .lock()
+            .unwrap()
+            .entry(job_id)
+            .or_insert_with(|| {
+                Arc::new(BatchControl {
+                    output_dir: output_dir.to_string(),
+                    ..Default::default()
+                })
+            })
+            .clone();
+        RunningBatch {
+            jobs: self,
+            job_id,
+            control,
+        }
+    }
+
+    fn end_batch(&self, job_id: u64) {
+        if let Some(batch) = self.running.lock().unwrap().remove(&job_id) {
+            *self.last_output_dir.lock().unwrap() = Some(batch.output_dir.clone());
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.running.lock().unwrap().is_empty()
+    }
+
+    // The batch `job_id` if it's running, or every running batch for `None`.
+    fn batches(&self, job_id: Option<u64>) -> Vec<Arc<BatchControl>> {
+        let running = self.running.lock().unwrap();
+        match job_id {
+            Some(job_id) => running.get(&job_id).cloned().into_iter().collect(),
+            None => running.values().cloned().collect(),
+        }
+    }
+
+    // The output directory of the batch started last, or else of the one that
+    // finished last.
+    pub fn output_dir(&self) -> Option<String> {
+        let running = self.running.lock().unwrap();
+        match running.iter().max_by_key(|(job_id, _)| **job_id) {
+            Some((_, batch)) => Some(batch.output_dir.clone()),
+            None => self.last_output_dir.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn memory(&self) -> &MemoryBudget {
+        &self.memory
+    }
+
+    pub fn set_paused(&self, job_id: Option<u64>, paused: bool) {
+        for batch in self.batches(job_id) {
+            batch.set_paused(paused);
+        }
+    }
+
+    // Whether any running batch is paused.
+    pub fn is_paused(&self) -> bool {
+        self.batches(None).iter().any(|batch| batch.is_paused())
+    }
+
+    pub fn cancel_batch(&self, job_id: Option<u64>) {
+        for batch in self.batches(job_id) {
+            batch.cancel();
+        }
+    }
+
+    pub fn cancelled(&self, job_id: u64) -> bool {
+        self.batches(Some(job_id))
+            .iter()
+            .any(|batch| batch.cancelled())
+    }
+
+    pub fn cancel_file(&self, job_id: u64, input_path: &str) {
+        for batch in self.batches(Some(job_id)) {
+            batch.cancel_file(input_path);
+        }
+    }
+
+    // Keeps what the job failed on; returns the job it retried, if it was a retry.
+    pub fn record(
+        &self,
+        job_id: u64,
+        output_dir: &str,
+        options: &ConvertOptions,
+        files: &[JobFile],
+        reports: &[FileReport],
+    ) -> Option<u64> {
+        let failed = files
+            .iter()
+            .zip(reports)
+            .filter_map(|(file, report)| retry_target(file, report, options.merge))
+            .collect();
+        let retry_of = self.pending_retries.lock().unwrap().remove(&job_id);
+        self.jobs.lock().unwrap().insert(
+            job_id,
+            JobRecord {
+                output_dir: output_dir.to_string(),
+                options: options.clone(),
+                failed,
+            },
+        );
+        retry_of
+    }
+
+    // Returns the output directory, options and failed files of a finished job.
+    pub fn failed(&self, job_id: u64) -> Option<(String, ConvertOptions, Vec<JobFile>)> {
+        let jobs = self.jobs.lock().unwrap();
+        let record = jobs.get(&job_id)?;
+        Some((
+            record.output_dir.clone(),
+            record.options.clone(),
+            record.failed.clone(),
+        ))
+    }
+}
+
+// Whole files are retried when they failed outright or were merged; otherwise only
+// the pages that errored.
+fn retry_target(file: &JobFile, report: &FileReport, merge: bool) -> Option<JobFile> {
+    if report.status != "success" || (merge && !report.errors.is_empty()) {
+        return Some(JobFile {
+            pages: None,
+            ..file.clone()
+        });
+    }
+    if report.failed_pages.is_empty() {
+        return None;
+    }
+    Some(JobFile {
+        pages: Some(report.failed_pages.iter().map(|p| p - 1).collect()),
+        ..file.clone()
+    })
+}
@@ -0,0 +1,85 @@
+use crate::download::is_url;
+use crate::error::ConvertError;
+use crate::ConvertOptions;
+use std::path::Path;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SpecInput {
+    Path(String),
+    Detailed {
+        path: String,
+        #[serde(default)]
+        page_range: Option<String>,
+    },
+}
+
+impl SpecInput {
+    pub fn path(&self) -> &str {
+        match self {
+            SpecInput::Path(path) | SpecInput::Detailed { path, .. } => path,
+        }
+    }
+
+    pub fn page_range(&self) -> Option<&String> {
+        match self {
+            SpecInput::Path(_) => None,
+            SpecInput::Detailed { page_range, .. } => page_range.as_ref(),
+        }
+    }
+
+    fn path_mut(&mut self) -> &mut String {
+        match self {
+            SpecInput::Path(path) | SpecInput::Detailed { path, .. } => path,
+        }
+    }
+}
+
+// A recurring batch kept in a JSON file. Relative paths are resolved against the
+// file's own folder, so a spec can be checked in next to the documents it converts.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct JobSpec {
+    pub inputs: Vec<SpecInput>,
+    pub output_dir: String,
+    pub options: ConvertOptions,
+}
+
+fn spec_error(path: &Path, message: impl ToString) -> ConvertError {
+    ConvertError::InvalidJobSpec {
+        path: path.to_string_lossy().to_string(),
+        message: message.to_string(),
+    }
+}
+
+impl JobSpec {
+    pub fn load(path: &Path) -> Result<Self, ConvertError> {
+        let bytes = std::fs::read(path).map_err(|e| ConvertError::io(path, e))?;
+        let mut spec: JobSpec = serde_json::from_slice(&bytes).map_err(|e| spec_error(path, e))?;
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let resolve = |p: &mut String| {
+            if !is_url(p) && Path::new(p).is_relative() {
+                *p = base_dir.join(&*p).to_string_lossy().to_string();
+            }
+        };
+        resolve(&mut spec.output_dir);
+        for input in &mut spec.inputs {
+            resolve(input.path_mut());
+        }
+        Ok(spec)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConvertError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| spec_error(path, e))?;
+        std::fs::write(path, json).map_err(|e| ConvertError::io(path, e))
+    }
+
+    // The per-input page range for a document, which may have been found in a folder.
+    pub fn page_range_for(&self, input_path: &str) -> Option<String> {
+        self.inputs
+            .iter()
+            .rev()
+            .find(|input| Path::new(input_path).starts_with(input.path()))
+            .and_then(|input| input.page_range().cloned())
+    }
+}
@@ -0,0 +1,665 @@
+// Optional content groups: the layers of CAD drawings and maps that viewers switch on
+// and off. Pdfium renders them as the document's default configuration sets them and
+// has no call to change that, so other choices are made by appending an update to the
+// file that replaces the configuration, as an editor saving in place would.
+
+use crate::paper::{self, ObjectStream};
+use crate::repair::{find, rfind};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(serde::Serialize)]
+pub(crate) struct Layer {
+    pub name: String,
+    // Whether the document shows it unless told otherwise
+    pub visible: bool,
+}
+
+// An object's number and generation.
+type Reference = (u64, u64);
+
+// Deeper nesting than any real dictionary is treated as damage.
+const MAX_DEPTH: u8 = 32;
+
+fn is_delimiter(b: u8) -> bool {
+    b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b)
+}
+
+fn skip_space(bytes: &[u8], mut i: usize) -> usize {
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'%' => {
+                while bytes.get(i).is_some_and(|&b| b != b'\n' && b != b'\r') {
+                    i += 1;
+                }
+            }
+            _ if b.is_ascii_whitespace() => i += 1,
+            _ => break,
+        }
+    }
+    i
+}
+
+// Where a run of regular characters, such as a number, keyword or name, ends.
+fn run_end(bytes: &[u8], from: usize) -> usize {
+    bytes[from..]
+        .iter()
+        .position(|&b| is_delimiter(b))
+        .map_or(bytes.len(), |len| from + len)
+}
+
+// Where the object starting at `i` ends.
+fn token_end(bytes: &[u8], i: usize, depth: u8) -> Option<usize> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    match *bytes.get(i)? {
+        b'<' if bytes.get(i + 1) == Some(&b'<') => items_end(bytes, i + 2, b">>", depth),
+        b'[' => items_end(bytes, i + 1, b"]", depth),
+        b'(' => {
+            let (mut j, mut open) = (i, 0);
+            loop {
+                match *bytes.get(j)? {
+                    b'\\' => j += 1,
+                    b'(' => open += 1,
+                    b')' => {
+                        open -= 1;
+                        if open == 0 {
+                            return Some(j + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                j += 1;
+            }
+        }
+        b'<' => Some(i + find(&bytes[i..], b">")? + 1),
+        b'/' => Some(run_end(bytes, i + 1)),
+        b if is_delimiter(b) => None,
+        _ => Some(run_end(bytes, i)),
+    }
+}
+
+// Where the objects from `i` up to `close` end, past `close`.
+fn items_end(bytes: &[u8], mut i: usize, close: &[u8], depth: u8) -> Option<usize> {
+    loop {
+        i = skip_space(bytes, i);
+        if bytes.get(i..i + close.len())? == close {
+            return Some(i + close.len());
+        }
+        i = token_end(bytes, i, depth + 1)?;
+    }
+}
+
+fn integer(word: &[u8]) -> Option<u64> {
+    std::str::from_utf8(word).ok()?.parse().ok()
+}
+
+// The keys and values of the dictionary `bytes` starts with; `num gen R` is one value.
+fn entries(bytes: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut i = skip_space(bytes, 0);
+    if !bytes[i..].starts_with(b"<<") {
+        return None;
+    }
+    i += 2;
+    let mut entries = Vec::new();
+    loop {
+        i = skip_space(bytes, i);
+        if bytes.get(i..i + 2)? == b">>" {
+            return Some(entries);
+        }
+        let key_end = token_end(bytes, i, 0)?;
+        let start = skip_space(bytes, key_end);
+        let mut end = token_end(bytes, start, 0)?;
+        let generation = skip_space(bytes, end);
+        if let Some(generation_end) = token_end(bytes, generation, 0) {
+            let r = skip_space(bytes, generation_end);
+            if integer(&bytes[start..end]).is_some()
+                && integer(&bytes[generation..generation_end]).is_some()
+                && bytes.get(r) == Some(&b'R')
+                && bytes.get(r + 1).is_none_or(|&b| is_delimiter(b))
+            {
+                end = r + 1;
+            }
+        }
+        entries.push((&bytes[i..key_end], &bytes[start..end]));
+        i = end;
+    }
+}
+
+fn value<'a>(dict: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    entries(dict)?
+        .into_iter()
+        .find_map(|(name, value)| (name == key).then_some(value))
+}
+
+// The `num gen R` references in a value, such as the array of `/OCGs`.
+fn references(value: &[u8]) -> Vec<Reference> {
+    let words: Vec<&[u8]> = value
+        .split(|&b| b.is_ascii_whitespace() || b == b'[' || b == b']')
+        .filter(|word| !word.is_empty())
+        .collect();
+    words
+        .windows(3)
+        .filter_map(|window| match window {
+            [number, generation, b"R"] => Some((integer(number)?, integer(generation)?)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn reference(value: &[u8]) -> Option<Reference> {
+    match references(value)[..] {
+        [reference] if value.first().is_some_and(u8::is_ascii_digit) => Some(reference),
+        _ => None,
+    }
+}
+
+// A string's text: UTF-16 or UTF-8 with a byte order mark, otherwise PDFDocEncoding,
+// taken as Latin-1.
+fn text(string: &[u8]) -> Option<String> {
+    let inner = string.get(1..string.len().checked_sub(1)?)?;
+    let bytes = match string[0] {
+        b'(' => unescape(inner),
+        b'<' => {
+            let digits: Vec<u8> = inner
+                .iter()
+                .filter_map(|&b| char::from(b).to_digit(16))
+                .map(|digit| digit as u8)
+                .collect();
+            digits
+                .chunks(2)
+                .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+                .collect()
+        }
+        _ => return None,
+    };
+    Some(match bytes.as_slice() {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => bytes.iter().map(|&b| char::from(b)).collect(),
+    })
+}
+
+// The bytes of a literal string between its parentheses.
+fn unescape(literal: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while let Some(&b) = literal.get(i) {
+        i += 1;
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        let Some(&escaped) = literal.get(i) else {
+            break;
+        };
+        i += 1;
+        match escaped {
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'b' => out.push(8),
+            b'f' => out.push(12),
+            b'0'..=b'7' => {
+                let mut code = u32::from(escaped - b'0');
+                // Up to three digits
+                for _ in 0..2 {
+                    match literal.get(i) {
+                        Some(&digit @ b'0'..=b'7') => code = code * 8 + u32::from(digit - b'0'),
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                out.push(code as u8);
+            }
+            // A line break after a backslash continues the string
+            b'\r' => {
+                if literal.get(i) == Some(&b'\n') {
+                    i += 1;
+                }
+            }
+            b'\n' => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+struct Source<'a> {
+    bytes: &'a [u8],
+    // Where `%PDF-` is, which offsets in the file count from
+    start: usize,
+    streams: Vec<ObjectStream>,
+}
+
+// The last cross-reference section, which an update links back to.
+struct Trailer<'a> {
+    offset: u64,
+    // A classic `xref` table rather than a cross-reference stream
+    table: bool,
+    dict: &'a [u8],
+}
+
+impl<'a> Source<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Self> {
+        Some(Source {
+            bytes,
+            start: find(bytes, b"%PDF-")?,
+            streams: paper::object_streams(bytes).collect(),
+        })
+    }
+
+    // The object's value; the last definition wins, as updates are appended.
+    fn object(&self, reference: Reference) -> Option<&[u8]> {
+        let body = self.object_start(reference)?;
+        Some(&body[..token_end(body, 0, 0)?])
+    }
+
+    // Where the object's value starts, up to the end of its file or object stream.
+    fn object_start(&self, (number, generation): Reference) -> Option<&[u8]> {
+        let header = format!("{} {} obj", number, generation);
+        let mut end = self.bytes.len();
+        while let Some(at) = rfind(&self.bytes[..end], header.as_bytes()) {
+            if at == 0 || is_delimiter(self.bytes[at - 1]) {
+                let body = &self.bytes[at + header.len()..];
+                return Some(&body[skip_space(body, 0)..]);
+            }
+            end = at;
+        }
+        // Objects in object streams are all of generation 0
+        if generation != 0 {
+            return None;
+        }
+        self.streams.iter().find_map(|stream| {
+            let header: Vec<&[u8]> = stream
+                .data
+                .get(..stream.first)?
+                .split(u8::is_ascii_whitespace)
+                .filter(|word| !word.is_empty())
+                .collect();
+            let offset = header
+                .chunks_exact(2)
+                .find(|pair| integer(pair[0]) == Some(number))?[1];
+            let offset = usize::try_from(integer(offset)?).ok()?;
+            let body = stream.data.get(stream.first.checked_add(offset)?..)?;
+            Some(&body[skip_space(body, 0)..])
+        })
+    }
+
+    // `value` itself, or the object it refers to.
+    fn resolve<'b>(&'b self, value: &'b [u8]) -> Option<&'b [u8]> {
+        match reference(value) {
+            Some(reference) => self.object(reference),
+            None => Some(value),
+        }
+    }
+
+    fn trailer(&self) -> Option<Trailer<'_>> {
+        let at = rfind(self.bytes, b"startxref")? + 9;
+        let at = skip_space(self.bytes, at);
+        let offset = integer(&self.bytes[at..run_end(self.bytes, at)])?;
+        let section = self
+            .bytes
+            .get(self.start.checked_add(usize::try_from(offset).ok()?)?..)?;
+        let table = section.starts_with(b"xref");
+        let dict = match table {
+            true => &section[find(section, b"trailer")? + 7..],
+            false => &section[find(section, b"<<")?..],
+        };
+        Some(Trailer {
+            offset,
+            table,
+            dict: &dict[skip_space(dict, 0)..],
+        })
+    }
+
+    fn name(&self, group: Reference) -> Option<String> {
+        text(self.resolve(value(self.object(group)?, b"/Name")?)?)
+    }
+
+    // The document's layers, and which of them its default configuration hides.
+    // Pdfium reads the configuration the same way: with everything on to start with,
+    // only the `/OFF` list counts, with everything off only the `/ON` list.
+    fn config(&self, catalog: &[u8]) -> Option<(Vec<Reference>, BTreeSet<Reference>)> {
+        let properties = self.resolve(value(catalog, b"/OCProperties")?)?;
+        let groups = references(self.resolve(value(properties, b"/OCGs")?)?);
+        let Some(default) = value(properties, b"/D").and_then(|config| self.resolve(config)) else {
+            return Some((groups, BTreeSet::new()));
+        };
+        let listed = |key: &[u8]| {
+            value(default, key)
+                .and_then(|list| self.resolve(list))
+                .map(references)
+                .unwrap_or_default()
+        };
+        let hidden = match value(default, b"/BaseState") == Some(b"/OFF") {
+            true => {
+                let shown = listed(b"/ON");
+                groups
+                    .iter()
+                    .filter(|group| !shown.contains(group))
+                    .copied()
+                    .collect()
+            }
+            false => listed(b"/OFF").into_iter().collect(),
+        };
+        Some((groups, hidden))
+    }
+}
+
+// The layers a document has, in the order it lists them. Layers sharing a name, as in
+// drawings with a group per sheet, are listed once.
+pub(crate) fn layers(bytes: &[u8]) -> Vec<Layer> {
+    let mut layers: Vec<Layer> = Vec::new();
+    let Some(source) = Source::new(bytes) else {
+        return layers;
+    };
+    let config = source
+        .trailer()
+        .and_then(|trailer| source.resolve(value(trailer.dict, b"/Root")?))
+        .and_then(|catalog| source.config(catalog));
+    let Some((groups, hidden)) = config else {
+        return layers;
+    };
+    for group in groups {
+        let Some(name) = source.name(group) else {
+            continue;
+        };
+        if !layers.iter().any(|layer| layer.name == name) {
+            layers.push(Layer {
+                name,
+                visible: !hidden.contains(&group),
+            });
+        }
+    }
+    layers
+}
+
+// A copy of the file with the named layers shown (true) or hidden (false), the rest as
+// the document sets them. Names the document doesn't have are ignored, so one choice
+// can cover a batch. `None` when nothing changes or the file can't be updated, e.g.
+// when it's encrypted.
+pub(crate) fn with_visibility(
+    bytes: &[u8],
+    visibility: &BTreeMap<String, bool>,
+) -> Option<Vec<u8>> {
+    let source = Source::new(bytes)?;
+    let trailer = source.trailer()?;
+    if value(trailer.dict, b"/Encrypt").is_some() {
+        return None;
+    }
+    let (number, generation) = reference(value(trailer.dict, b"/Root")?)?;
+    let catalog = source.object((number, generation))?;
+    let (groups, mut hidden) = source.config(catalog)?;
+    let mut changed = false;
+    for &group in &groups {
+        let Some(&visible) = source.name(group).and_then(|name| visibility.get(&name)) else {
+            continue;
+        };
+        changed |= match visible {
+            true => hidden.remove(&group),
+            false => hidden.insert(group),
+        };
+    }
+    if !changed {
+        return None;
+    }
+
+    // The catalog again, with a configuration listing exactly the hidden layers
+    let list = |references: &mut dyn Iterator<Item = &Reference>| {
+        references
+            .map(|(number, generation)| format!("{} {} R", number, generation))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut dict = b"<<".to_vec();
+    for (key, value) in entries(catalog)? {
+        if key != b"/OCProperties" {
+            dict.extend([b" ", key, b" ", value].concat());
+        }
+    }
+    dict.extend(
+        format!(
+            " /OCProperties << /OCGs [{}] /D << /BaseState /ON /OFF [{}] >> >> >>",
+            list(&mut groups.iter()),
+            list(&mut hidden.iter())
+        )
+        .as_bytes(),
+    );
+
+    let mut out = bytes.to_vec();
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    let offset = out.len() - source.start;
+    out.extend(format!("{} {} obj\n", number, generation).as_bytes());
+    out.extend(dict);
+    out.extend(b"\nendobj\n");
+    let xref = out.len() - source.start;
+    let size = integer(value(trailer.dict, b"/Size")?)?;
+    // A cross-reference stream is an object itself and needs a number of its own
+    let new_size = if trailer.table { size } else { size + 1 };
+    let mut tail = format!(
+        "<< /Size {} /Root {} {} R /Prev {}",
+        new_size, number, generation, trailer.offset
+    )
+    .into_bytes();
+    for key in [&b"/Info"[..], b"/ID"] {
+        if let Some(value) = value(trailer.dict, key) {
+            tail.extend([b" ", key, b" ", value].concat());
+        }
+    }
+    if trailer.table {
+        out.extend(
+            format!(
+                "xref\n0 1\n0000000000 65535 f \n{} 1\n{:010} {:05} n \ntrailer\n",
+                number, offset, generation
+            )
+            .as_bytes(),
+        );
+        out.extend(tail);
+        out.extend(b" >>\n");
+    } else {
+        let mut entries = Vec::new();
+        for (offset, generation) in [(offset, generation), (xref, 0)] {
+            entries.push(1u8);
+            entries.extend((offset as u64).to_be_bytes());
+            entries.extend((generation as u16).to_be_bytes());
+        }
+        out.extend(format!("{} 0 obj\n", size).as_bytes());
+        out.extend(tail);
+        out.extend(
+            format!(
+                " /Type /XRef /W [1 8 2] /Index [{} 1 {} 1] /Length {} >>\nstream\n",
+                number,
+                size,
+                entries.len()
+            )
+            .as_bytes(),
+        );
+        out.extend(entries);
+        out.extend(b"\nendstream\nendobj\n");
+    }
+    out.extend(format!("startxref\n{}\n%%EOF\n", xref).as_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    const CATALOG: &[u8] = b"<< /Type /Catalog /Pages 2 0 R /OCProperties << /OCGs [4 0 R 5 0 R] \
+                             /D << /OFF [5 0 R] >> >> >>";
+    const PAGES: &[u8] = b"<< /Type /Pages /Kids [] /Count 0 >>";
+    const TEXT: &[u8] = b"<< /Type /OCG /Name (Text) >>";
+    // "Dimensions" in UTF-16
+    const DIMENSIONS: &[u8] =
+        b"<< /Type /OCG /Name <FEFF00440069006D0065006E00730069006F006E0073> >>";
+
+    fn objects() -> Vec<(u64, Vec<u8>)> {
+        [(1, CATALOG), (2, PAGES), (4, TEXT), (5, DIMENSIONS)]
+            .into_iter()
+            .map(|(number, body)| (number, body.to_vec()))
+            .collect()
+    }
+
+    // A file with the objects written out in full, ending in an `xref` table or, with
+    // `xref_stream`, in a cross-reference stream. `junk` comes before `%PDF-`.
+    fn pdf(junk: &str, objects: &[(u64, Vec<u8>)], xref_stream: bool) -> Vec<u8> {
+        let mut out = format!("{}%PDF-1.5\n", junk).into_bytes();
+        for (number, body) in objects {
+            out.extend(format!("{} 0 obj\n", number).as_bytes());
+            out.extend(body);
+            out.extend(b"\nendobj\n");
+        }
+        let xref = out.len() - junk.len();
+        match xref_stream {
+            true => out.extend(
+                b"9 0 obj\n<< /Type /XRef /Size 10 /Root 1 0 R /Length 0 >>\nstream\n\
+                  \nendstream\nendobj\n",
+            ),
+            false => {
+                out.extend(b"xref\n0 1\n0000000000 65535 f \ntrailer\n<< /Size 6 /Root 1 0 R >>\n")
+            }
+        }
+        out.extend(format!("startxref\n{}\n%%EOF\n", xref).as_bytes());
+        out
+    }
+
+    // A compressed object stream with the given header of object numbers and offsets.
+    fn object_stream(header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(header).unwrap();
+        encoder.write_all(body).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut out = format!(
+            "<< /Type /ObjStm /N 1 /First {} /Filter /FlateDecode /Length {} >>\nstream\n",
+            header.len(),
+            data.len()
+        )
+        .into_bytes();
+        out.extend(data);
+        out.extend(b"\nendstream");
+        out
+    }
+
+    // The objects packed into one object stream, as PDF 1.5 writers do.
+    fn packed(objects: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let (mut header, mut body) = (Vec::new(), Vec::new());
+        for (number, object) in objects {
+            header.extend(format!("{} {} ", number, body.len()).as_bytes());
+            body.extend(object);
+            body.push(b'\n');
+        }
+        object_stream(&header, &body)
+    }
+
+    fn states(bytes: &[u8]) -> Vec<(String, bool)> {
+        layers(bytes)
+            .into_iter()
+            .map(|layer| (layer.name, layer.visible))
+            .collect()
+    }
+
+    fn as_listed() -> Vec<(String, bool)> {
+        vec![("Text".into(), true), ("Dimensions".into(), false)]
+    }
+
+    fn swapped() -> Vec<(String, bool)> {
+        vec![("Text".into(), false), ("Dimensions".into(), true)]
+    }
+
+    fn swap() -> BTreeMap<String, bool> {
+        BTreeMap::from([
+            ("Dimensions".to_string(), true),
+            ("Text".to_string(), false),
+        ])
+    }
+
+    #[test]
+    fn updates_a_file_with_an_xref_table() {
+        let bytes = pdf("", &objects(), false);
+        assert_eq!(states(&bytes), as_listed());
+        let updated = with_visibility(&bytes, &swap()).unwrap();
+        assert!(updated.starts_with(&bytes));
+        let update = String::from_utf8_lossy(&updated[bytes.len()..]);
+        let xref = find(&bytes, b"xref").unwrap();
+        assert!(update.contains("\nxref\n"));
+        assert!(update.contains(&format!("<< /Size 6 /Root 1 0 R /Prev {}", xref)));
+        assert_eq!(states(&updated), swapped());
+    }
+
+    #[test]
+    fn updates_a_file_with_an_xref_stream() {
+        let bytes = pdf("", &objects(), true);
+        assert_eq!(states(&bytes), as_listed());
+        let updated = with_visibility(&bytes, &swap()).unwrap();
+        let update = String::from_utf8_lossy(&updated[bytes.len()..]);
+        // The new cross-reference stream takes the next free number
+        assert!(update.contains("10 0 obj\n<< /Size 11 /Root 1 0 R"));
+        assert!(update.contains("/Type /XRef /W [1 8 2] /Index [1 1 10 1]"));
+        assert_eq!(states(&updated), swapped());
+    }
+
+    #[test]
+    fn reads_layers_from_object_streams() {
+        let objects = [(1, CATALOG.to_vec()), (3, packed(&objects()[1..]))];
+        let bytes = pdf("", &objects, true);
+        assert_eq!(states(&bytes), as_listed());
+        let updated = with_visibility(&bytes, &swap()).unwrap();
+        assert_eq!(states(&updated), swapped());
+    }
+
+    #[test]
+    fn offsets_count_from_the_header() {
+        let bytes = pdf("Content-Type: application/pdf\n\n", &objects(), false);
+        assert_eq!(states(&bytes), as_listed());
+        let updated = with_visibility(&bytes, &swap()).unwrap();
+        assert_eq!(states(&updated), swapped());
+    }
+
+    #[test]
+    fn leaves_files_without_changes_alone() {
+        let bytes = pdf("", &objects(), false);
+        let unchanged = BTreeMap::from([("Text".to_string(), true), ("Other".to_string(), false)]);
+        assert!(with_visibility(&bytes, &unchanged).is_none());
+    }
+
+    #[test]
+    fn overflowing_offsets_are_ignored() {
+        let mut bytes = pdf("junk", &objects(), false);
+        bytes.truncate(rfind(&bytes, b"startxref").unwrap());
+        bytes.extend(format!("startxref\n{}\n%%EOF\n", u64::MAX).as_bytes());
+        assert!(layers(&bytes).is_empty());
+        assert!(with_visibility(&bytes, &swap()).is_none());
+
+        let header = format!("4 {} ", u64::MAX);
+        let objects = [
+            (1, CATALOG.to_vec()),
+            (3, object_stream(header.as_bytes(), TEXT)),
+        ];
+        assert!(layers(&pdf("", &objects, true)).is_empty());
+    }
+
+    #[test]
+    fn gives_up_on_malformed_files() {
+        for bytes in [
+            &b""[..],
+            b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /OCProperties << /OCGs [4 0 R",
+            b"%PDF-1.4\nstartxref\n99999\n%%EOF",
+            b"%PDF-1.4\nxref\ntrailer\n<< /Root 1 0 R >>\nstartxref\n9\n%%EOF",
+            b"%PDF-1.4\n1 0 obj\n<< (((( >>\nendobj\ntrailer\n<< /Root 1 0 R >>\nstartxref\n9",
+        ] {
+            assert!(layers(bytes).is_empty());
+            assert!(with_visibility(bytes, &swap()).is_none());
+        }
+    }
+}
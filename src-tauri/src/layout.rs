@@ -0,0 +1,258 @@
+// Sheets that carry several PDF pages each. They're built as a new in-memory document
+// that takes the source's place for rendering, so every sheet becomes one output image.
+
+use pdfium_render::prelude::*;
+
+// Pages per sheet in a grid, filled left to right, then top to bottom.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct NUp {
+    pub columns: u8,
+    pub rows: u8,
+    // Sheet size in points; A4, turned landscape for wide grids, when unset
+    pub sheet_width: Option<f32>,
+    pub sheet_height: Option<f32>,
+}
+
+impl Default for NUp {
+    fn default() -> Self {
+        NUp {
+            columns: 2,
+            rows: 1,
+            sheet_width: None,
+            sheet_height: None,
+        }
+    }
+}
+
+// Two pages side by side on each sheet, ordered so the sheets, printed double-sided
+// and flipped on the short edge, stack and fold into a booklet.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct Booklet {
+    // Sheet size in points; landscape A4 when unset
+    pub sheet_width: Option<f32>,
+    pub sheet_height: Option<f32>,
+}
+
+// Small thumbnails of the selected pages with their page numbers under them and the
+// document title on top, for reviewing long documents at a glance.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct ContactSheet {
+    pub columns: u8,
+    pub rows: u8,
+    // Heading of every sheet; the file name when unset
+    pub title: Option<String>,
+    // Sheet size in points; portrait A4 when unset
+    pub sheet_width: Option<f32>,
+    pub sheet_height: Option<f32>,
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        ContactSheet {
+            columns: 4,
+            rows: 5,
+            title: None,
+            sheet_width: None,
+            sheet_height: None,
+        }
+    }
+}
+
+// Contact sheet spacing in points: the band for the title, the band for each caption
+// and the gap around thumbnails.
+const TITLE_HEIGHT: f32 = 36.0;
+const CAPTION_HEIGHT: f32 = 14.0;
+const GAP: f32 = 8.0;
+
+fn sheet_size(width: Option<f32>, height: Option<f32>, landscape: bool) -> PdfPagePaperSize {
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            PdfPagePaperSize::Custom(PdfPoints::new(width), PdfPoints::new(height))
+        }
+        _ if landscape => PdfPagePaperSize::a4().landscape(),
+        _ => PdfPagePaperSize::a4(),
+    }
+}
+
+// Pdfium's page range syntax for zero-based indices, e.g. "3,1,2".
+fn page_list(indices: impl Iterator<Item = usize>) -> String {
+    let pages: Vec<String> = indices.map(|i| (i + 1).to_string()).collect();
+    pages.join(",")
+}
+
+// A new document holding `pages` of `source`, in that order.
+fn copy_pages<'a>(
+    pdfium: &'a Pdfium,
+    source: &PdfDocument,
+    pages: &str,
+) -> Result<PdfDocument<'a>, PdfiumError> {
+    let mut copy = pdfium.create_new_pdf()?;
+    copy.pages_mut()
+        .copy_pages_from_document(source, pages, 0)?;
+    Ok(copy)
+}
+
+// Pdfium ties a tiled document to the one it was tiled from, so sheets are saved and
+// reloaded to stand on their own.
+fn reload<'a>(pdfium: &'a Pdfium, document: &PdfDocument) -> Result<PdfDocument<'a>, PdfiumError> {
+    pdfium.load_pdf_from_byte_vec(document.save_to_bytes()?, None)
+}
+
+// Tiles every page of `pages` onto sheets.
+fn tile<'a>(
+    pdfium: &'a Pdfium,
+    pages: &PdfDocument,
+    rows: u8,
+    columns: u8,
+    size: PdfPagePaperSize,
+) -> Result<PdfDocument<'a>, PdfiumError> {
+    let sheets = pages
+        .pages()
+        .tile_into_new_document(rows.max(1), columns.max(1), size)?;
+    reload(pdfium, &sheets)
+}
+
+// A line of text whose bottom-left corner lands at `x`, `y`, or which is centered on
+// `x` when `centered` is set.
+fn add_text<'a>(
+    document: &PdfDocument<'a>,
+    page: &mut PdfPage<'a>,
+    text: &str,
+    font: PdfFontToken,
+    size: f32,
+    (x, y): (f32, f32),
+    centered: bool,
+) -> Result<(), PdfiumError> {
+    let mut object = PdfPageTextObject::new(document, text, font, PdfPoints::new(size))?;
+    let bounds = object.bounds()?;
+    let x = match centered {
+        true => x - bounds.width().value / 2.0,
+        false => x,
+    };
+    object.translate(
+        PdfPoints::new(x - bounds.left().value),
+        PdfPoints::new(y - bounds.bottom().value),
+    )?;
+    page.objects_mut().add_text_object(object)?;
+    Ok(())
+}
+
+pub(crate) fn n_up<'a>(
+    pdfium: &'a Pdfium,
+    source: &PdfDocument,
+    page_indices: &[usize],
+    layout: &NUp,
+) -> Result<PdfDocument<'a>, PdfiumError> {
+    let selected = copy_pages(pdfium, source, &page_list(page_indices.iter().copied()))?;
+    let size = sheet_size(
+        layout.sheet_width,
+        layout.sheet_height,
+        layout.columns > layout.rows,
+    );
+    tile(pdfium, &selected, layout.rows, layout.columns, size)
+}
+
+pub(crate) fn booklet<'a>(
+    pdfium: &'a Pdfium,
+    source: &PdfDocument,
+    page_indices: &[usize],
+    layout: &Booklet,
+) -> Result<PdfDocument<'a>, PdfiumError> {
+    let mut selected = copy_pages(pdfium, source, &page_list(page_indices.iter().copied()))?;
+    // Blank pages at the back make up whole sheets of four pages
+    let first = selected.pages().get(0)?;
+    let size = PdfPagePaperSize::from_points(first.width(), first.height());
+    drop(first);
+    while selected.pages().len() % 4 != 0 {
+        selected.pages_mut().create_page_at_end(size)?;
+    }
+    // Each sheet's front carries the last and first pages not yet placed, its back the
+    // next two inwards: for 8 pages, 8 1 | 2 7 | 6 3 | 4 5
+    let count = selected.pages().len() as usize;
+    let order = (0..count / 2).flat_map(|k| {
+        if k % 2 == 0 {
+            [count - 1 - k, k]
+        } else {
+            [k, count - 1 - k]
+        }
+    });
+    let imposed = copy_pages(pdfium, &selected, &page_list(order))?;
+    let size = sheet_size(layout.sheet_width, layout.sheet_height, true);
+    tile(pdfium, &imposed, 1, 2, size)
+}
+
+// Tiles the pages like `n_up`, then shrinks each tile to leave room for its page
+// number below it and the title above the grid. Each tile is a single form object, in
+// page order.
+pub(crate) fn contact_sheet<'a>(
+    pdfium: &'a Pdfium,
+    source: &PdfDocument,
+    page_indices: &[usize],
+    layout: &ContactSheet,
+    name: &str,
+) -> Result<PdfDocument<'a>, PdfiumError> {
+    let selected = copy_pages(pdfium, source, &page_list(page_indices.iter().copied()))?;
+    let (columns, rows) = (layout.columns.max(1), layout.rows.max(1));
+    let size = sheet_size(layout.sheet_width, layout.sheet_height, false);
+    let mut sheets = selected
+        .pages()
+        .tile_into_new_document(rows, columns, size)?;
+    let font = sheets.fonts_mut().helvetica();
+    let bold = sheets.fonts_mut().helvetica_bold();
+    let title = layout.title.as_deref().unwrap_or(name);
+
+    let (width, height) = (size.width().value, size.height().value);
+    let cell_width = width / f32::from(columns);
+    let cell_height = (height - TITLE_HEIGHT) / f32::from(rows);
+    let (box_width, box_height) = (
+        cell_width - 2.0 * GAP,
+        cell_height - CAPTION_HEIGHT - 2.0 * GAP,
+    );
+    let per_sheet = usize::from(columns) * usize::from(rows);
+    let sheet_count = sheets.pages().len();
+    for sheet_index in 0..sheet_count {
+        let mut sheet = sheets.pages().get(sheet_index)?;
+        sheet.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::Manual);
+        let mut captions = Vec::new();
+        for tile in 0..sheet.objects().len() {
+            let mut object = sheet.objects().get(tile)?;
+            let bounds = object.bounds()?;
+            let (tile_width, tile_height) = (bounds.width().value, bounds.height().value);
+            if tile_width <= 0.0 || tile_height <= 0.0 {
+                continue;
+            }
+            let fit = (box_width / tile_width).min(box_height / tile_height);
+            let left = (tile % usize::from(columns)) as f32 * cell_width + GAP;
+            let bottom =
+                height - TITLE_HEIGHT - (tile / usize::from(columns) + 1) as f32 * cell_height
+                    + CAPTION_HEIGHT
+                    + GAP;
+            // Centered across the cell and sitting on the caption
+            object.translate(-bounds.left(), -bounds.bottom())?;
+            object.scale(fit, fit)?;
+            object.translate(
+                PdfPoints::new(left + (box_width - tile_width * fit) / 2.0),
+                PdfPoints::new(bottom),
+            )?;
+            if let Some(page_index) = page_indices.get(usize::from(sheet_index) * per_sheet + tile)
+            {
+                let center = left + box_width / 2.0;
+                captions.push(((page_index + 1).to_string(), (center, bottom - 11.0)));
+            }
+        }
+        for (text, origin) in captions {
+            add_text(&sheets, &mut sheet, &text, font, 8.0, origin, true)?;
+        }
+        let heading = match sheet_count {
+            1 => title.to_string(),
+            _ => format!("{} ({} / {})", title, sheet_index + 1, sheet_count),
+        };
+        let origin = (GAP, height - TITLE_HEIGHT + GAP);
+        add_text(&sheets, &mut sheet, &heading, bold, 14.0, origin, false)?;
+        sheet.regenerate_content()?;
+    }
+    reload(pdfium, &sheets)
+}
@@ -1,20 +1,760 @@
+mod attachments;
+mod autoformat;
+mod cache;
+mod cli;
+mod compare;
+mod dedupe;
+mod deeplink;
+mod diagnostics;
+mod discover;
+mod download;
+mod events;
+mod history;
+mod hooks;
+mod ink;
+mod inspect;
+mod installer;
+mod jobs;
+mod jobspec;
+mod layers;
+mod layout;
+mod memory;
+mod merge;
+mod paper;
+mod presets;
+mod print;
+mod priority;
+mod queue;
+mod repair;
+mod report;
+mod scheduler;
+mod session;
+mod settings;
+mod tray;
+mod watch;
+mod webhook;
+
+use adjust::{Adjustments, Border, Crop, Sharpen};
+use cache::RenderCache;
+use cmyk::CmykConversion;
+use dedupe::{DuplicatePages, PageHashes};
+use discover::{expand_inputs, sort_inputs, DiscoveredPdf, InputFilter, SortOrder};
+use encode::{
+    encode_image, encode_to_vec, output_extension, save_bytes, save_image, EncodeSettings, RgbaView,
+};
+use error::{ConvertError, ErrorPayload, Reason};
+use events::{EventSink, PageRate, Throttle};
+use history::{History, HistoryEntry};
+use jobs::{BatchControl, JobFile, JobRegistry};
+use jobspec::JobSpec;
+use layout::{Booklet, ContactSheet, NUp};
+use library::PdfiumLibrary;
+use merge::MergedOutput;
+use paper::{Orientation, SizeFilter};
+use pdf2img_core::pages::parse_page_range;
+use pdf2img_core::{
+    adjust, cmyk, encode, error, fonts, i18n, library, metadata, mupdf, poppler, render, stamp, svg,
+};
 use pdfium_render::prelude::*;
-use std::path::Path;
-use tauri::{Emitter, Manager, Window};
+use presets::{Preset, PresetStore};
+use queue::{ConversionQueue, QueueEntry, QueueState};
+use render::{Backend, PageBox, PageBuffer, RenderSettings};
+use report::{BatchReport, DuplicatePage, FileReport, PageMetrics, ReportFormat};
+use session::SessionLog;
+use settings::{Settings, SettingsStore};
+use stamp::{Caption, PageNumbers, Stamper, Stamps, Watermark};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State, WindowEvent};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_opener::OpenerExt;
+use watch::{FolderWatch, WatchConfig};
 
 #[derive(Clone, serde::Serialize)]
 struct ProgressPayload {
     filename: String,
     current: usize,
     total: usize,
+    // The page about to be rendered: its number in the document and pixel size
+    page: usize,
+    width: u64,
+    height: u64,
+    // Latest output of the file so far, i.e. the previous page's
+    output_path: Option<String>,
+    // Unknown until the first page of the file is done
+    pages_per_sec: Option<f64>,
+    eta_ms: Option<u64>,
 }
 
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Default, serde::Serialize)]
 struct FileStatusPayload {
     filename: String,
-    status: String, // "queued", "processing", "success", "error"
+    status: String, // "queued", "processing", "success", "error", "skipped", "cancelled"
     error: Option<String>,
     output_path: Option<String>,
+    conflict_action: Option<String>, // "overwritten", "skipped", "renamed", "failed"
+    error_detail: Option<ConvertError>,
+    // The PDF only opened after repairing it
+    repaired: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct PageDonePayload {
+    filename: String,
+    #[serde(flatten)]
+    metrics: PageMetrics,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct MissingFontsPayload {
+    filename: String,
+    fonts: Vec<String>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct PageClampedPayload {
+    filename: String,
+    page: usize,
+    // The size asked for and the size rendered, in pixels
+    requested_width: u64,
+    requested_height: u64,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct BatchProgressPayload {
+    job_id: u64,
+    files_done: usize,
+    files_total: usize,
+    pages_done: usize,
+    pages_total: usize,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct JobStarted {
+    job_id: u64,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct BatchFailedPayload {
+    job_id: u64,
+    error: ErrorPayload,
+}
+
+// Keeps the error for the batch report and the file's final status.
+fn record_error(report: &mut FileReport, error: ConvertError) {
+    report.errors.push(error.into());
+}
+
+// The file's one final status. A file that failed carries its last error; one that
+// converted may still carry the error of a page that failed along the way.
+fn emit_file_status(
+    events: &dyn EventSink,
+    report: &FileReport,
+    output_path: Option<String>,
+    conflict_action: Option<&str>,
+) {
+    let error = report.errors.last();
+    let failed_on_conflict = report.status == "error"
+        && matches!(
+            error.map(|e| &e.error),
+            Some(ConvertError::OutputExists { .. })
+        );
+    events.emit(
+        "file_status",
+        FileStatusPayload {
+            filename: report.filename.clone(),
+            status: report.status.clone(),
+            error: error.map(|e| e.message.clone()),
+            output_path,
+            conflict_action: conflict_action
+                .or(failed_on_conflict.then_some("failed"))
+                .map(Into::into),
+            error_detail: error.map(|e| e.error.clone()),
+            repaired: report.repaired,
+        },
+    );
+}
+
+// Ends a file that couldn't be converted.
+fn fail_file(events: &dyn EventSink, report: &mut FileReport, error: ConvertError) {
+    report.status = "error".into();
+    record_error(report, error);
+    emit_file_status(events, report, None, None);
+}
+
+// Ends a file cancelled part way through.
+fn cancelled_file(events: &dyn EventSink, mut report: FileReport, started: Instant) -> FileReport {
+    report.status = "cancelled".into();
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    emit_file_status(events, &report, None, None);
+    report
+}
+
+// Keeps the page's timings for the report and, if asked for, reports them live.
+fn record_page(
+    events: &dyn EventSink,
+    report: &mut FileReport,
+    options: &ConvertOptions,
+    metrics: PageMetrics,
+) {
+    if options.page_events {
+        events.emit(
+            "page_done",
+            PageDonePayload {
+                filename: report.filename.clone(),
+                metrics: metrics.clone(),
+            },
+        );
+    }
+    report.pages.push(metrics);
+}
+
+fn run_output_hook(report: &mut FileReport, out_path: &Path) {
+    if let Some(command) = hooks::post_command() {
+        if let Err(e) = hooks::run_post_command(&command, out_path) {
+            record_error(report, e);
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct PlannedOutput {
+    pages: Vec<usize>,
+    output_path: Option<String>,
+    width: u32,
+    height: u32,
+    conflict_action: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct PlannedFile {
+    input_path: String,
+    filename: String,
+    outputs: Vec<PlannedOutput>,
+    error: Option<ErrorPayload>,
+}
+
+#[derive(serde::Serialize)]
+struct FileSizeEstimate {
+    input_path: String,
+    pages: usize,
+    sampled_pages: usize,
+    estimated_bytes: u64,
+    error: Option<ErrorPayload>,
+}
+
+#[derive(serde::Serialize)]
+struct SizeEstimate {
+    files: Vec<FileSizeEstimate>,
+    total_pages: usize,
+    estimated_bytes: u64,
+}
+
+#[derive(serde::Serialize)]
+struct ConversionPlan {
+    files: Vec<PlannedFile>,
+    total_pages: usize,
+    total_outputs: usize,
+}
+
+fn default_quality() -> u8 {
+    90
+}
+
+fn default_progress_interval() -> u64 {
+    100
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct ConvertOptions {
+    format: String,
+    scale: f32,
+    // Pages that would render past this many megapixels are rendered at a lower scale,
+    // with a `page_clamped` warning
+    #[serde(default)]
+    max_megapixels: Option<f32>,
+    // Scales for page ranges that differ from `scale`; the first range a page is in
+    // wins. With a layout, ranges count sheets.
+    #[serde(default)]
+    page_scales: Vec<PageScale>,
+    #[serde(default)]
+    page_range: String,
+    // Only pages of this orientation within `page_range`, e.g. the wide charts of a
+    // report
+    #[serde(default)]
+    orientation: Option<Orientation>,
+    // Only pages of these paper sizes or within these bounds, e.g. the drawings of an
+    // engineering set with `{ "min": "A3" }`
+    #[serde(default)]
+    page_size: Option<SizeFilter>,
+    // Layers to show (true) or hide (false) by name, e.g. `{ "Dimensions": false }`;
+    // the others stay as the document sets them
+    #[serde(default)]
+    layers: BTreeMap<String, bool>,
+    // The engine that draws pages; MuPDF needs `mutool` installed and can't be combined
+    // with options that change pages before they're drawn
+    #[serde(default)]
+    backend: Backend,
+    #[serde(default)]
+    merge: bool,
+    #[serde(default = "default_quality")]
+    quality: u8,
+    // Zero-pad width for page numbers; derived from the page count when unset
+    #[serde(default)]
+    page_digits: Option<usize>,
+    // e.g. `{name}-p{page}`; the extension is appended and `{page}` is `merged` for
+    // merged output
+    #[serde(default)]
+    name_template: Option<String>,
+    #[serde(default)]
+    subfolder_per_document: bool,
+    #[serde(default)]
+    conflict_policy: ConflictPolicy,
+    #[serde(default)]
+    dry_run: bool,
+    // Upper bound for each JPEG/WebP output; quality is lowered until the page fits
+    #[serde(default)]
+    max_bytes_per_page: Option<u64>,
+    #[serde(default)]
+    webp_lossless: bool,
+    #[serde(default)]
+    report_format: Option<ReportFormat>,
+    #[serde(default)]
+    on_error: ErrorPolicy,
+    // Leave outputs alone that are newer than their source PDF
+    #[serde(default)]
+    skip_up_to_date: bool,
+    // Reuse encoded pages from earlier runs with the same document and settings
+    #[serde(default)]
+    render_cache: bool,
+    // Recreate the layout of dropped folders below the output directory
+    #[serde(default)]
+    mirror_folders: bool,
+    // Glob patterns for PDFs found in folders, e.g. `invoices_*.pdf`
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    sort: SortOrder,
+    // Receives the batch report as JSON when the batch finishes
+    #[serde(default)]
+    webhook_url: Option<String>,
+    // Approximate ceiling for rendered pages and merge buffers held at once; pages
+    // wait for running work to free memory and fail if they could never fit
+    #[serde(default)]
+    memory_limit_mb: Option<u64>,
+    // Minimum gap between `progress` events of a file; 0 sends one per page
+    #[serde(default = "default_progress_interval")]
+    progress_interval_ms: u64,
+    // Emit `page_done` with each page's timings and output size
+    #[serde(default)]
+    page_events: bool,
+    // Run the batch at background thread priority
+    #[serde(default)]
+    background_priority: bool,
+    // Pause between pages to leave CPU for other work
+    #[serde(default)]
+    throttle_ms: Option<u64>,
+    // What happens to a source PDF once it converted without errors
+    #[serde(default)]
+    source_action: SourceAction,
+    // Destination for `move`; a `processed` folder next to the source when unset
+    #[serde(default)]
+    processed_dir: Option<String>,
+    // Which page boundary to render: media, crop, bleed or trim box
+    #[serde(default)]
+    page_box: PageBox,
+    // Render pages upright even when their /Rotate entry says otherwise, for
+    // generators that write bogus values
+    #[serde(default)]
+    ignore_page_rotation: bool,
+    // Clockwise degrees (0, 90, 180, 270) every page is shown at, replacing its own
+    #[serde(default)]
+    rotation: Option<u16>,
+    // Flatten form fields so their filled-in values are drawn
+    #[serde(default)]
+    flatten_forms: bool,
+    // Render without highlights, comments and stamps
+    #[serde(default)]
+    hide_annotations: bool,
+    // Export each page's annotations alone on a transparent background, to overlay on
+    // the original elsewhere; needs PNG or WebP to keep the transparency
+    #[serde(default)]
+    annotations_only: bool,
+    // Leave text out of the render (graphics only) or images out of it
+    #[serde(default)]
+    hide_text: bool,
+    #[serde(default)]
+    hide_images: bool,
+    // Sub-pixel text antialiasing; sharper small text on screens, colour fringes in print
+    #[serde(default)]
+    lcd_text: bool,
+    // Invert lightness for dark-background pages, leaving embedded photos as they are
+    #[serde(default)]
+    dark_mode: bool,
+    // Brightness, contrast, gamma and levels applied before encoding
+    #[serde(default)]
+    adjustments: Option<Adjustments>,
+    // Unsharp mask for text that turns soft at small scales
+    #[serde(default)]
+    sharpen: Option<Sharpen>,
+    // Cut a share of each side off every page, e.g. scanner edges; before the border
+    #[serde(default)]
+    crop: Option<Crop>,
+    // Padding, outline and drop shadow around each page
+    #[serde(default)]
+    border: Option<Border>,
+    // Text or logo drawn over every page
+    #[serde(default)]
+    watermark: Option<Watermark>,
+    #[serde(default)]
+    page_numbers: Option<PageNumbers>,
+    // Text lines across the top and bottom of every page
+    #[serde(default)]
+    header: Option<Caption>,
+    #[serde(default)]
+    footer: Option<Caption>,
+    // Several pages per output image, e.g. 2-up or 4-up handouts
+    #[serde(default)]
+    n_up: Option<NUp>,
+    // Pages imposed two to a sheet for double-sided printing and folding; takes the
+    // place of `n_up`
+    #[serde(default)]
+    booklet: Option<Booklet>,
+    // Overview images of page thumbnails with page numbers and the document title;
+    // takes the place of both layouts above
+    #[serde(default)]
+    contact_sheet: Option<ContactSheet>,
+    // Report or leave out pages that look like one converted earlier in the batch;
+    // merged outputs keep every page
+    #[serde(default)]
+    duplicate_pages: DuplicatePages,
+    // Write the source file, page number, PDF title and author, DPI and conversion
+    // time into each page's EXIF; merged outputs go without
+    #[serde(default)]
+    embed_provenance: bool,
+    // Guarantee outputs carry no EXIF, XMP or text chunks, for privacy-sensitive
+    // publishing; wins over `embed_provenance`
+    #[serde(default)]
+    strip_metadata: bool,
+    // Write CMYK TIFFs for print, whatever `format` says
+    #[serde(default)]
+    cmyk: bool,
+    // ICC output profile of the printing process the CMYK conversion goes through; a
+    // simple formula when unset
+    #[serde(default)]
+    cmyk_profile: Option<String>,
+    // 16 bits per channel in PNG and TIFF outputs, for renders that get tone-adjusted
+    // afterwards; pages still render at 8 bits, so this only buys headroom
+    #[serde(default)]
+    sixteen_bit: bool,
+    // Pick each page's format from its content: PNG for text and line art, `format`
+    // (JPEG or WebP) for photographic pages; merged outputs keep `format`
+    #[serde(default)]
+    smart_format: bool,
+    // Retry PDFs that fail to open with junk around them stripped and a missing
+    // trailer rebuilt
+    #[serde(default)]
+    repair: bool,
+    // Show a desktop notification for batches that finish while the window is in the
+    // background
+    #[serde(default = "default_true")]
+    notify_on_finish: bool,
+}
+
+impl ConvertOptions {
+    fn encode_settings(&self) -> EncodeSettings<'static> {
+        EncodeSettings {
+            ext: match self.cmyk {
+                true => "tiff",
+                false => output_extension(&self.format),
+            },
+            quality: self.quality,
+            lossless: self.webp_lossless,
+            max_bytes: self.max_bytes_per_page,
+            exif: None,
+            strip_metadata: self.strip_metadata,
+            cmyk: None,
+            sixteen_bit: self.sixteen_bit,
+        }
+    }
+
+    // Loaded once per batch, as reading the ICC profile and building the transform
+    // costs more than converting a page.
+    fn cmyk_conversion(&self) -> Result<Option<CmykConversion>, ConvertError> {
+        self.cmyk
+            .then(|| CmykConversion::load(self.cmyk_profile.as_deref()))
+            .transpose()
+    }
+
+    fn stamps(&self) -> Stamps<'_> {
+        Stamps {
+            watermark: self.watermark.as_ref(),
+            page_numbers: self.page_numbers.as_ref(),
+            header: self.header.as_ref(),
+            footer: self.footer.as_ref(),
+        }
+    }
+
+    fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            page_box: self.page_box,
+            rotation: match self.rotation {
+                Some(degrees) => Some(render::rotation_from_degrees(degrees)),
+                None if self.ignore_page_rotation => Some(PdfPageRenderRotation::None),
+                None => None,
+            },
+            flatten_forms: self.flatten_forms,
+            hide_annotations: self.hide_annotations,
+            annotations_only: self.annotations_only,
+            hide_text: self.hide_text,
+            hide_images: self.hide_images,
+            lcd_text: self.lcd_text,
+            invert: self.dark_mode,
+            adjustments: self.adjustments,
+            sharpen: self.sharpen,
+            crop: self.crop,
+            border: self.border,
+            max_pixels: self
+                .max_megapixels
+                .map(|megapixels| (megapixels * 1e6) as u64),
+        }
+    }
+
+    // What a unit of the rendered pages measures in points: the source's UserUnit, or 1
+    // for the sheets of a layout, which are laid out in plain points.
+    fn render_unit(&self, user_unit: f32) -> f32 {
+        match self.has_layout() {
+            true => 1.0,
+            false => user_unit,
+        }
+    }
+
+    // The scale of each page of a document with `page_count` pages, in pixels per
+    // point times `render_unit` so DPIs hold for the physical page size.
+    fn page_scales(&self, page_count: u16, render_unit: f32) -> Vec<f32> {
+        let mut scales = vec![self.scale; usize::from(page_count)];
+        for page_scale in self.page_scales.iter().rev() {
+            for page_index in parse_page_range(&page_scale.pages, page_count) {
+                scales[page_index] = page_scale.scale;
+            }
+        }
+        scales.iter().map(|scale| scale * render_unit).collect()
+    }
+
+    fn has_layout(&self) -> bool {
+        self.n_up.is_some() || self.booklet.is_some() || self.contact_sheet.is_some()
+    }
+
+    fn validate(&self) -> Result<(), ConvertError> {
+        validate_scale(self.scale)?;
+        for page_scale in &self.page_scales {
+            validate_scale(page_scale.scale)?;
+        }
+        match self.max_megapixels {
+            Some(megapixels) if megapixels > 0.0 => Ok(()),
+            Some(megapixels) => Err(ConvertError::InvalidOption {
+                option: "max_megapixels".into(),
+                reason: Reason::NotPositive {
+                    value: megapixels.to_string(),
+                },
+            }),
+            None => Ok(()),
+        }?;
+        if let Some(crop) = &self.crop {
+            let sides = [crop.left, crop.top, crop.right, crop.bottom];
+            if sides.iter().any(|side| !(0.0..1.0).contains(side))
+                || crop.left + crop.right >= 1.0
+                || crop.top + crop.bottom >= 1.0
+            {
+                return Err(ConvertError::InvalidOption {
+                    option: "crop".into(),
+                    reason: Reason::NothingLeft,
+                });
+            }
+        }
+        if self.backend == Backend::Mupdf {
+            let stamps = self.stamps();
+            let unsupported = [
+                ("page_box", self.page_box != PageBox::Crop),
+                (
+                    "rotation",
+                    self.rotation.is_some() || self.ignore_page_rotation,
+                ),
+                ("flatten_forms", self.flatten_forms),
+                ("hide_annotations", self.hide_annotations),
+                ("annotations_only", self.annotations_only),
+                ("hide_text", self.hide_text),
+                ("hide_images", self.hide_images),
+                ("lcd_text", self.lcd_text),
+                (
+                    "stamps",
+                    stamps.watermark.is_some()
+                        || stamps.page_numbers.is_some()
+                        || stamps.header.is_some()
+                        || stamps.footer.is_some(),
+                ),
+                ("layouts", self.has_layout()),
+            ];
+            if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+                return Err(ConvertError::InvalidOption {
+                    option: "backend".into(),
+                    reason: Reason::MupdfConflict {
+                        with: option.to_string(),
+                    },
+                });
+            }
+        }
+        // poppler only renders whole pages as they are; anything that reads or changes
+        // the document, or works on the pixels past encoding them, needs pdfium
+        if self.backend == Backend::Poppler {
+            let stamps = self.stamps();
+            let unsupported = [
+                ("merge", self.merge),
+                ("layouts", self.has_layout()),
+                (
+                    "stamps",
+                    stamps.watermark.is_some()
+                        || stamps.page_numbers.is_some()
+                        || stamps.header.is_some()
+                        || stamps.footer.is_some(),
+                ),
+                ("page_box", self.page_box != PageBox::Crop),
+                (
+                    "rotation",
+                    self.rotation.is_some() || self.ignore_page_rotation,
+                ),
+                ("flatten_forms", self.flatten_forms),
+                ("hide_annotations", self.hide_annotations),
+                ("annotations_only", self.annotations_only),
+                ("hide_text", self.hide_text),
+                ("hide_images", self.hide_images),
+                ("lcd_text", self.lcd_text),
+                ("layers", !self.layers.is_empty()),
+                ("dark_mode", self.dark_mode),
+                ("adjustments", self.adjustments.is_some()),
+                ("sharpen", self.sharpen.is_some()),
+                ("crop", self.crop.is_some()),
+                ("border", self.border.is_some()),
+                ("max_megapixels", self.max_megapixels.is_some()),
+                ("cmyk", self.cmyk),
+                ("page_size", self.page_size.is_some()),
+                ("orientation", self.orientation.is_some()),
+                (
+                    "duplicate_pages",
+                    self.duplicate_pages != DuplicatePages::Keep,
+                ),
+                ("smart_format", self.smart_format),
+                ("embed_provenance", self.embed_provenance),
+                ("repair", self.repair),
+            ];
+            if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+                return Err(ConvertError::InvalidOption {
+                    option: "backend".into(),
+                    reason: Reason::PopplerConflict {
+                        with: option.to_string(),
+                    },
+                });
+            }
+        }
+        // SVG pages are written from pdfium's page objects, never as pixels
+        if self.encode_settings().ext == "svg" {
+            let unsupported = [
+                ("merge", self.merge),
+                ("backend", self.backend != Backend::Pdfium),
+                ("annotations_only", self.annotations_only),
+                ("dark_mode", self.dark_mode),
+                ("adjustments", self.adjustments.is_some()),
+                ("sharpen", self.sharpen.is_some()),
+                ("crop", self.crop.is_some()),
+                ("border", self.border.is_some()),
+                (
+                    "duplicate_pages",
+                    self.duplicate_pages != DuplicatePages::Keep,
+                ),
+            ];
+            if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+                return Err(ConvertError::InvalidOption {
+                    option: "format".into(),
+                    reason: Reason::SvgConflict {
+                        with: option.to_string(),
+                    },
+                });
+            }
+        }
+        match self.page_size.as_ref().and_then(SizeFilter::invalid_size) {
+            Some(size) => Err(ConvertError::InvalidOption {
+                option: "page_size".into(),
+                reason: Reason::UnknownPaperSize {
+                    size: size.to_string(),
+                },
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+// 3600 DPI; past that renders outgrow any printer or screen and only exhaust memory.
+const MAX_SCALE: f32 = 50.0;
+
+fn validate_scale(scale: f32) -> Result<(), ConvertError> {
+    if scale > 0.0 && scale <= MAX_SCALE {
+        return Ok(());
+    }
+    Err(ConvertError::InvalidOption {
+        option: "scale".into(),
+        reason: Reason::OutOfRange {
+            value: scale.to_string(),
+            max: MAX_SCALE.to_string(),
+        },
+    })
+}
+
+// A scale for some of the pages, e.g. the cover at 600 DPI and the body at 150.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct PageScale {
+    pages: String,
+    scale: f32,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ConflictPolicy {
+    #[default]
+    Overwrite,
+    Skip,
+    Rename,
+    Fail,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ErrorPolicy {
+    #[default]
+    Continue,
+    // Stop the batch at the first failing page or file
+    Abort,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SourceAction {
+    #[default]
+    Keep,
+    Move,
+    Trash,
 }
 
 #[tauri::command]
@@ -43,261 +783,2666 @@ async fn open_folder(path: String) -> Result<(), String> {
     Ok(())
 }
 
-fn parse_page_range(range_str: &str, total_pages: u16) -> Vec<usize> {
-    if range_str.trim().is_empty() {
-        return (0..total_pages as usize).collect();
-    }
-
-    let mut pages = Vec::new();
-    for part in range_str.split(',') {
-        let part = part.trim();
-        if part.contains('-') {
-            let bounds: Vec<&str> = part.split('-').collect();
-            if bounds.len() == 2 {
-                if let (Ok(start), Ok(end)) = (
-                    bounds[0].trim().parse::<usize>(),
-                    bounds[1].trim().parse::<usize>(),
-                ) {
-                    let s = start.saturating_sub(1);
-                    let e = (end as usize).min(total_pages as usize);
-                    for i in s..e {
-                        pages.push(i);
-                    }
+// Opens the file manager with the given output selected.
+#[tauri::command]
+fn reveal_file(app: AppHandle, path: String) -> Result<(), String> {
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| e.to_string())
+}
+
+fn padded_page_number(page_number: usize, total_pages: u16, digits: Option<usize>) -> String {
+    let width = digits.unwrap_or_else(|| total_pages.max(1).to_string().len());
+    format!("{:0width$}", page_number, width = width)
+}
+
+fn apply_name_template(template: &str, stem: &str, page: &str, ext: &str) -> String {
+    let name = template.replace("{name}", stem).replace("{page}", page);
+    format!("{}.{}", name, ext)
+}
+
+fn page_file_name(
+    stem: &str,
+    page_index: usize,
+    total_work: usize,
+    total_pages: u16,
+    options: &ConvertOptions,
+    ext: &str,
+) -> String {
+    let page = padded_page_number(page_index + 1, total_pages, options.page_digits);
+    if let Some(template) = &options.name_template {
+        return apply_name_template(template, stem, &page, ext);
+    }
+    let suffix = if total_work > 1 {
+        format!("_page_{}", page)
+    } else {
+        "".to_string()
+    };
+    format!("{}{}.{}", stem, suffix, ext)
+}
+
+fn merged_file_name(stem: &str, options: &ConvertOptions, ext: &str) -> String {
+    match &options.name_template {
+        Some(template) => apply_name_template(template, stem, "merged", ext),
+        None => format!("{}_merged.{}", stem, ext),
+    }
+}
+
+// Picks `<output_dir>/<stem>`, falling back to `<stem>_2`, `<stem>_3`, ... when an
+// earlier document in the batch already claimed the name or a file is in the way.
+fn document_output_dir(output_dir: &Path, stem: &str, used: &mut HashSet<PathBuf>) -> PathBuf {
+    let mut candidate = output_dir.join(stem);
+    let mut n = 2;
+    while used.contains(&candidate) || (candidate.exists() && !candidate.is_dir()) {
+        candidate = output_dir.join(format!("{}_{}", stem, n));
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+// Applies the conflict policy to a planned output path. Returns `None` when the
+// output should be skipped, plus the action taken if the path already existed.
+fn resolve_output_path(
+    path: PathBuf,
+    policy: ConflictPolicy,
+) -> Result<(Option<PathBuf>, Option<&'static str>), ConvertError> {
+    if !path.exists() {
+        return Ok((Some(path), None));
+    }
+    match policy {
+        ConflictPolicy::Overwrite => Ok((Some(path), Some("overwritten"))),
+        ConflictPolicy::Skip => Ok((None, Some("skipped"))),
+        ConflictPolicy::Fail => Err(ConvertError::OutputExists {
+            path: path.to_string_lossy().to_string(),
+        }),
+        ConflictPolicy::Rename => {
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output")
+                .to_string();
+            let ext = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+            let mut n = 2;
+            loop {
+                let candidate = path.with_file_name(format!("{}_{}.{}", stem, n, ext));
+                if !candidate.exists() {
+                    return Ok((Some(candidate), Some("renamed")));
                 }
+                n += 1;
             }
-        } else if let Ok(p) = part.parse::<usize>() {
-            if p > 0 && p <= total_pages as usize {
-                pages.push(p - 1);
+        }
+    }
+}
+
+// Gets a converted PDF out of the way so inbox-style folders don't convert it again.
+// Returns where it was moved to, if anywhere.
+fn dispose_source(input: &Path, options: &ConvertOptions) -> Result<Option<PathBuf>, ConvertError> {
+    match options.source_action {
+        SourceAction::Keep => Ok(None),
+        SourceAction::Trash => trash::delete(input)
+            .map(|()| None)
+            .map_err(|e| ConvertError::io(input, std::io::Error::other(e.to_string()))),
+        SourceAction::Move => {
+            let dir = match &options.processed_dir {
+                Some(dir) => PathBuf::from(dir),
+                None => input.parent().unwrap_or(Path::new(".")).join("processed"),
+            };
+            std::fs::create_dir_all(&dir).map_err(|e| ConvertError::io(&dir, e))?;
+            let target = dir.join(input.file_name().unwrap_or_default());
+            let (Some(target), _) = resolve_output_path(target, ConflictPolicy::Rename)? else {
+                return Ok(None);
+            };
+            // Renaming fails across drives, where the file is copied instead
+            if std::fs::rename(input, &target).is_err() {
+                std::fs::copy(input, &target).map_err(|e| ConvertError::io(&target, e))?;
+                std::fs::remove_file(input).map_err(|e| ConvertError::io(input, e))?;
             }
+            Ok(Some(target))
         }
     }
+}
 
-    // Remote duplicates and sort
-    pages.sort_unstable();
-    pages.dedup();
-    pages
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
-#[tauri::command]
-fn convert_pdf(
-    window: Window,
-    input_paths: Vec<String>,
-    output_dir: String,
-    format: String,
+fn is_up_to_date(output: &Path, source_modified: Option<SystemTime>) -> bool {
+    match (modified_time(output), source_modified) {
+        (Some(output), Some(source)) => output > source,
+        _ => false,
+    }
+}
+
+fn shared_pdfium(app: &AppHandle) -> Result<Arc<Pdfium>, ConvertError> {
+    app.state::<PdfiumLibrary>().get()
+}
+
+// Pixel size `PageBuffer::render` produces, crop and border included; (0, 0) when the
+// page can't be read, which rendering then reports.
+fn render_size(
+    document: &PdfDocument,
+    page_index: usize,
     scale: f32,
-    page_range: String,
-    merge: bool,
-    quality: u8,
-) -> Result<String, String> {
-    let resource_dir = window
-        .app_handle()
-        .path()
-        .resource_dir()
-        .unwrap_or_else(|_| std::env::current_dir().unwrap());
-    let binaries_dir = resource_dir.join("binaries");
-    let binaries_dir_str = binaries_dir.to_string_lossy();
-
-    let pdfium = Pdfium::new(
-        Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&binaries_dir))
-            .or_else(|_| Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./")))
-            .or_else(|_| Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./src-tauri/")))
-            .or_else(|_| Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./target/release/")))
-            .or_else(|_| Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./target/debug/")))
-            .or_else(|_| Pdfium::bind_to_system_library())
-            .map_err(|e| format!("Failed to load PDFium library: {}. \n\nTips: \n1. Install libpdfium (e.g., 'sudo apt install libpdfium-dev' on Linux). \n2. Or download the shared library from GitHub and place it next to the app executable.", e))?
-    );
+    settings: &RenderSettings,
+) -> (u64, u64) {
+    let (width, height) = document
+        .pages()
+        .get(page_index as u16)
+        .map(|page| {
+            let (width, height, _) = render::pixel_size(&page, scale, settings);
+            (u64::from(width), u64::from(height))
+        })
+        .unwrap_or((0, 0));
+    settings.output_size(width, height)
+}
 
-    std::env::set_var("FONTCONFIG_PATH", "/etc/fonts");
+// The stages a conversion runs on each page: its stamps, then what its options turn on.
+fn register_stages<'s>(
+    page_buffer: &mut PageBuffer<'s>,
+    settings: &RenderSettings,
+    stamper: &'s Stamper,
+) {
+    if !settings.annotations_only {
+        page_buffer.add_post_processor(Box::new(stamper));
+    }
+    for stage in settings.post_processors() {
+        page_buffer.add_post_processor(stage);
+    }
+}
 
-    for path_str in input_paths {
-        let path = Path::new(&path_str);
-        let filename = path
+fn source_bytes(file: &JobFile) -> Result<Vec<u8>, ConvertError> {
+    match &file.data {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => std::fs::read(&file.input_path)
+            .map_err(|e| ConvertError::io(Path::new(&file.input_path), e)),
+    }
+}
+
+// The file MuPDF draws a source from: a copy when it only exists in memory or has
+// layers switched.
+fn mupdf_source(file: &JobFile, options: &ConvertOptions) -> Result<mupdf::Source, ConvertError> {
+    let bytes = match (&file.data, options.layers.is_empty()) {
+        (None, true) => None,
+        _ => {
+            let bytes = source_bytes(file)?;
+            Some(layers::with_visibility(&bytes, &options.layers).unwrap_or(bytes))
+        }
+    };
+    mupdf::Source::new(Path::new(&file.input_path), bytes)
+}
+
+// Opens a source with the layers `options` picks shown or hidden.
+fn load_document<'a>(
+    pdfium: &'a Pdfium,
+    file: &'a JobFile,
+    options: &ConvertOptions,
+) -> Result<PdfDocument<'a>, ConvertError> {
+    if !options.layers.is_empty() {
+        let bytes = source_bytes(file)?;
+        if let Some(bytes) = layers::with_visibility(&bytes, &options.layers) {
+            return pdfium
+                .load_pdf_from_byte_vec(bytes, None)
+                .map_err(|e| ConvertError::pdf_open(&file.input_path, e));
+        }
+    }
+    match &file.data {
+        Some(bytes) => pdfium.load_pdf_from_byte_slice(bytes, None),
+        None => pdfium.load_pdf_from_file(&file.input_path, None),
+    }
+    .map_err(|e| ConvertError::pdf_open(&file.input_path, e))
+}
+
+// Swaps in sheets holding several of the selected pages each when a contact sheet,
+// booklet or N-up layout is set; the selection then covers every sheet.
+fn lay_out<'a>(
+    pdfium: &'a Pdfium,
+    document: PdfDocument<'a>,
+    target_pages: Vec<usize>,
+    options: &ConvertOptions,
+    name: &str,
+) -> Result<(PdfDocument<'a>, Vec<usize>), ConvertError> {
+    let sheets = if let Some(sheet) = &options.contact_sheet {
+        layout::contact_sheet(pdfium, &document, &target_pages, sheet, name)
+    } else if let Some(booklet) = &options.booklet {
+        layout::booklet(pdfium, &document, &target_pages, booklet)
+    } else if let Some(n_up) = &options.n_up {
+        layout::n_up(pdfium, &document, &target_pages, n_up)
+    } else {
+        return Ok((document, target_pages));
+    }
+    .map_err(|e| ConvertError::LayoutFailed {
+        message: e.to_string(),
+    })?;
+    let count = sheets.pages().len() as usize;
+    Ok((sheets, (0..count).collect()))
+}
+
+// Narrows the selected pages down to the ones the filters in `options` let through.
+fn filter_pages(
+    document: &PdfDocument,
+    pages: Vec<usize>,
+    options: &ConvertOptions,
+    user_unit: f32,
+) -> Vec<usize> {
+    if options.orientation.is_none() && options.page_size.is_none() {
+        return pages;
+    }
+    pages
+        .into_iter()
+        .filter(|&page_index| {
+            document.pages().get(page_index as u16).is_ok_and(|page| {
+                options
+                    .orientation
+                    .is_none_or(|orientation| Orientation::of(&page) == orientation)
+                    && options
+                        .page_size
+                        .as_ref()
+                        .is_none_or(|filter| filter.matches(paper::size_mm(&page, user_unit)))
+            })
+        })
+        .collect()
+}
+
+// The UserUnit of a source's pages.
+fn user_unit(path: &str, data: Option<&[u8]>) -> f32 {
+    match data {
+        Some(bytes) => paper::user_unit(bytes),
+        None => std::fs::read(path).map_or(1.0, |bytes| paper::user_unit(&bytes)),
+    }
+}
+
+// Like `load_document`, retrying with a repaired copy when `options.repair` is set.
+// The flag tells whether the repair was needed.
+fn open_document<'a>(
+    pdfium: &'a Pdfium,
+    file: &'a JobFile,
+    options: &ConvertOptions,
+) -> Result<(PdfDocument<'a>, bool), ConvertError> {
+    let error = match load_document(pdfium, file, options) {
+        Ok(document) => return Ok((document, false)),
+        Err(e @ ConvertError::PasswordRequired { .. }) => return Err(e),
+        Err(e) if !options.repair => return Err(e),
+        Err(e) => e,
+    };
+    let bytes = source_bytes(file)?;
+    repair::recover(&bytes)
+        .map(|repaired| layers::with_visibility(&repaired, &options.layers).unwrap_or(repaired))
+        .and_then(|repaired| pdfium.load_pdf_from_byte_vec(repaired, None).ok())
+        .map(|document| (document, true))
+        .ok_or(error)
+}
+
+// A document opened the way it gets converted: repaired and with its layers switched
+// if asked, and its selection filtered and laid out.
+struct PreparedDocument<'a> {
+    document: PdfDocument<'a>,
+    target_pages: Vec<usize>,
+    user_unit: f32,
+    repaired: bool,
+}
+
+fn prepare_document<'a>(
+    pdfium: &'a Pdfium,
+    file: &'a JobFile,
+    options: &ConvertOptions,
+    name: &str,
+) -> Result<PreparedDocument<'a>, ConvertError> {
+    let (document, repaired) = open_document(pdfium, file, options)?;
+    let page_range = file.page_range.as_ref().unwrap_or(&options.page_range);
+    let target_pages = parse_page_range(page_range, document.pages().len());
+    render::prepare_pages(&document, &target_pages, &options.render_settings());
+    let user_unit = user_unit(&file.input_path, file.data.as_deref().map(Vec::as_slice));
+    let target_pages = filter_pages(&document, target_pages, options, user_unit);
+    if target_pages.is_empty() {
+        return Err(ConvertError::InvalidPageRange {
+            range: page_range.clone(),
+        });
+    }
+    let (document, target_pages) = lay_out(pdfium, document, target_pages, options, name)?;
+    Ok(PreparedDocument {
+        document,
+        target_pages,
+        user_unit,
+        repaired,
+    })
+}
+
+// Resolves everything `convert_pdf` would do without rendering or writing files.
+fn plan_conversion(
+    pdfium: &Pdfium,
+    job_files: &[JobFile],
+    options: &ConvertOptions,
+) -> ConversionPlan {
+    let ext = options.encode_settings().ext;
+    let render_settings = options.render_settings();
+    let mut files = Vec::new();
+
+    for file in job_files {
+        let path_str = &file.input_path;
+        let filename = Path::new(path_str)
             .file_stem()
             .and_then(|s| s.to_str())
-            .unwrap_or("unknown");
-
-        let _ = window.emit(
-            "file_status",
-            FileStatusPayload {
-                filename: filename.to_string(),
-                status: "processing".into(),
-                error: None,
-                output_path: None,
-            },
-        );
+            .unwrap_or("unknown")
+            .to_string();
+        let mut planned = PlannedFile {
+            input_path: path_str.clone(),
+            filename: filename.clone(),
+            outputs: Vec::new(),
+            error: None,
+        };
 
-        let document_res = pdfium.load_pdf_from_file(&path_str, None);
+        let doc_output_dir = &file.output_dir;
 
-        match document_res {
-            Ok(document) => {
-                let total_pages_in_doc = document.pages().len();
-                let target_pages = parse_page_range(&page_range, total_pages_in_doc);
-                let total_work = target_pages.len();
+        let document = match open_document(pdfium, file, options) {
+            Ok((document, _)) => document,
+            Err(e) => {
+                planned.error = Some(e.into());
+                files.push(planned);
+                continue;
+            }
+        };
 
-                if total_work == 0 {
-                    let _ = window.emit(
-                        "file_status",
-                        FileStatusPayload {
-                            filename: filename.to_string(),
-                            status: "error".into(),
-                            error: Some("No valid pages selected in range".into()),
-                            output_path: None,
-                        },
-                    );
+        let page_range = file.page_range.as_ref().unwrap_or(&options.page_range);
+        let target_pages = parse_page_range(page_range, document.pages().len());
+        render::prepare_pages(&document, &target_pages, &options.render_settings());
+        let user_unit = user_unit(path_str, file.data.as_deref().map(Vec::as_slice));
+        let target_pages = filter_pages(&document, target_pages, options, user_unit);
+        if target_pages.is_empty() {
+            planned.error = Some(
+                ConvertError::InvalidPageRange {
+                    range: page_range.clone(),
+                }
+                .into(),
+            );
+            files.push(planned);
+            continue;
+        }
+        let (document, target_pages) =
+            match lay_out(pdfium, document, target_pages, options, &filename) {
+                Ok(laid_out) => laid_out,
+                Err(e) => {
+                    planned.error = Some(e.into());
+                    files.push(planned);
                     continue;
                 }
+            };
+        let total_pages_in_doc = document.pages().len();
+        let scales = options.page_scales(total_pages_in_doc, options.render_unit(user_unit));
 
-                let mut rendered_images = Vec::new();
-                let mut last_output = String::new();
+        let mut sizes = Vec::new();
+        for &page_index in &target_pages {
+            if let Ok(page) = document.pages().get(page_index as u16) {
+                let (width, height, _) =
+                    render::pixel_size(&page, scales[page_index], &render_settings);
+                sizes.push((page_index, width, height));
+            }
+        }
 
-                for (idx, &page_index) in target_pages.iter().enumerate() {
-                    let _ = window.emit(
-                        "progress",
-                        ProgressPayload {
-                            filename: filename.to_string(),
-                            current: idx + 1,
-                            total: total_work,
-                        },
-                    );
+        let source_modified = file
+            .data
+            .is_none()
+            .then(|| modified_time(Path::new(path_str)))
+            .flatten();
+        let mut candidates = Vec::new();
+        if options.merge {
+            let width = sizes.iter().map(|&(_, w, _)| w).max().unwrap_or(0);
+            let height = sizes.iter().map(|&(_, _, h)| h).sum();
+            let pages = sizes.iter().map(|&(i, _, _)| i + 1).collect();
+            let path = doc_output_dir.join(merged_file_name(&filename, options, ext));
+            candidates.push((pages, path, width, height));
+        } else {
+            for &(page_index, width, height) in &sizes {
+                let name = page_file_name(
+                    &filename,
+                    page_index,
+                    target_pages.len(),
+                    total_pages_in_doc,
+                    options,
+                    ext,
+                );
+                candidates.push((
+                    vec![page_index + 1],
+                    doc_output_dir.join(name),
+                    width,
+                    height,
+                ));
+            }
+        }
 
-                    if let Ok(page) = document.pages().get(page_index as u16) {
-                        let render_width = (page.width().value * scale) as i32;
-                        let render_height = (page.height().value * scale) as i32;
-
-                        if let Ok(bitmap) = page.render(render_width, render_height, None) {
-                            let image = bitmap.as_image();
-
-                            if merge {
-                                rendered_images.push(image);
-                            } else {
-                                let ext = if format.to_lowercase() == "png" {
-                                    "png"
-                                } else {
-                                    "jpg"
-                                };
-                                let suffix = if total_work > 1 {
-                                    format!("_page_{}", page_index + 1)
-                                } else {
-                                    "".to_string()
-                                };
-                                let out_path = Path::new(&output_dir)
-                                    .join(format!("{}{}.{}", filename, suffix, ext));
-
-                                let save_res = if ext == "jpg" {
-                                    let mut file = std::fs::File::create(&out_path)
-                                        .map_err(|e| e.to_string())?;
-                                    let mut encoder =
-                                        image::codecs::jpeg::JpegEncoder::new_with_quality(
-                                            &mut file, quality,
-                                        );
-                                    encoder.encode_image(&image).map_err(|e| e.to_string())
-                                } else {
-                                    image.save(&out_path).map_err(|e| e.to_string())
-                                };
-
-                                if let Err(e) = save_res {
-                                    let _ = window.emit(
-                                        "file_status",
-                                        FileStatusPayload {
-                                            filename: filename.to_string(),
-                                            status: "error".into(),
-                                            error: Some(format!("Save error: {}", e)),
-                                            output_path: None,
-                                        },
-                                    );
-                                } else {
-                                    last_output = out_path.to_string_lossy().to_string();
-                                }
-                            }
-                        }
-                    }
+        for (pages, path, width, height) in candidates {
+            if options.skip_up_to_date && is_up_to_date(&path, source_modified) {
+                planned.outputs.push(PlannedOutput {
+                    pages,
+                    output_path: None,
+                    width,
+                    height,
+                    conflict_action: Some("up_to_date".into()),
+                });
+                continue;
+            }
+            match resolve_output_path(path, options.conflict_policy) {
+                Ok((path, action)) => planned.outputs.push(PlannedOutput {
+                    pages,
+                    output_path: path.map(|p| p.to_string_lossy().to_string()),
+                    width,
+                    height,
+                    conflict_action: action.map(Into::into),
+                }),
+                Err(e) => {
+                    planned.error = Some(e.into());
+                    planned.outputs.clear();
+                    break;
                 }
+            }
+        }
 
-                if merge && !rendered_images.is_empty() {
-                    let total_width = rendered_images
-                        .iter()
-                        .map(|img| img.width())
-                        .max()
-                        .unwrap_or(0);
-                    let total_height: u32 = rendered_images.iter().map(|img| img.height()).sum();
-
-                    if total_width > 0 && total_height > 0 {
-                        let mut combined =
-                            image::DynamicImage::new_rgba8(total_width, total_height);
-                        let mut current_y = 0;
-                        for img in rendered_images {
-                            image::imageops::replace(&mut combined, &img, 0, i64::from(current_y));
-                            current_y += img.height();
-                        }
+        files.push(planned);
+    }
 
-                        let ext = if format.to_lowercase() == "png" {
-                            "png"
-                        } else {
-                            "jpg"
-                        };
-                        let out_path =
-                            Path::new(&output_dir).join(format!("{}_merged.{}", filename, ext));
-
-                        let save_res = if ext == "jpg" {
-                            let mut file =
-                                std::fs::File::create(&out_path).map_err(|e| e.to_string())?;
-                            let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
-                                &mut file, quality,
-                            );
-                            encoder.encode_image(&combined).map_err(|e| e.to_string())
-                        } else {
-                            combined.save(&out_path).map_err(|e| e.to_string())
-                        };
-
-                        if let Err(e) = save_res {
-                            let _ = window.emit(
-                                "file_status",
-                                FileStatusPayload {
-                                    filename: filename.to_string(),
-                                    status: "error".into(),
-                                    error: Some(format!("Merge save error: {}", e)),
-                                    output_path: None,
-                                },
-                            );
-                        } else {
-                            last_output = out_path.to_string_lossy().to_string();
-                        }
-                    }
-                }
+    let total_pages = files
+        .iter()
+        .flat_map(|f| &f.outputs)
+        .map(|o| o.pages.len())
+        .sum();
+    let total_outputs = files
+        .iter()
+        .flat_map(|f| &f.outputs)
+        .filter(|o| o.output_path.is_some())
+        .count();
+    ConversionPlan {
+        files,
+        total_pages,
+        total_outputs,
+    }
+}
 
-                let _ = window.emit(
-                    "file_status",
-                    FileStatusPayload {
-                        filename: filename.to_string(),
-                        status: "success".into(),
-                        error: None,
-                        output_path: Some(last_output),
-                    },
-                );
+#[allow(clippy::too_many_arguments)]
+fn convert_file(
+    events: &dyn EventSink,
+    pdfium: &Pdfium,
+    jobs: &JobRegistry,
+    batch: &BatchControl,
+    cache: &RenderCache,
+    file: &JobFile,
+    options: &ConvertOptions,
+    encode_settings: &EncodeSettings,
+    page_hashes: &RefCell<PageHashes>,
+    on_page_saved: &dyn Fn(usize),
+) -> FileReport {
+    let started = Instant::now();
+    let path_str = file.input_path.as_str();
+    let doc_output_dir = file.output_dir.as_path();
+    let filename = Path::new(path_str)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown");
+    let mut report = FileReport {
+        input_path: path_str.to_string(),
+        filename: filename.to_string(),
+        status: "error".into(),
+        ..Default::default()
+    };
+
+    let PreparedDocument {
+        mut document,
+        target_pages,
+        user_unit,
+        repaired,
+    } = match prepare_document(pdfium, file, options, filename) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            report.duration_ms = started.elapsed().as_millis() as u64;
+            fail_file(events, &mut report, e);
+            return report;
+        }
+    };
+    report.repaired = repaired;
+    let render_settings = options.render_settings();
+    let total_pages_in_doc = document.pages().len();
+    let render_unit = options.render_unit(user_unit);
+    let scales = options.page_scales(total_pages_in_doc, render_unit);
+    let total_work = target_pages.len();
+    // Stamps go onto whole sheets when pages are laid out several to a sheet
+    let stamper = match Stamper::new(&mut document, options.stamps(), filename) {
+        Ok(stamper) => stamper,
+        Err(e) => {
+            report.duration_ms = started.elapsed().as_millis() as u64;
+            fail_file(events, &mut report, e);
+            return report;
+        }
+    };
+    let mut merged_pages = 0;
+    let mut last_output = String::new();
+    let mut conflict_action = None;
+    let mut fatal_error = None;
+
+    // Naming keeps following the full selection so retried pages land on the same files
+    let work_pages: Vec<usize> = match &file.pages {
+        Some(pages) => target_pages
+            .iter()
+            .copied()
+            .filter(|p| pages.contains(p))
+            .collect(),
+        None => target_pages.clone(),
+    };
+    // Merged outputs depend on every page, so only single pages are cached. Duplicate
+    // detection needs every page's pixels, so it goes without the cache.
+    let file_hash = match &file.data {
+        _ if !options.render_cache || options.merge => None,
+        _ if options.duplicate_pages != DuplicatePages::Keep => None,
+        // The conversion time in the metadata would go stale
+        _ if options.embed_provenance => None,
+        // A page's format isn't known until it's rendered
+        _ if options.smart_format => None,
+        Some(bytes) => Some(RenderCache::bytes_hash(bytes)),
+        None => RenderCache::file_hash(Path::new(path_str)),
+    }
+    // Layers are switched in the document itself, so they count as part of the source
+    .map(|hash| match options.layers.is_empty() {
+        true => hash,
+        false => format!("{}:{:?}", hash, options.layers),
+    });
+    let source_modified = file
+        .data
+        .is_none()
+        .then(|| modified_time(Path::new(path_str)))
+        .flatten();
+    let merged_path = doc_output_dir.join(merged_file_name(filename, options, encode_settings.ext));
+    let work_pages: Vec<usize> = if options.merge {
+        if options.skip_up_to_date && is_up_to_date(&merged_path, source_modified) {
+            report.pages_up_to_date = work_pages.len();
+            last_output = merged_path.to_string_lossy().to_string();
+            Vec::new()
+        } else {
+            work_pages
+        }
+    } else {
+        work_pages
+            .into_iter()
+            .filter(|p| !file.done_pages.contains(p))
+            .collect()
+    };
+
+    // Merged pages are stacked into the output as they render, so only one page is
+    // held at a time plus, for JPEG/WebP, the combined canvas. That is reserved up
+    // front rather than failing after most pages were rendered.
+    let mut merged = None;
+    let _merge_memory = if options.merge && !work_pages.is_empty() {
+        let sizes: Vec<(u64, u64)> = work_pages
+            .iter()
+            .map(|&page_index| {
+                render_size(&document, page_index, scales[page_index], &render_settings)
+            })
+            .collect();
+        let width = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+        let height: u64 = sizes.iter().map(|(_, h)| *h).sum();
+        let page_bytes = sizes.iter().map(|(w, h)| w * h * 4).max().unwrap_or(0);
+        let canvas_bytes = match encode_settings.ext {
+            "png" => 0,
+            _ => width * height * 4,
+        };
+        let prepared = jobs
+            .memory()
+            .reserve(page_bytes + canvas_bytes, options.memory_limit_mb, || {
+                batch.file_cancelled(path_str)
+            })
+            .and_then(|reservation| {
+                let (out_path, action) =
+                    resolve_output_path(merged_path.clone(), options.conflict_policy)?;
+                conflict_action = action;
+                if let Some(out_path) = out_path.filter(|_| width > 0 && height > 0) {
+                    merged = Some(MergedOutput::create(
+                        out_path,
+                        width as u32,
+                        height as u32,
+                        encode_settings,
+                    )?);
+                }
+                Ok(reservation)
+            });
+        match prepared {
+            Ok(reservation) => Some(reservation),
+            Err(ConvertError::Cancelled) => return cancelled_file(events, report, started),
+            Err(e) => {
+                report.duration_ms = started.elapsed().as_millis() as u64;
+                fail_file(events, &mut report, e);
+                return report;
             }
+        }
+    } else {
+        None
+    };
+    let work_pages = match &merged {
+        None if options.merge => Vec::new(),
+        _ => work_pages,
+    };
+    // Explains text coming out in the wrong typeface
+    report.missing_fonts = fonts::missing_fonts(&document, &work_pages);
+    if !report.missing_fonts.is_empty() {
+        events.emit(
+            "missing_fonts",
+            MissingFontsPayload {
+                filename: filename.to_string(),
+                fonts: report.missing_fonts.clone(),
+            },
+        );
+    }
+    let source_name = Path::new(path_str)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let provenance = (options.embed_provenance && !options.strip_metadata)
+        .then(|| metadata::Provenance::of(&document, &source_name));
+    let mut page_buffer = PageBuffer::for_sizes(work_pages.iter().map(|&page_index| {
+        render_size(&document, page_index, scales[page_index], &render_settings)
+    }));
+    if options.backend == Backend::Mupdf {
+        match mupdf_source(file, options) {
+            Ok(source) => page_buffer.use_renderer(Box::new(source)),
             Err(e) => {
-                let _ = window.emit(
-                    "file_status",
-                    FileStatusPayload {
-                        filename: filename.to_string(),
-                        status: "error".into(),
-                        error: Some(format!("Load PDF error: {}", e)),
-                        output_path: None,
-                    },
-                );
+                report.duration_ms = started.elapsed().as_millis() as u64;
+                fail_file(events, &mut report, e);
+                return report;
             }
         }
     }
+    register_stages(&mut page_buffer, &render_settings, &stamper);
 
-    Ok("Batch processing complete".to_string())
-}
+    let smart_format =
+        options.smart_format && !options.merge && matches!(encode_settings.ext, "jpg" | "webp");
+    let page_exts = match smart_format {
+        true => vec![encode_settings.ext, "png"],
+        false => vec![encode_settings.ext],
+    };
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
+    let mut progress = Throttle::new(Duration::from_millis(options.progress_interval_ms));
+    let mut rate = PageRate::default();
+    for (idx, &page_index) in work_pages.iter().enumerate() {
+        let scale = scales[page_index];
+        rate.finish_page();
+        if let Some(throttle_ms) = options.throttle_ms.filter(|_| idx > 0) {
+            std::thread::sleep(Duration::from_millis(throttle_ms));
+        }
+        batch.wait_while_paused(path_str);
+        if batch.file_cancelled(path_str) {
+            return cancelled_file(events, report, started);
+        }
+        rate.start_page();
+
+        let page_path = |ext: &str| {
+            doc_output_dir.join(page_file_name(
+                filename,
+                page_index,
+                total_work,
+                total_pages_in_doc,
+                options,
+                ext,
+            ))
+        };
+        // With smart format, an output in either format is current
+        let current = (!options.merge && options.skip_up_to_date)
+            .then(|| {
+                page_exts
+                    .iter()
+                    .map(|ext| page_path(ext))
+                    .find(|path| is_up_to_date(path, source_modified))
+            })
+            .flatten();
+        if let Some(out_path) = current {
+            report.pages_up_to_date += 1;
+            last_output = out_path.to_string_lossy().to_string();
+            continue;
+        }
+
+        // The first and last pages always go out so the UI starts and ends exact
+        if progress.ready(idx == 0 || idx + 1 == work_pages.len()) {
+            let (width, height) = render_size(&document, page_index, scale, &render_settings);
+            events.emit(
+                "progress",
+                ProgressPayload {
+                    filename: filename.to_string(),
+                    current: idx + 1,
+                    total: work_pages.len(),
+                    page: page_index + 1,
+                    width,
+                    height,
+                    output_path: (!last_output.is_empty()).then(|| last_output.clone()),
+                    pages_per_sec: rate.pages_per_sec(),
+                    eta_ms: rate.eta_ms(work_pages.len() - idx),
+                },
+            );
+        }
+
+        if let Ok(page) = document.pages().get(page_index as u16) {
+            let (width, height, clamped) = render::pixel_size(&page, scale, &render_settings);
+            if clamped {
+                events.emit(
+                    "page_clamped",
+                    PageClampedPayload {
+                        filename: filename.to_string(),
+                        page: page_index + 1,
+                        requested_width: (page.width().value * scale) as u64,
+                        requested_height: (page.height().value * scale) as u64,
+                        width,
+                        height,
+                    },
+                );
+            }
+        }
+
+        let mut metrics = PageMetrics {
+            page: page_index + 1,
+            ..Default::default()
+        };
+        if let Some(output) = merged.as_mut() {
+            let render_started = Instant::now();
+            match page_buffer.render(&document, page_index, scale, &render_settings) {
+                Ok(image) => {
+                    metrics.render_ms = render_started.elapsed().as_millis() as u64;
+                    let push_started = Instant::now();
+                    if let Err(e) = output.push(&image) {
+                        fatal_error = Some(e);
+                        break;
+                    }
+                    metrics.encode_ms = push_started.elapsed().as_millis() as u64;
+                    merged_pages += 1;
+                    record_page(events, &mut report, options, metrics);
+                }
+                Err(e) => {
+                    record_error(&mut report, e);
+                    report.failed_pages.push(page_index + 1);
+                    // Keeps the following pages at the offsets laid out up front
+                    let (_, height) = render_size(&document, page_index, scale, &render_settings);
+                    if let Err(e) = output.push_blank(height as u32) {
+                        fatal_error = Some(e);
+                        break;
+                    }
+                    if options.on_error == ErrorPolicy::Abort {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        // Smart format looks at the rendered page before naming its output
+        let mut page_memory = None;
+        let mut page_ext = encode_settings.ext;
+        if smart_format {
+            let (width, height) = render_size(&document, page_index, scale, &render_settings);
+            let render_started = Instant::now();
+            let rendered = jobs
+                .memory()
+                .reserve(width * height * 4, options.memory_limit_mb, || {
+                    batch.file_cancelled(path_str)
+                })
+                .and_then(|memory| {
+                    page_memory = Some(memory);
+                    page_buffer.render(&document, page_index, scale, &render_settings)
+                });
+            match rendered {
+                Ok(image) => {
+                    metrics.render_ms = render_started.elapsed().as_millis() as u64;
+                    if !autoformat::is_photographic(&image) {
+                        page_ext = "png";
+                    }
+                }
+                Err(ConvertError::Cancelled) => return cancelled_file(events, report, started),
+                Err(e) => {
+                    record_error(&mut report, e);
+                    report.failed_pages.push(page_index + 1);
+                    if options.on_error == ErrorPolicy::Abort {
+                        break;
+                    }
+                    continue;
+                }
+            }
+        }
+
+        let out_path = match resolve_output_path(page_path(page_ext), options.conflict_policy) {
+            Ok((out_path, action)) => {
+                conflict_action = action.or(conflict_action);
+                out_path
+            }
+            Err(e) => {
+                fatal_error = Some(e);
+                break;
+            }
+        };
+        let Some(out_path) = out_path else {
+            continue;
+        };
+
+        let exif = provenance.as_ref().map(|provenance| {
+            provenance.exif(
+                page_index + 1,
+                total_pages_in_doc as usize,
+                scale / render_unit,
+            )
+        });
+        let page_settings = EncodeSettings {
+            ext: page_ext,
+            exif: exif.as_deref(),
+            ..*encode_settings
+        };
+        let cache_key = file_hash.as_deref().map(|hash| {
+            RenderCache::key(
+                hash,
+                page_index,
+                scale,
+                &(
+                    &render_settings,
+                    options.stamps(),
+                    stamper.context(),
+                    options.backend,
+                ),
+                &page_settings,
+            )
+        });
+        let mut duplicate_of = None;
+        let saved = match cache_key.as_deref().and_then(|key| cache.get(key)) {
+            Some(bytes) => {
+                metrics.cached = true;
+                let write_started = Instant::now();
+                let saved = save_bytes(&bytes, &out_path);
+                metrics.encode_ms = write_started.elapsed().as_millis() as u64;
+                saved
+            }
+            None if page_ext == "svg" => {
+                let render_started = Instant::now();
+                svg::page(&document, page_index, scale, &render_settings, &stamper).and_then(
+                    |bytes| {
+                        metrics.render_ms = render_started.elapsed().as_millis() as u64;
+                        if let Some(key) = &cache_key {
+                            cache.put(key, &bytes);
+                        }
+                        save_bytes(&bytes, &out_path)
+                    },
+                )
+            }
+            None => {
+                // The page is rendered and encoded in place, without further copies
+                let (width, height) = render_size(&document, page_index, scale, &render_settings);
+                let memory = match page_memory.take() {
+                    Some(memory) => Ok(memory),
+                    None => {
+                        jobs.memory()
+                            .reserve(width * height * 4, options.memory_limit_mb, || {
+                                batch.file_cancelled(path_str)
+                            })
+                    }
+                };
+                memory.and_then(|_memory| {
+                    let image = match smart_format {
+                        true => {
+                            page_buffer
+                                .last_render()
+                                .ok_or_else(|| ConvertError::Internal {
+                                    message: "Page was not rendered".into(),
+                                })?
+                        }
+                        false => {
+                            let render_started = Instant::now();
+                            let image = page_buffer.render(
+                                &document,
+                                page_index,
+                                scale,
+                                &render_settings,
+                            )?;
+                            metrics.render_ms = render_started.elapsed().as_millis() as u64;
+                            image
+                        }
+                    };
+                    if options.duplicate_pages != DuplicatePages::Keep {
+                        let hash = dedupe::page_hash(&image);
+                        duplicate_of = page_hashes
+                            .borrow_mut()
+                            .check(hash, &out_path.to_string_lossy());
+                        if duplicate_of.is_some() && options.duplicate_pages == DuplicatePages::Skip
+                        {
+                            return Ok(());
+                        }
+                    }
+                    let encode_started = Instant::now();
+                    let saved = match &cache_key {
+                        Some(key) => {
+                            let bytes = encode_to_vec(&image, &page_settings)?;
+                            cache.put(key, &bytes);
+                            save_bytes(&bytes, &out_path)
+                        }
+                        None => save_image(&image, &out_path, &page_settings),
+                    };
+                    metrics.encode_ms = encode_started.elapsed().as_millis() as u64;
+                    saved
+                })
+            }
+        };
+
+        if let (Ok(()), Some(original)) = (&saved, duplicate_of) {
+            report.duplicate_pages.push(DuplicatePage {
+                page: page_index + 1,
+                duplicate_of: original,
+            });
+            if options.duplicate_pages == DuplicatePages::Skip {
+                continue;
+            }
+        }
+        match saved {
+            Ok(()) => {
+                last_output = out_path.to_string_lossy().to_string();
+                report.outputs.push(last_output.clone());
+                report.pages_converted += 1;
+                metrics.output_bytes = std::fs::metadata(&out_path).ok().map(|m| m.len());
+                record_page(events, &mut report, options, metrics);
+                on_page_saved(page_index);
+                run_output_hook(&mut report, &out_path);
+            }
+            Err(ConvertError::Cancelled) => return cancelled_file(events, report, started),
+            Err(e) => {
+                record_error(&mut report, e);
+                report.failed_pages.push(page_index + 1);
+                if options.on_error == ErrorPolicy::Abort {
+                    break;
+                }
+            }
+        }
+    }
+
+    // An aborted or cancelled merge is discarded along with its partial output
+    let merge_complete = merged_pages > 0
+        && merged_pages + report.failed_pages.len() == work_pages.len()
+        && fatal_error.is_none();
+    if let Some(output) = merged.filter(|_| merge_complete) {
+        let out_path = output.out_path().to_path_buf();
+        match output.finish() {
+            Ok(()) => {
+                last_output = out_path.to_string_lossy().to_string();
+                report.outputs.push(last_output.clone());
+                report.pages_converted += merged_pages;
+                run_output_hook(&mut report, &out_path);
+            }
+            Err(e) => fatal_error = Some(e),
+        }
+    }
+
+    report.duration_ms = started.elapsed().as_millis() as u64;
+
+    if let Some(e) = fatal_error {
+        fail_file(events, &mut report, e);
+        return report;
+    }
+
+    if options.on_error == ErrorPolicy::Abort && !report.errors.is_empty() {
+        emit_file_status(events, &report, None, None);
+        return report;
+    }
+
+    if options.subfolder_per_document {
+        last_output = doc_output_dir.to_string_lossy().to_string();
+    }
+
+    report.status = "success".into();
+    emit_file_status(events, &report, Some(last_output), conflict_action);
+    report
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Files and pages a batch will go through, for `batch_progress`. Counting opens each
+// document once up front; remote inputs aren't fetched for it and count no pages.
+#[derive(Clone, Copy)]
+struct BatchTotals {
+    files: usize,
+    pages: usize,
+}
+
+impl BatchTotals {
+    fn count(pdfium: Option<&Pdfium>, files: &[JobFile], options: &ConvertOptions) -> Self {
+        let pages = files
+            .iter()
+            .filter(|file| file.data.is_some() || !download::is_url(&file.input_path))
+            .filter_map(|file| {
+                let Some(pdfium) = pdfium.filter(|_| options.backend != Backend::Poppler) else {
+                    let page_count = poppler::page_count(&file.input_path).ok()?;
+                    let page_range = file.page_range.as_ref().unwrap_or(&options.page_range);
+                    let selected = parse_page_range(page_range, page_count);
+                    return Some(pending_pages(file, &selected, options));
+                };
+                let (document, _) = open_document(pdfium, file, options).ok()?;
+                let page_range = file.page_range.as_ref().unwrap_or(&options.page_range);
+                let selected = parse_page_range(page_range, document.pages().len());
+                render::prepare_pages(&document, &selected, &options.render_settings());
+                // Only the size filter needs the UserUnit, which means reading the file
+                let user_unit = match options.page_size {
+                    Some(_) => user_unit(&file.input_path, file.data.as_deref().map(Vec::as_slice)),
+                    None => 1.0,
+                };
+                let selected = filter_pages(&document, selected, options, user_unit);
+                Some(pending_pages(file, &selected, options))
+            })
+            .sum();
+        BatchTotals {
+            files: files.len(),
+            pages,
+        }
+    }
+}
+
+// How many of a file's selected pages are still to be converted.
+fn pending_pages(file: &JobFile, selected: &[usize], options: &ConvertOptions) -> usize {
+    let pending = selected.iter().filter(|page| {
+        file.pages.as_ref().is_none_or(|pages| pages.contains(page))
+            && (options.merge || !file.done_pages.contains(page))
+    });
+    pending.count()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_job(
+    events: &dyn EventSink,
+    pdfium: Option<&Pdfium>,
+    session: &SessionLog,
+    jobs: &JobRegistry,
+    cache: &RenderCache,
+    job_id: u64,
+    output_dir: &str,
+    files: impl IntoIterator<Item = JobFile>,
+    totals: BatchTotals,
+    options: &ConvertOptions,
+    on_page_saved: &dyn Fn(usize),
+) -> Result<BatchReport, ErrorPayload> {
+    let started = Instant::now();
+    let started_at = unix_now();
+    let batch = jobs.begin_batch(job_id, output_dir);
+    options.validate()?;
+    let cmyk = options.cmyk_conversion()?;
+    let encode_settings = EncodeSettings {
+        cmyk: cmyk.as_ref(),
+        ..options.encode_settings()
+    };
+    let mut processed = Vec::new();
+    let mut reports = Vec::new();
+    let mut aborted = false;
+    let _priority = options
+        .background_priority
+        .then(priority::BackgroundPriority::enter);
+
+    let (files_done, pages_done) = (Cell::new(0), Cell::new(0));
+    let throttle = RefCell::new(Throttle::new(Duration::from_millis(
+        options.progress_interval_ms,
+    )));
+    // Files enqueued mid-batch can push the counts past the totals taken at the start
+    let batch_progress = |force: bool| {
+        if throttle.borrow_mut().ready(force) {
+            events.emit(
+                "batch_progress",
+                BatchProgressPayload {
+                    job_id,
+                    files_done: files_done.get(),
+                    files_total: totals.files.max(files_done.get()),
+                    pages_done: pages_done.get(),
+                    pages_total: totals.pages.max(pages_done.get()),
+                },
+            );
+        }
+    };
+    let on_page_saved = |page_index| {
+        on_page_saved(page_index);
+        pages_done.set(pages_done.get() + 1);
+        batch_progress(false);
+    };
+    // Saved pages were counted as they went; merged ones only exist once the file is done
+    let file_done = |report: &FileReport| {
+        let merged = if options.merge {
+            report.pages_converted
+        } else {
+            0
+        };
+        let counted = report.pages_up_to_date + report.failed_pages.len() + merged;
+        pages_done.set(pages_done.get() + counted);
+        files_done.set(files_done.get() + 1);
+        batch_progress(true);
+    };
+    batch_progress(true);
+
+    let page_hashes = RefCell::new(PageHashes::default());
+    for file in files {
+        processed.push(file.clone());
+
+        let filename = Path::new(&file.input_path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown");
+
+        let skip_status = if aborted {
+            Some("skipped")
+        } else if batch.file_cancelled(&file.input_path) {
+            Some("cancelled")
+        } else {
+            None
+        };
+        if let Some(status) = skip_status {
+            events.emit(
+                "file_status",
+                FileStatusPayload {
+                    filename: filename.to_string(),
+                    status: status.into(),
+                    ..Default::default()
+                },
+            );
+            let report = FileReport {
+                input_path: file.input_path.clone(),
+                filename: filename.to_string(),
+                status: status.into(),
+                ..Default::default()
+            };
+            file_done(&report);
+            reports.push(report);
+            continue;
+        }
+
+        events.emit(
+            "file_status",
+            FileStatusPayload {
+                filename: filename.to_string(),
+                status: "processing".into(),
+                ..Default::default()
+            },
+        );
+
+        let prepared = std::fs::create_dir_all(&file.output_dir)
+            .map_err(|e| ConvertError::io(&file.output_dir, e))
+            .and_then(|_| download::fetch_if_remote(events, &batch, &file.input_path));
+        let downloaded = match prepared {
+            Ok(downloaded) => downloaded,
+            // Cancelled while downloading
+            Err(_) if batch.file_cancelled(&file.input_path) => {
+                let report = FileReport {
+                    input_path: file.input_path.clone(),
+                    filename: filename.to_string(),
+                    status: "cancelled".into(),
+                    ..Default::default()
+                };
+                emit_file_status(events, &report, None, None);
+                file_done(&report);
+                reports.push(report);
+                continue;
+            }
+            Err(e) => {
+                let mut report = FileReport {
+                    input_path: file.input_path.clone(),
+                    filename: filename.to_string(),
+                    ..Default::default()
+                };
+                fail_file(events, &mut report, e);
+                file_done(&report);
+                reports.push(report);
+                aborted = options.on_error == ErrorPolicy::Abort;
+                continue;
+            }
+        };
+        let local_file = match &downloaded {
+            Some(path) => JobFile {
+                input_path: path.to_string_lossy().to_string(),
+                ..file.clone()
+            },
+            None => file.clone(),
+        };
+
+        let mut report = match pdfium.filter(|_| options.backend != Backend::Poppler) {
+            Some(pdfium) => convert_file(
+                events,
+                pdfium,
+                jobs,
+                &batch,
+                cache,
+                &local_file,
+                options,
+                &encode_settings,
+                &page_hashes,
+                &on_page_saved,
+            ),
+            None => convert_with_poppler(
+                events,
+                &batch,
+                &local_file,
+                options,
+                &encode_settings,
+                &on_page_saved,
+            ),
+        };
+        if let Some(path) = downloaded {
+            report.input_path = file.input_path.clone();
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        } else if file.data.is_none() && report.status == "success" && report.errors.is_empty() {
+            match dispose_source(Path::new(&file.input_path), options) {
+                Ok(moved) => {
+                    report.source_moved_to = moved.map(|p| p.to_string_lossy().to_string())
+                }
+                Err(e) => report.errors.push(e.into()),
+            }
+        }
+        aborted = options.on_error == ErrorPolicy::Abort && !report.errors.is_empty();
+        file_done(&report);
+        reports.push(report);
+    }
+
+    drop(batch);
+
+    let pdfium_version = match pdfium.filter(|_| options.backend != Backend::Poppler) {
+        Some(pdfium) => format!("{:?}", pdfium.bindings().version()),
+        None => format!("poppler {}", poppler::version().unwrap_or_default()),
+    };
+    session.record(started_at, pdfium_version, options, &reports);
+    let retry_of = jobs.record(job_id, output_dir, options, &processed, &reports);
+
+    let mut report = BatchReport::new(
+        job_id,
+        started_at,
+        started.elapsed().as_millis() as u64,
+        reports,
+    );
+    report.aborted = aborted;
+    report.retry_of = retry_of;
+    if let Some(format) = options.report_format {
+        report.write(Path::new(output_dir), format)?;
+    }
+    if let Some(url) = &options.webhook_url {
+        report.webhook_error = webhook::post_report(url, &report).err().map(Into::into);
+    }
+    Ok(report)
+}
+
+// Runs the batch on its own thread so the command returns at once. The report
+// arrives as a `batch_finished` event, or `batch_failed` if the batch couldn't run.
+// Without pdfium the pages go through poppler.
+fn spawn_job(
+    app: AppHandle,
+    pdfium: Option<Arc<Pdfium>>,
+    job_id: u64,
+    output_dir: String,
+    files: Vec<JobFile>,
+    options: ConvertOptions,
+) -> Result<String, ErrorPayload> {
+    std::thread::spawn(move || {
+        let totals = BatchTotals::count(pdfium.as_deref(), &files, &options);
+        let result = run_job(
+            &app,
+            pdfium.as_deref(),
+            &app.state::<SessionLog>(),
+            &app.state::<JobRegistry>(),
+            &app.state::<RenderCache>(),
+            job_id,
+            &output_dir,
+            files,
+            totals,
+            &options,
+            &|_| {},
+        );
+        match result {
+            Ok(report) => {
+                app.state::<History>()
+                    .record(&output_dir, &options, &report);
+                notify_finished(&app, &options, &report);
+                let _ = app.emit("batch_finished", report);
+            }
+            Err(error) => {
+                let _ = app.emit("batch_failed", BatchFailedPayload { job_id, error });
+            }
+        }
+    });
+    to_json(&JobStarted { job_id })
+}
+
+// Converts with poppler's tools, page by page like `convert_file`. `validate` keeps
+// out everything only pdfium can do, so pages go straight from render to encode.
+fn convert_with_poppler(
+    events: &dyn EventSink,
+    batch: &BatchControl,
+    file: &JobFile,
+    options: &ConvertOptions,
+    encode_settings: &EncodeSettings,
+    on_page_saved: &dyn Fn(usize),
+) -> FileReport {
+    let started = Instant::now();
+    let path_str = file.input_path.as_str();
+    let filename = Path::new(path_str)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown");
+    let mut report = FileReport {
+        input_path: path_str.to_string(),
+        filename: filename.to_string(),
+        status: "error".into(),
+        ..Default::default()
+    };
+
+    let page_count = match poppler::page_count(path_str) {
+        Ok(page_count) => page_count,
+        Err(e) => {
+            report.duration_ms = started.elapsed().as_millis() as u64;
+            fail_file(events, &mut report, e);
+            return report;
+        }
+    };
+    let page_range = file.page_range.as_ref().unwrap_or(&options.page_range);
+    let target_pages = parse_page_range(page_range, page_count);
+    if target_pages.is_empty() {
+        let error = ConvertError::InvalidPageRange {
+            range: page_range.clone(),
+        };
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        fail_file(events, &mut report, error);
+        return report;
+    }
+    let scales = options.page_scales(page_count, user_unit(path_str, None));
+    let source_modified = modified_time(Path::new(path_str));
+    let work_pages: Vec<usize> = target_pages
+        .iter()
+        .copied()
+        .filter(|p| file.pages.as_ref().is_none_or(|pages| pages.contains(p)))
+        .filter(|p| !file.done_pages.contains(p))
+        .collect();
+
+    let mut last_output = String::new();
+    let mut conflict_action = None;
+    let mut progress = Throttle::new(Duration::from_millis(options.progress_interval_ms));
+    let mut rate = PageRate::default();
+    for (idx, &page_index) in work_pages.iter().enumerate() {
+        rate.finish_page();
+        if let Some(throttle_ms) = options.throttle_ms.filter(|_| idx > 0) {
+            std::thread::sleep(Duration::from_millis(throttle_ms));
+        }
+        batch.wait_while_paused(path_str);
+        if batch.file_cancelled(path_str) {
+            return cancelled_file(events, report, started);
+        }
+        rate.start_page();
+
+        let page_path = file.output_dir.join(page_file_name(
+            filename,
+            page_index,
+            target_pages.len(),
+            page_count,
+            options,
+            encode_settings.ext,
+        ));
+        if options.skip_up_to_date && is_up_to_date(&page_path, source_modified) {
+            report.pages_up_to_date += 1;
+            last_output = page_path.to_string_lossy().to_string();
+            continue;
+        }
+        let out_path = match resolve_output_path(page_path, options.conflict_policy) {
+            Ok((out_path, action)) => {
+                conflict_action = action.or(conflict_action);
+                out_path
+            }
+            Err(e) => {
+                report.duration_ms = started.elapsed().as_millis() as u64;
+                fail_file(events, &mut report, e);
+                return report;
+            }
+        };
+        let Some(out_path) = out_path else {
+            continue;
+        };
+
+        let render_started = Instant::now();
+        let saved = poppler::render(path_str, page_index, scales[page_index]).and_then(
+            |(width, height, pixels)| {
+                // Sizes are only known once poppler has rendered the page
+                if progress.ready(idx == 0 || idx + 1 == work_pages.len()) {
+                    events.emit(
+                        "progress",
+                        ProgressPayload {
+                            filename: filename.to_string(),
+                            current: idx + 1,
+                            total: work_pages.len(),
+                            page: page_index + 1,
+                            width: u64::from(width),
+                            height: u64::from(height),
+                            output_path: (!last_output.is_empty()).then(|| last_output.clone()),
+                            pages_per_sec: rate.pages_per_sec(),
+                            eta_ms: rate.eta_ms(work_pages.len() - idx),
+                        },
+                    );
+                }
+                let render_ms = render_started.elapsed().as_millis() as u64;
+                let image =
+                    RgbaView::from_raw(width, height, pixels.as_slice()).ok_or_else(|| {
+                        ConvertError::RenderFailed {
+                            page: page_index + 1,
+                            message: "Rendered bitmap has an unexpected size".into(),
+                        }
+                    })?;
+                let encode_started = Instant::now();
+                save_image(&image, &out_path, encode_settings)?;
+                Ok(PageMetrics {
+                    page: page_index + 1,
+                    render_ms,
+                    encode_ms: encode_started.elapsed().as_millis() as u64,
+                    output_bytes: std::fs::metadata(&out_path).ok().map(|m| m.len()),
+                    ..Default::default()
+                })
+            },
+        );
+        match saved {
+            Ok(metrics) => {
+                last_output = out_path.to_string_lossy().to_string();
+                report.outputs.push(last_output.clone());
+                report.pages_converted += 1;
+                record_page(events, &mut report, options, metrics);
+                on_page_saved(page_index);
+                run_output_hook(&mut report, &out_path);
+            }
+            Err(e) => {
+                record_error(&mut report, e);
+                report.failed_pages.push(page_index + 1);
+                if options.on_error == ErrorPolicy::Abort {
+                    break;
+                }
+            }
+        }
+    }
+
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    if options.on_error == ErrorPolicy::Abort && !report.errors.is_empty() {
+        emit_file_status(events, &report, None, None);
+        return report;
+    }
+    report.status = "success".into();
+    emit_file_status(events, &report, Some(last_output), conflict_action);
+    report
+}
+
+// Returns the batch report to the frontend, notifying the desktop when enabled.
+fn finish_batch(
+    app: &AppHandle,
+    output_dir: &str,
+    options: &ConvertOptions,
+    report: BatchReport,
+) -> Result<String, ErrorPayload> {
+    app.state::<History>().record(output_dir, options, &report);
+    notify_finished(app, options, &report);
+    to_json(&report)
+}
+
+fn notify_finished(app: &AppHandle, options: &ConvertOptions, report: &BatchReport) {
+    let focused = app
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if !options.notify_on_finish || focused {
+        return;
+    }
+    let title = if report.aborted {
+        "notification.aborted"
+    } else if report.failed > 0 {
+        "notification.finished_with_errors"
+    } else {
+        "notification.finished"
+    };
+    let _ = app
+        .notification()
+        .builder()
+        .title(i18n::text(title, &[]))
+        .body(i18n::text(
+            "notification.summary",
+            &[
+                ("succeeded", report.succeeded.to_string()),
+                ("failed", report.failed.to_string()),
+                ("skipped", report.skipped.to_string()),
+            ],
+        ))
+        .show();
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, ErrorPayload> {
+    serde_json::to_string(value).map_err(|e| {
+        ConvertError::Internal {
+            message: e.to_string(),
+        }
+        .into()
+    })
+}
+
+fn new_job_file(
+    input_path: String,
+    output_dir: &Path,
+    options: &ConvertOptions,
+    used_dirs: &mut HashSet<PathBuf>,
+) -> JobFile {
+    let output_dir = if options.subfolder_per_document {
+        let stem = Path::new(&input_path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown");
+        document_output_dir(output_dir, stem, used_dirs)
+    } else {
+        output_dir.to_path_buf()
+    };
+    JobFile {
+        input_path,
+        output_dir,
+        pages: None,
+        done_pages: Vec::new(),
+        data: None,
+        page_range: None,
+    }
+}
+
+// Expands folders among the inputs and assigns every PDF its output directory.
+fn batch_files(
+    input_paths: Vec<String>,
+    output_dir: &str,
+    options: &ConvertOptions,
+) -> Result<Vec<JobFile>, ConvertError> {
+    let filter = InputFilter::new(&options.include, &options.exclude)?;
+    let mut pdfs = expand_inputs(input_paths, &filter);
+    sort_inputs(&mut pdfs, options.sort);
+    let mut used_dirs = HashSet::new();
+    Ok(pdfs
+        .into_iter()
+        .map(|pdf| {
+            let base_dir = if options.mirror_folders {
+                Path::new(output_dir).join(&pdf.relative_dir)
+            } else {
+                PathBuf::from(output_dir)
+            };
+            new_job_file(pdf.path, &base_dir, options, &mut used_dirs)
+        })
+        .collect())
+}
+
+// Like `batch_files`, with each document's page range taken from the spec.
+fn spec_files(spec: &JobSpec) -> Result<Vec<JobFile>, ConvertError> {
+    let input_paths = spec.inputs.iter().map(|i| i.path().to_string()).collect();
+    let mut files = batch_files(input_paths, &spec.output_dir, &spec.options)?;
+    for file in &mut files {
+        file.page_range = spec.page_range_for(&file.input_path);
+    }
+    Ok(files)
+}
+
+#[tauri::command(async)]
+fn convert_pdf(
+    app: AppHandle,
+    jobs: State<'_, JobRegistry>,
+    input_paths: Vec<String>,
+    output_dir: String,
+    options: ConvertOptions,
+) -> Result<String, ErrorPayload> {
+    options.validate()?;
+    let pdfium = match shared_pdfium(&app) {
+        Ok(pdfium) => pdfium,
+        Err(ConvertError::LibraryLoad { .. })
+            if !options.dry_run
+                && options.encode_settings().ext != "svg"
+                && poppler::version().is_some() =>
+        {
+            let options = ConvertOptions {
+                backend: Backend::Poppler,
+                ..options
+            };
+            options.validate()?;
+            let files = batch_files(input_paths, &output_dir, &options)?;
+            let job_id = jobs.register(&output_dir);
+            return spawn_job(app, None, job_id, output_dir, files, options);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let files = batch_files(input_paths, &output_dir, &options)?;
+
+    if options.dry_run {
+        return to_json(&plan_conversion(&pdfium, &files, &options));
+    }
+
+    app.state::<SettingsStore>()
+        .remember(Some(&output_dir), &options);
+    spawn_job(
+        app,
+        Some(pdfium),
+        jobs.register(&output_dir),
+        output_dir,
+        files,
+        options,
+    )
+}
+
+// Converts a PDF handed over as bytes, e.g. dragged from a browser or pasted, without
+// writing it to disk first. `name` is only used to name the outputs.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command(async)]
+fn convert_pdf_bytes(
+    app: AppHandle,
+    jobs: State<'_, JobRegistry>,
+    name: String,
+    data: Vec<u8>,
+    output_dir: String,
+    options: ConvertOptions,
+) -> Result<String, ErrorPayload> {
+    options.validate()?;
+    let pdfium = shared_pdfium(&app)?;
+    let file = JobFile {
+        data: Some(Arc::new(data)),
+        ..new_job_file(name, Path::new(&output_dir), &options, &mut HashSet::new())
+    };
+
+    if options.dry_run {
+        return to_json(&plan_conversion(&pdfium, &[file], &options));
+    }
+
+    app.state::<SettingsStore>()
+        .remember(Some(&output_dir), &options);
+    spawn_job(
+        app,
+        Some(pdfium),
+        jobs.register(&output_dir),
+        output_dir,
+        vec![file],
+        options,
+    )
+}
+
+// Renders one page (1-based) of a PDF outside of any batch and hands it to `use_page`.
+fn with_rendered_page<T>(
+    app: &AppHandle,
+    path: &str,
+    page: usize,
+    scale: f32,
+    use_page: impl FnOnce(&RgbaView) -> Result<T, ConvertError>,
+) -> Result<T, ConvertError> {
+    validate_scale(scale)?;
+    let pdfium = shared_pdfium(app)?;
+    let document = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|e| ConvertError::pdf_open(path, e))?;
+    let page_index = page
+        .checked_sub(1)
+        .filter(|&index| index < document.pages().len() as usize)
+        .ok_or_else(|| ConvertError::InvalidPageRange {
+            range: page.to_string(),
+        })?;
+    let mut buffer = PageBuffer::for_sizes(std::iter::once(render_size(
+        &document,
+        page_index,
+        scale,
+        &RenderSettings::default(),
+    )));
+    let image = buffer.render(&document, page_index, scale, &RenderSettings::default())?;
+    use_page(&image)
+}
+
+// Renders one page (1-based) straight onto the clipboard, e.g. to paste it into an
+// email or a slide.
+#[tauri::command(async)]
+fn copy_page_to_clipboard(
+    app: AppHandle,
+    path: String,
+    page: usize,
+    scale: f32,
+) -> Result<(), ErrorPayload> {
+    with_rendered_page(&app, &path, page, scale, |image| {
+        app.clipboard()
+            .write_image(&tauri::image::Image::new(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+            ))
+            .map_err(|e| ConvertError::ClipboardFailed {
+                message: e.to_string(),
+            })
+    })
+    .map_err(Into::into)
+}
+
+#[derive(serde::Deserialize)]
+struct PrintPage {
+    path: String,
+    page: usize, // 1-based
+    scale: f32,
+}
+
+// Prints existing output images, or renders a page of a PDF first, e.g. "convert and
+// print page 3".
+#[tauri::command(async)]
+fn print_images(
+    app: AppHandle,
+    paths: Vec<String>,
+    page: Option<PrintPage>,
+) -> Result<(), ErrorPayload> {
+    let mut paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    if let Some(page) = page {
+        let dir = std::env::temp_dir().join("pdf-to-image").join("print");
+        std::fs::create_dir_all(&dir).map_err(|e| ConvertError::io(&dir, e))?;
+        let stem = Path::new(&page.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let out_path = dir.join(format!("{}-{}.png", stem, page.page));
+        let settings = EncodeSettings {
+            ext: "png",
+            quality: default_quality(),
+            lossless: false,
+            max_bytes: None,
+            exif: None,
+            strip_metadata: false,
+            cmyk: None,
+            sixteen_bit: false,
+        };
+        with_rendered_page(&app, &page.path, page.page, page.scale, |image| {
+            save_image(image, &out_path, &settings)
+        })?;
+        paths.push(out_path);
+    }
+    print::send_to_printer(&paths).map_err(Into::into)
+}
+
+#[tauri::command]
+fn load_job_spec(path: String) -> Result<JobSpec, ErrorPayload> {
+    JobSpec::load(Path::new(&path)).map_err(Into::into)
+}
+
+#[tauri::command]
+fn save_job_spec(path: String, spec: JobSpec) -> Result<(), ErrorPayload> {
+    spec.save(Path::new(&path)).map_err(Into::into)
+}
+
+#[tauri::command(async)]
+fn run_job_spec(
+    app: AppHandle,
+    jobs: State<'_, JobRegistry>,
+    spec: JobSpec,
+) -> Result<String, ErrorPayload> {
+    let pdfium = shared_pdfium(&app)?;
+    let files = spec_files(&spec)?;
+
+    if spec.options.dry_run {
+        return to_json(&plan_conversion(&pdfium, &files, &spec.options));
+    }
+
+    spawn_job(
+        app,
+        Some(pdfium),
+        jobs.register(&spec.output_dir),
+        spec.output_dir,
+        files,
+        spec.options,
+    )
+}
+
+// Re-runs only the files, or pages, that failed in a previous job, as a new job so
+// the previous one's record stays as it was.
+#[tauri::command(async)]
+fn retry_failed(
+    app: AppHandle,
+    jobs: State<'_, JobRegistry>,
+    job_id: u64,
+) -> Result<String, ErrorPayload> {
+    let (output_dir, options, files) = jobs
+        .failed(job_id)
+        .ok_or(ConvertError::UnknownJob { job_id })?;
+    // Jobs that fell back to poppler are retried with it
+    let pdfium = match options.backend {
+        Backend::Poppler => None,
+        _ => Some(shared_pdfium(&app)?),
+    };
+    spawn_job(
+        app,
+        pdfium,
+        jobs.register_retry(job_id, &output_dir),
+        output_dir,
+        files,
+        options,
+    )
+}
+
+// Renders an evenly spaced sample of each document's selected pages and
+// extrapolates the encoded size to the whole selection.
+#[tauri::command]
+fn estimate_output_size(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    options: ConvertOptions,
+    sample_pages: Option<usize>,
+) -> Result<SizeEstimate, ErrorPayload> {
+    options.validate()?;
+    let pdfium = shared_pdfium(&app)?;
+    let cmyk = options.cmyk_conversion()?;
+    let encode_settings = EncodeSettings {
+        cmyk: cmyk.as_ref(),
+        ..options.encode_settings()
+    };
+    let render_settings = options.render_settings();
+    let sample_pages = sample_pages.unwrap_or(5).max(1);
+    let mut files = Vec::new();
+
+    for path_str in input_paths {
+        let mut estimate = FileSizeEstimate {
+            input_path: path_str.clone(),
+            pages: 0,
+            sampled_pages: 0,
+            estimated_bytes: 0,
+            error: None,
+        };
+
+        let name = Path::new(&path_str)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let file = new_job_file(path_str, Path::new(""), &options, &mut HashSet::new());
+        let prepared = prepare_document(&pdfium, &file, &options, &name).and_then(|prepared| {
+            let PreparedDocument {
+                mut document,
+                target_pages,
+                user_unit,
+                ..
+            } = prepared;
+            let stamper = Stamper::new(&mut document, options.stamps(), &name)?;
+            Ok((document, target_pages, user_unit, stamper))
+        });
+        let (document, target_pages, user_unit, stamper) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                estimate.error = Some(e.into());
+                files.push(estimate);
+                continue;
+            }
+        };
+        estimate.pages = target_pages.len();
+        let scales = options.page_scales(document.pages().len(), options.render_unit(user_unit));
+        let step = target_pages.len().div_ceil(sample_pages).max(1);
+        let mut sampled_bytes = 0u64;
+        let mut last_error = None;
+        let mut page_buffer = PageBuffer::for_sizes(std::iter::empty());
+        if options.backend == Backend::Mupdf {
+            match mupdf_source(&file, &options) {
+                Ok(source) => page_buffer.use_renderer(Box::new(source)),
+                Err(e) => {
+                    estimate.error = Some(e.into());
+                    files.push(estimate);
+                    continue;
+                }
+            }
+        }
+        register_stages(&mut page_buffer, &render_settings, &stamper);
+        for &page_index in target_pages.iter().step_by(step) {
+            let scale = scales[page_index];
+            let encoded = match encode_settings.ext {
+                "svg" => svg::page(&document, page_index, scale, &render_settings, &stamper),
+                _ => {
+                    let mut buffer = std::io::Cursor::new(Vec::new());
+                    page_buffer
+                        .render(&document, page_index, scale, &render_settings)
+                        .and_then(|image| encode_image(&image, &mut buffer, &encode_settings))
+                        .map(|()| buffer.into_inner())
+                }
+            };
+            match encoded {
+                Ok(bytes) => {
+                    sampled_bytes += bytes.len() as u64;
+                    estimate.sampled_pages += 1;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        if estimate.sampled_pages > 0 {
+            estimate.estimated_bytes =
+                sampled_bytes / estimate.sampled_pages as u64 * estimate.pages as u64;
+        } else if estimate.pages == 0 {
+            estimate.error = Some(
+                ConvertError::InvalidPageRange {
+                    range: options.page_range.clone(),
+                }
+                .into(),
+            );
+        } else {
+            estimate.error = last_error.map(Into::into);
+        }
+        files.push(estimate);
+    }
+
+    let total_pages = files.iter().map(|f| f.pages).sum();
+    let estimated_bytes = files.iter().map(|f| f.estimated_bytes).sum();
+    Ok(SizeEstimate {
+        files,
+        total_pages,
+        estimated_bytes,
+    })
+}
+
+// Reports how much of each page is inked and which pages are blank, without
+// converting anything, so the page range can be narrowed first.
+#[tauri::command(async)]
+fn find_blank_pages(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    options: Option<ink::BlankPageOptions>,
+) -> Result<Vec<ink::InkReport>, ErrorPayload> {
+    let pdfium = shared_pdfium(&app)?;
+    let options = options.unwrap_or_default();
+    let reports = input_paths
+        .into_iter()
+        .map(|path| {
+            let mut report = ink::InkReport {
+                input_path: path.clone(),
+                pages: Vec::new(),
+                blank_pages: Vec::new(),
+                error: None,
+            };
+            match pdfium.load_pdf_from_file(&path, None) {
+                Ok(document) => {
+                    let pages = parse_page_range(&options.page_range, document.pages().len());
+                    ink::measure(&document, &pages, &options, &mut report);
+                }
+                Err(e) => report.error = Some(ConvertError::pdf_open(&path, e).into()),
+            }
+            report
+        })
+        .collect();
+    Ok(reports)
+}
+
+// Lists the files embedded in each PDF and, given an output folder, extracts them into
+// the folder the document's pages are converted to.
+#[tauri::command(async)]
+fn extract_attachments(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    output_dir: Option<String>,
+) -> Result<Vec<attachments::AttachmentReport>, ErrorPayload> {
+    let pdfium = shared_pdfium(&app)?;
+    let reports = input_paths
+        .into_iter()
+        .map(|path| {
+            let mut report = attachments::AttachmentReport {
+                input_path: path.clone(),
+                attachments: Vec::new(),
+                error: None,
+            };
+            let dir = output_dir.as_ref().map(|output_dir| {
+                let stem = Path::new(&path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Path::new(output_dir).join(stem)
+            });
+            match pdfium.load_pdf_from_file(&path, None) {
+                Ok(document) => attachments::extract(&document, dir.as_deref(), &mut report),
+                Err(e) => report.error = Some(ConvertError::pdf_open(&path, e).into()),
+            }
+            report
+        })
+        .collect();
+    Ok(reports)
+}
+
+fn emit_queue_changed(app: &AppHandle, queue: &ConversionQueue) {
+    let _ = app.emit("queue_changed", queue.state());
+}
+
+// Queues PDFs the app was opened with, e.g. by double-clicking one, and brings the
+// window forward. Relative paths are taken from the launching process's `cwd`.
+fn open_documents(app: &AppHandle, args: impl IntoIterator<Item = String>, cwd: &Path) {
+    let paths: Vec<String> = args
+        .into_iter()
+        .map(|arg| cwd.join(arg))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    let queue = app.state::<ConversionQueue>();
+    queue.enqueue(paths);
+    emit_queue_changed(app, &queue);
+    tray::show_main_window(app);
+}
+
+#[tauri::command]
+fn enqueue_files(
+    app: AppHandle,
+    queue: State<'_, ConversionQueue>,
+    input_paths: Vec<String>,
+) -> Vec<QueueEntry> {
+    let added = queue.enqueue(input_paths);
+    emit_queue_changed(&app, &queue);
+    added
+}
+
+#[tauri::command]
+fn remove_from_queue(app: AppHandle, queue: State<'_, ConversionQueue>, id: u64) -> bool {
+    let removed = queue.remove(id);
+    emit_queue_changed(&app, &queue);
+    removed
+}
+
+#[tauri::command]
+fn reorder_queue(app: AppHandle, queue: State<'_, ConversionQueue>, ids: Vec<u64>) {
+    queue.reorder(&ids);
+    emit_queue_changed(&app, &queue);
+}
+
+// Checks dropped paths before they are added to a batch.
+#[tauri::command(async)]
+fn inspect_files(app: AppHandle, paths: Vec<String>) -> Vec<inspect::FileInspection> {
+    let pdfium = shared_pdfium(&app).ok();
+    paths
+        .iter()
+        .map(|path| inspect::inspect(pdfium.as_deref(), path))
+        .collect()
+}
+
+// Renders two revisions of a document page by page and scores how much each page
+// changed, writing highlighted diff images when an output folder is given.
+#[tauri::command(async)]
+fn compare_pdfs(
+    app: AppHandle,
+    old_path: String,
+    new_path: String,
+    options: Option<compare::CompareOptions>,
+) -> Result<compare::Comparison, ErrorPayload> {
+    let pdfium = shared_pdfium(&app)?;
+    compare::compare(&pdfium, &old_path, &new_path, &options.unwrap_or_default())
+        .map_err(Into::into)
+}
+
+// Lists the PDFs below a folder, e.g. to enqueue them one by one.
+#[tauri::command]
+fn scan_folder(
+    path: String,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+) -> Result<Vec<DiscoveredPdf>, ErrorPayload> {
+    let filter = InputFilter::new(&include.unwrap_or_default(), &exclude.unwrap_or_default())?;
+    Ok(discover::scan_folder(Path::new(&path), &filter))
+}
+
+#[tauri::command]
+fn get_queue(queue: State<'_, ConversionQueue>) -> QueueState {
+    queue.state()
+}
+
+// Converts queued files until the queue is empty, picking up entries added meanwhile.
+// Without settings, resumes with those of the queue's last run, e.g. after an app
+// restart, or else the last-used settings.
+fn drain_queue(
+    app: &AppHandle,
+    output_dir: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<String, ErrorPayload> {
+    let queue = app.state::<ConversionQueue>();
+    let (output_dir, options) = match (output_dir, options) {
+        (Some(output_dir), Some(options)) => (output_dir, options),
+        _ => queue
+            .saved_settings()
+            .or_else(|| app.state::<SettingsStore>().saved())
+            .ok_or(ConvertError::NoSavedSettings)?,
+    };
+    let pdfium = shared_pdfium(app)?;
+    if !queue.try_start(&output_dir, &options) {
+        return Err(ConvertError::QueueBusy.into());
+    }
+    let pending: Vec<JobFile> = queue
+        .state()
+        .entries
+        .into_iter()
+        .map(|entry| JobFile {
+            done_pages: entry.done_pages,
+            ..new_job_file(
+                entry.input_path,
+                Path::new(&output_dir),
+                &options,
+                &mut HashSet::new(),
+            )
+        })
+        .collect();
+    let totals = BatchTotals::count(Some(&pdfium), &pending, &options);
+
+    let jobs = app.state::<JobRegistry>();
+    let job_id = jobs.register(&output_dir);
+    let mut used_dirs = HashSet::new();
+    // Cancelling leaves the files that didn't start in the queue
+    let files = queue.drain(|| jobs.cancelled(job_id)).map(|entry| {
+        emit_queue_changed(app, &queue);
+        JobFile {
+            done_pages: entry.done_pages,
+            ..new_job_file(
+                entry.input_path,
+                Path::new(&output_dir),
+                &options,
+                &mut used_dirs,
+            )
+        }
+    });
+
+    let result = run_job(
+        app,
+        Some(&pdfium),
+        &app.state::<SessionLog>(),
+        &jobs,
+        &app.state::<RenderCache>(),
+        job_id,
+        &output_dir,
+        files,
+        totals,
+        &options,
+        &|page_index| queue.checkpoint(page_index),
+    );
+    queue.finish();
+    emit_queue_changed(app, &queue);
+    result.and_then(|report| finish_batch(app, &output_dir, &options, report))
+}
+
+#[tauri::command(async)]
+async fn process_queue(
+    app: AppHandle,
+    output_dir: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<String, ErrorPayload> {
+    // Keeps the queue off the async runtime's worker threads while it runs
+    tauri::async_runtime::spawn_blocking(move || drain_queue(&app, output_dir, options))
+        .await
+        .map_err(|e| ConvertError::Internal {
+            message: e.to_string(),
+        })?
+}
+
+// Starts the queue at `start_at` (unix seconds) with the given settings.
+#[tauri::command]
+fn schedule_queue(
+    app: AppHandle,
+    queue: State<'_, ConversionQueue>,
+    start_at: u64,
+    output_dir: String,
+    options: ConvertOptions,
+) {
+    queue.schedule(start_at, &output_dir, &options);
+    emit_queue_changed(&app, &queue);
+}
+
+#[tauri::command]
+fn cancel_schedule(app: AppHandle, queue: State<'_, ConversionQueue>) {
+    queue.cancel_schedule();
+    emit_queue_changed(&app, &queue);
+}
+
+// Converts one PDF that appeared in the watched folder as a batch of its own.
+// Files that were already in the folder are skipped once their outputs are up to
+// date, so restarting the watch doesn't convert them all again.
+fn convert_watched_file(
+    app: &AppHandle,
+    config: &WatchConfig,
+    input_path: String,
+    existing: bool,
+) -> Result<BatchReport, ErrorPayload> {
+    let mut options = app.state::<PresetStore>().get(&config.preset)?;
+    options.skip_up_to_date |= existing;
+    let pdfium = shared_pdfium(app)?;
+    let file = new_job_file(
+        input_path,
+        Path::new(&config.output_dir),
+        &options,
+        &mut HashSet::new(),
+    );
+    let totals = BatchTotals::count(Some(&pdfium), std::slice::from_ref(&file), &options);
+    let jobs = app.state::<JobRegistry>();
+    run_job(
+        app,
+        Some(&pdfium),
+        &app.state::<SessionLog>(),
+        &jobs,
+        &app.state::<RenderCache>(),
+        jobs.register(&config.output_dir),
+        &config.output_dir,
+        [file],
+        totals,
+        &options,
+        &|_| {},
+    )
+}
+
+// Converts with the named preset; PDFs already in the folder are converted too.
+#[tauri::command]
+fn start_watch_folder(
+    app: AppHandle,
+    watch: State<'_, FolderWatch>,
+    presets: State<'_, PresetStore>,
+    folder: String,
+    output_dir: String,
+    preset: String,
+) -> Result<(), ErrorPayload> {
+    presets.get(&preset)?.validate()?;
+    let config = WatchConfig {
+        folder,
+        output_dir,
+        preset,
+    };
+    watch.start(app, config).map_err(Into::into)
+}
+
+#[tauri::command]
+fn stop_watch_folder(watch: State<'_, FolderWatch>) {
+    watch.stop();
+}
+
+#[tauri::command]
+fn get_watch_folder(watch: State<'_, FolderWatch>) -> Option<WatchConfig> {
+    watch.config()
+}
+
+// Lets the frontend show a library load failure as soon as it starts.
+#[tauri::command]
+fn pdfium_status(library: State<'_, PdfiumLibrary>) -> Result<String, ErrorPayload> {
+    library.status().map_err(Into::into)
+}
+
+#[tauri::command]
+fn check_environment(
+    app: AppHandle,
+    library: State<'_, PdfiumLibrary>,
+    output_dir: Option<String>,
+) -> diagnostics::Environment {
+    diagnostics::check(app.package_info().version.to_string(), &library, output_dir)
+}
+
+#[tauri::command]
+fn get_pdfium_path(library: State<'_, PdfiumLibrary>) -> Option<String> {
+    library.configured_path()
+}
+
+// Installs the prebuilt pdfium for this platform into the app data dir and binds to it,
+// or uses it from the next launch when a library is already loaded (`None`).
+#[tauri::command]
+async fn download_pdfium(app: AppHandle) -> Result<Option<String>, ErrorPayload> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ConvertError::Internal {
+            message: e.to_string(),
+        })?
+        .join("pdfium");
+    tauri::async_runtime::spawn_blocking(move || {
+        let library_path = installer::install(&dir)?;
+        app.state::<PdfiumLibrary>()
+            .set_path(Some(library_path.to_string_lossy().to_string()))
+    })
+    .await
+    .map_err(|e| ConvertError::Internal {
+        message: e.to_string(),
+    })?
+    .map_err(Into::into)
+}
+
+// `None` goes back to searching the bundled and system libraries. Returns the version
+// now bound, or `None` when the change waits for the next launch.
+#[tauri::command]
+fn set_pdfium_path(
+    library: State<'_, PdfiumLibrary>,
+    path: Option<String>,
+) -> Result<Option<String>, ErrorPayload> {
+    library.set_path(path).map_err(Into::into)
+}
+
+#[tauri::command]
+fn get_fonts_dir(library: State<'_, PdfiumLibrary>) -> Option<String> {
+    library.fonts_dir()
+}
+
+// A directory holding a fonts.conf for fontconfig; takes effect on the next launch.
+#[tauri::command]
+fn set_fonts_dir(
+    library: State<'_, PdfiumLibrary>,
+    path: Option<String>,
+) -> Result<(), ErrorPayload> {
+    library.set_fonts_dir(path).map_err(Into::into)
+}
+
+#[tauri::command]
+fn get_font_substitution(library: State<'_, PdfiumLibrary>) -> fonts::FontSubstitution {
+    library.font_substitution()
+}
+
+// `false` when the change needs a restart to take effect
+#[tauri::command]
+fn set_font_substitution(
+    library: State<'_, PdfiumLibrary>,
+    substitution: fonts::FontSubstitution,
+) -> Result<bool, ErrorPayload> {
+    library
+        .set_font_substitution(substitution)
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsStore>) -> Settings {
+    settings.get()
+}
+
+#[tauri::command]
+fn set_settings(store: State<'_, SettingsStore>, settings: Settings) -> Result<(), ErrorPayload> {
+    store.set(settings).map_err(Into::into)
+}
+
+#[tauri::command]
+fn get_history(history: State<'_, History>) -> Vec<HistoryEntry> {
+    history.list()
+}
+
+#[tauri::command]
+fn clear_history(history: State<'_, History>) -> Result<(), ErrorPayload> {
+    history.clear().map_err(Into::into)
+}
+
+// Runs a past batch again with its inputs, output folder and options.
+#[tauri::command(async)]
+fn reconvert_from_history(
+    app: AppHandle,
+    jobs: State<'_, JobRegistry>,
+    history: State<'_, History>,
+    id: u64,
+) -> Result<String, ErrorPayload> {
+    let entry = history
+        .get(id)
+        .ok_or(ConvertError::UnknownHistoryEntry { id })?;
+    let pdfium = shared_pdfium(&app)?;
+    let files = batch_files(entry.inputs, &entry.output_dir, &entry.options)?;
+    spawn_job(
+        app,
+        Some(pdfium),
+        jobs.register(&entry.output_dir),
+        entry.output_dir,
+        files,
+        entry.options,
+    )
+}
+
+#[tauri::command]
+fn list_presets(presets: State<'_, PresetStore>) -> Vec<Preset> {
+    presets.list()
+}
+
+#[tauri::command]
+fn save_preset(
+    presets: State<'_, PresetStore>,
+    name: String,
+    options: ConvertOptions,
+) -> Result<Vec<Preset>, ErrorPayload> {
+    presets.save(name, options)?;
+    Ok(presets.list())
+}
+
+#[tauri::command]
+fn delete_preset(presets: State<'_, PresetStore>, name: String) -> Result<bool, ErrorPayload> {
+    presets.delete(&name).map_err(Into::into)
+}
+
+// Without a job id, these act on every running batch.
+#[tauri::command]
+fn pause_batch(jobs: State<'_, JobRegistry>, job_id: Option<u64>) {
+    jobs.set_paused(job_id, true);
+}
+
+#[tauri::command]
+fn resume_batch(jobs: State<'_, JobRegistry>, job_id: Option<u64>) {
+    jobs.set_paused(job_id, false);
+}
+
+#[tauri::command]
+fn cancel_batch(jobs: State<'_, JobRegistry>, job_id: Option<u64>) {
+    jobs.cancel_batch(job_id);
+}
+
+// Skips the remaining pages of one document; the rest of the batch carries on.
+#[tauri::command]
+fn cancel_file(jobs: State<'_, JobRegistry>, job_id: u64, input_path: String) {
+    jobs.cancel_file(job_id, &input_path);
+}
+
+#[tauri::command]
+fn clear_render_cache(cache: State<'_, RenderCache>) -> Result<(), ErrorPayload> {
+    cache
+        .clear()
+        .map_err(|e| ConvertError::io(cache.dir(), e).into())
+}
+
+#[tauri::command]
+fn export_error_log(session: State<'_, SessionLog>, path: String) -> Result<String, ErrorPayload> {
+    let path = PathBuf::from(path);
+    std::fs::write(&path, session.render()).map_err(|e| ConvertError::io(&path, e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Where tauri keeps the app's config; matches `identifier` in tauri.conf.json.
+const APP_IDENTIFIER: &str = "com.anhduong.tauri-app";
+
+// Entry point for `pdf-to-image convert ...`; returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    // Loading the window's settings applies its language and post-processing command;
+    // without them, the system language is used
+    match dirs::config_dir() {
+        Some(dir) => drop(SettingsStore::load(
+            dir.join(APP_IDENTIFIER).join("settings.json"),
+        )),
+        None => i18n::set_locale(i18n::Locale::resolve(None)),
+    }
+    cli::run(args)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        // A file opened while the app runs goes to the running instance
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            open_documents(app, args.into_iter().skip(1), Path::new(&cwd));
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![convert_pdf, open_folder])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .manage(SessionLog::default())
+        .manage(JobRegistry::default())
+        .manage(deeplink::PendingDeepLinks::default())
+        .setup(|app| {
+            let resource_dir = app
+                .path()
+                .resource_dir()
+                .unwrap_or_else(|_| std::env::current_dir().unwrap_or_default());
+            let library =
+                PdfiumLibrary::load(app.path().app_data_dir()?.join("pdfium.json"), resource_dir);
+            // The window asks for the status once it's loaded and shows the error
+            if let Err(e) = library.status() {
+                app.state::<SessionLog>().record_startup(&e);
+            }
+            app.manage(library);
+            let config_dir = app.path().app_config_dir()?;
+            app.manage(PresetStore::load(config_dir.join("presets.json")));
+            app.manage(SettingsStore::load(config_dir.join("settings.json")));
+            app.manage(History::load(
+                app.path().app_data_dir()?.join("history.json"),
+            ));
+            let queue_path = app.path().app_data_dir()?.join("queue.json");
+            app.manage(ConversionQueue::load(queue_path));
+            let cache_dir = app.path().app_cache_dir()?.join("render_cache");
+            app.manage(RenderCache::new(cache_dir));
+            let watch = FolderWatch::new(app.path().app_data_dir()?.join("watch.json"));
+            if let Some(config) = watch.saved_config() {
+                let _ = watch.start(app.handle().clone(), config);
+            }
+            app.manage(watch);
+            tray::setup(app)?;
+            scheduler::spawn(app.handle().clone());
+            open_documents(
+                app.handle(),
+                std::env::args().skip(1),
+                &std::env::current_dir().unwrap_or_default(),
+            );
+            // Registered at runtime too, for builds that weren't installed
+            #[cfg(any(windows, target_os = "linux"))]
+            let _ = app.deep_link().register_all();
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for url in event.urls() {
+                    deeplink::open(&handle, &url);
+                }
+            });
+            for url in app
+                .deep_link()
+                .get_current()
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+            {
+                deeplink::open(app.handle(), &url);
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // Keep converting in the tray instead of quitting mid-batch
+            if let WindowEvent::CloseRequested { api, .. } = event {
+                if window.state::<JobRegistry>().is_running() {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            cancel_batch,
+            cancel_file,
+            cancel_schedule,
+            check_environment,
+            clear_history,
+            clear_render_cache,
+            compare_pdfs,
+            convert_pdf,
+            convert_pdf_bytes,
+            copy_page_to_clipboard,
+            deeplink::answer_deep_link,
+            deeplink::pending_deep_links,
+            delete_preset,
+            download_pdfium,
+            enqueue_files,
+            estimate_output_size,
+            export_error_log,
+            extract_attachments,
+            find_blank_pages,
+            get_font_substitution,
+            get_fonts_dir,
+            get_history,
+            get_pdfium_path,
+            get_queue,
+            get_settings,
+            get_watch_folder,
+            inspect_files,
+            list_presets,
+            load_job_spec,
+            process_queue,
+            reconvert_from_history,
+            remove_from_queue,
+            reorder_queue,
+            reveal_file,
+            retry_failed,
+            run_job_spec,
+            save_job_spec,
+            save_preset,
+            scan_folder,
+            schedule_queue,
+            set_font_substitution,
+            set_fonts_dir,
+            set_pdfium_path,
+            set_settings,
+            start_watch_folder,
+            stop_watch_folder,
+            open_folder,
+            pause_batch,
+            pdfium_status,
+            print_images,
+            resume_batch
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, _event| {
+            // macOS hands double-clicked files over as an event instead of arguments
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Opened { urls } = _event {
+                let paths = urls
+                    .into_iter()
+                    .filter_map(|url| url.to_file_path().ok())
+                    .map(|path| path.to_string_lossy().to_string());
+                open_documents(_app, paths, Path::new("/"));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options(extra: serde_json::Value) -> ConvertOptions {
+        let mut options = json!({ "format": "png", "scale": 1.0 });
+        if let (Some(options), serde_json::Value::Object(extra)) = (options.as_object_mut(), extra)
+        {
+            options.extend(extra);
+        }
+        serde_json::from_value(options).unwrap()
+    }
+
+    #[test]
+    fn pads_page_numbers_to_the_page_count() {
+        assert_eq!(padded_page_number(7, 9, None), "7");
+        assert_eq!(padded_page_number(7, 120, None), "007");
+        assert_eq!(padded_page_number(7, 120, Some(4)), "0007");
+        let options = options(json!({}));
+        assert_eq!(
+            page_file_name("report", 4, 12, 12, &options, "png"),
+            "report_page_05.png"
+        );
+        assert_eq!(
+            page_file_name("report", 0, 1, 12, &options, "png"),
+            "report.png"
+        );
+    }
+
+    #[test]
+    fn renames_past_existing_outputs() {
+        let dir = std::env::temp_dir().join(format!("pdf-to-image-rename-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["page.png", "page_2.png"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let path = dir.join("page.png");
+        let (renamed, action) = resolve_output_path(path.clone(), ConflictPolicy::Rename).unwrap();
+        assert_eq!(renamed, Some(dir.join("page_3.png")));
+        assert_eq!(action, Some("renamed"));
+        let (skipped, action) = resolve_output_path(path.clone(), ConflictPolicy::Skip).unwrap();
+        assert_eq!((skipped, action), (None, Some("skipped")));
+        assert!(matches!(
+            resolve_output_path(path, ConflictPolicy::Fail),
+            Err(ConvertError::OutputExists { .. })
+        ));
+        let fresh = dir.join("other.png");
+        let (kept, action) = resolve_output_path(fresh.clone(), ConflictPolicy::Rename).unwrap();
+        assert_eq!((kept, action), (Some(fresh), None));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("convert") {
+        std::process::exit(tauri_app_lib::run_cli(&args[1..]));
+    }
     tauri_app_lib::run()
 }
@@ -0,0 +1,83 @@
+use crate::error::ConvertError;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+const MB: u64 = 1024 * 1024;
+
+// How often a waiting reservation looks whether its file was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(200);
+
+// Approximate bytes held by rendered pages and merge buffers across running batches.
+// Reservations over the limit wait for others to be released and only fail outright
+// when a single one could never fit, or the file waiting on them is cancelled.
+#[derive(Default)]
+pub(crate) struct MemoryBudget {
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+pub(crate) struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn reserve(
+        &self,
+        bytes: u64,
+        limit_mb: Option<u64>,
+        cancelled: impl Fn() -> bool,
+    ) -> Result<Reservation<'_>, ConvertError> {
+        let mut in_use = self.in_use.lock().unwrap();
+        if let Some(limit) = limit_mb.map(|mb| mb.saturating_mul(MB)) {
+            if bytes > limit {
+                return Err(ConvertError::MemoryLimitExceeded {
+                    needed_mb: bytes.div_ceil(MB),
+                    limit_mb: limit / MB,
+                });
+            }
+            while *in_use + bytes > limit {
+                if cancelled() {
+                    return Err(ConvertError::Cancelled);
+                }
+                in_use = self.released.wait_timeout(in_use, CANCEL_POLL).unwrap().0;
+            }
+        }
+        *in_use += bytes;
+        Ok(Reservation {
+            budget: self,
+            bytes,
+        })
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.in_use.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_until_cancelled() {
+        let budget = MemoryBudget::default();
+        let _held = budget.reserve(MB, Some(1), || false).unwrap();
+        let waited = budget.reserve(MB, Some(1), || true);
+        assert!(matches!(waited, Err(ConvertError::Cancelled)));
+    }
+
+    #[test]
+    fn fits_once_released() {
+        let budget = MemoryBudget::default();
+        drop(budget.reserve(MB, Some(1), || false).unwrap());
+        assert!(budget.reserve(MB, Some(1), || true).is_ok());
+        assert!(matches!(
+            budget.reserve(2 * MB, Some(1), || false),
+            Err(ConvertError::MemoryLimitExceeded { .. })
+        ));
+    }
+}
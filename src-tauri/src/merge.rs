@@ -0,0 +1,206 @@
+use crate::encode::{part_path, save_image, view, widen, EncodeSettings, RgbaView};
+use crate::error::ConvertError;
+use image::RgbaImage;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+enum Target<'a> {
+    // Rows go straight to a hidden sibling file, so only the current page is held
+    Png {
+        writer: Box<png::StreamWriter<'static, BufWriter<File>>>,
+        file: File,
+        tmp_path: PathBuf,
+        sixteen_bit: bool,
+    },
+    // JPEG, WebP and TIFF encoders need the whole image at once
+    Canvas {
+        canvas: RgbaImage,
+        y: u32,
+        settings: EncodeSettings<'a>,
+    },
+}
+
+// Stacks pages into one output as they are rendered, so each page can be dropped
+// before the next one is rendered. Dropping it unfinished discards the output.
+pub(crate) struct MergedOutput<'a> {
+    out_path: PathBuf,
+    width: u32,
+    target: Option<Target<'a>>,
+}
+
+fn png_error(err: png::EncodingError) -> ConvertError {
+    ConvertError::EncodeFailed {
+        message: err.to_string(),
+    }
+}
+
+impl<'a> MergedOutput<'a> {
+    pub fn create(
+        out_path: PathBuf,
+        width: u32,
+        height: u32,
+        settings: &EncodeSettings<'a>,
+    ) -> Result<Self, ConvertError> {
+        let target = if settings.ext == "png" {
+            let tmp_path = part_path(&out_path);
+            let file = File::create(&tmp_path).map_err(|e| ConvertError::io(&tmp_path, e))?;
+            let handle = file
+                .try_clone()
+                .map_err(|e| ConvertError::io(&tmp_path, e))?;
+            let mut encoder = png::Encoder::new(BufWriter::new(handle), width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(match settings.sixteen_bit {
+                true => png::BitDepth::Sixteen,
+                false => png::BitDepth::Eight,
+            });
+            let writer = encoder
+                .write_header()
+                .and_then(|writer| writer.into_stream_writer())
+                .map_err(png_error);
+            match writer {
+                Ok(writer) => Target::Png {
+                    writer: Box::new(writer),
+                    file,
+                    tmp_path,
+                    sixteen_bit: settings.sixteen_bit,
+                },
+                Err(e) => {
+                    let _ = std::fs::remove_file(&tmp_path);
+                    return Err(e);
+                }
+            }
+        } else {
+            Target::Canvas {
+                canvas: RgbaImage::new(width, height),
+                y: 0,
+                // Metadata describes single pages
+                settings: EncodeSettings {
+                    exif: None,
+                    ..*settings
+                },
+            }
+        };
+        Ok(MergedOutput {
+            out_path,
+            width,
+            target: Some(target),
+        })
+    }
+
+    pub fn out_path(&self) -> &Path {
+        &self.out_path
+    }
+
+    pub fn push(&mut self, image: &RgbaView) -> Result<(), ConvertError> {
+        match self.target.as_mut() {
+            Some(Target::Png {
+                writer,
+                sixteen_bit,
+                ..
+            }) => {
+                let row_bytes = image.width() as usize * 4;
+                let mut padding = vec![0u8; (self.width as usize * 4).saturating_sub(row_bytes)];
+                if *sixteen_bit {
+                    padding.extend_from_within(..);
+                }
+                for row in image.as_raw().chunks_exact(row_bytes.max(1)) {
+                    // PNG stores 16-bit samples big-endian
+                    let wide;
+                    let row = match sixteen_bit {
+                        true => {
+                            wide = widen(row, u16::to_be_bytes);
+                            &wide
+                        }
+                        false => row,
+                    };
+                    writer
+                        .write_all(row)
+                        .and_then(|_| writer.write_all(&padding))
+                        .map_err(|e| ConvertError::EncodeFailed {
+                            message: e.to_string(),
+                        })?;
+                }
+            }
+            Some(Target::Canvas { canvas, y, .. }) => {
+                image::imageops::replace(canvas, image, 0, i64::from(*y));
+                *y += image.height();
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    // Leaves a transparent band where a page failed to render.
+    pub fn push_blank(&mut self, height: u32) -> Result<(), ConvertError> {
+        match self.target.as_mut() {
+            Some(Target::Png {
+                writer,
+                sixteen_bit,
+                ..
+            }) => {
+                let samples = match sixteen_bit {
+                    true => 8,
+                    false => 4,
+                };
+                let row = vec![0u8; self.width as usize * samples];
+                for _ in 0..height {
+                    writer
+                        .write_all(&row)
+                        .map_err(|e| ConvertError::EncodeFailed {
+                            message: e.to_string(),
+                        })?;
+                }
+            }
+            Some(Target::Canvas { y, .. }) => *y += height,
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), ConvertError> {
+        match self.target.take() {
+            Some(Target::Png {
+                writer,
+                file,
+                tmp_path,
+                ..
+            }) => {
+                let res = writer
+                    .finish()
+                    .map_err(png_error)
+                    .and_then(|_| file.sync_all().map_err(|e| ConvertError::io(&tmp_path, e)));
+                // Windows can't rename or remove a file that is still open
+                drop(file);
+                let res = res.and_then(|_| {
+                    std::fs::rename(&tmp_path, &self.out_path)
+                        .map_err(|e| ConvertError::io(&self.out_path, e))
+                });
+                if res.is_err() {
+                    let _ = std::fs::remove_file(&tmp_path);
+                }
+                res
+            }
+            Some(Target::Canvas {
+                canvas, settings, ..
+            }) => save_image(&view(&canvas), &self.out_path, &settings),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MergedOutput<'_> {
+    fn drop(&mut self) {
+        if let Some(Target::Png {
+            writer,
+            file,
+            tmp_path,
+            ..
+        }) = self.target.take()
+        {
+            drop(writer);
+            drop(file);
+            let _ = std::fs::remove_file(tmp_path);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// Physical page sizes. PDF measures pages in points of 1/72 inch, multiplied by the
+// page's UserUnit when it sets one; large-format drawings do to get past the 200 inch
+// limit on page sizes. Pdfium ignores UserUnit, so it's read from the file itself.
+
+use flate2::read::ZlibDecoder;
+use pdfium_render::prelude::*;
+use std::io::Read;
+
+const MM_PER_POINT: f32 = 25.4 / 72.0;
+
+// Slack for sizes rounded to whole points or drawn slightly off by the producer.
+const TOLERANCE_MM: f32 = 3.0;
+
+// Named paper sizes in millimetres, short side first.
+const PAPERS: [(&str, f32, f32); 19] = [
+    ("a0", 841.0, 1189.0),
+    ("a1", 594.0, 841.0),
+    ("a2", 420.0, 594.0),
+    ("a3", 297.0, 420.0),
+    ("a4", 210.0, 297.0),
+    ("a5", 148.0, 210.0),
+    ("a6", 105.0, 148.0),
+    ("b0", 1000.0, 1414.0),
+    ("b1", 707.0, 1000.0),
+    ("b2", 500.0, 707.0),
+    ("b3", 353.0, 500.0),
+    ("b4", 250.0, 353.0),
+    ("b5", 176.0, 250.0),
+    ("letter", 215.9, 279.4),
+    ("legal", 215.9, 355.6),
+    ("tabloid", 279.4, 431.8),
+    ("ansi-c", 431.8, 558.8),
+    ("ansi-d", 558.8, 863.6),
+    ("ansi-e", 863.6, 1117.6),
+];
+
+// A paper name such as "A3" or "letter", or "<width>x<height>" in millimetres, as its
+// short and long sides.
+pub(crate) fn parse_size(size: &str) -> Option<(f32, f32)> {
+    let size = size.trim().to_lowercase();
+    let (a, b) = match PAPERS.iter().find(|(name, _, _)| *name == size) {
+        Some(&(_, a, b)) => (a, b),
+        None => {
+            let (width, height) = size.split_once('x')?;
+            (width.trim().parse().ok()?, height.trim().parse().ok()?)
+        }
+    };
+    (a > 0.0 && b > 0.0).then(|| (a.min(b), a.max(b)))
+}
+
+// Pages to keep by physical size. Sides are compared short to short and long to long,
+// so orientation doesn't matter.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct SizeFilter {
+    // Paper sizes a page must be one of, e.g. ["A3", "A2"]
+    pub sizes: Vec<String>,
+    // Bounds a page must lie within, e.g. "A3" for A3 and larger
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+impl SizeFilter {
+    // The first size that's neither a known paper nor "<width>x<height>".
+    pub fn invalid_size(&self) -> Option<&str> {
+        self.sizes
+            .iter()
+            .chain(&self.min)
+            .chain(&self.max)
+            .find(|size| parse_size(size).is_none())
+            .map(String::as_str)
+    }
+
+    pub fn matches(&self, (width, height): (f32, f32)) -> bool {
+        let (short, long) = (width.min(height), width.max(height));
+        let bound = |size: &Option<String>| size.as_deref().and_then(parse_size);
+        let is_size = |(a, b): (f32, f32)| {
+            (short - a).abs() <= TOLERANCE_MM && (long - b).abs() <= TOLERANCE_MM
+        };
+        (self.sizes.is_empty() || self.sizes.iter().filter_map(|s| parse_size(s)).any(is_size))
+            && bound(&self.min)
+                .is_none_or(|(a, b)| short >= a - TOLERANCE_MM && long >= b - TOLERANCE_MM)
+            && bound(&self.max)
+                .is_none_or(|(a, b)| short <= a + TOLERANCE_MM && long <= b + TOLERANCE_MM)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+impl Orientation {
+    // As the page is shown, i.e. after its rotation.
+    pub fn of(page: &PdfPage) -> Self {
+        Orientation::of_size(page.width().value, page.height().value)
+    }
+
+    // Square pages count as portrait.
+    fn of_size(width: f32, height: f32) -> Self {
+        match width > height {
+            true => Orientation::Landscape,
+            false => Orientation::Portrait,
+        }
+    }
+}
+
+// The number after each `/UserUnit` key in `bytes`.
+fn user_units(bytes: &[u8], units: &mut Vec<f32>) {
+    const KEY: &[u8] = b"/UserUnit";
+    let mut rest = bytes;
+    while let Some(at) = rest.windows(KEY.len()).position(|window| window == KEY) {
+        rest = &rest[at + KEY.len()..];
+        let number: String = rest
+            .iter()
+            .skip_while(|b| b.is_ascii_whitespace())
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .map(|&b| char::from(b))
+            .collect();
+        if let Ok(unit) = number.parse::<f32>() {
+            units.push(unit);
+        }
+    }
+}
+
+// The number right after `key` in `bytes`, if any.
+fn number_after(bytes: &[u8], key: &[u8]) -> Option<f32> {
+    let at = bytes.windows(key.len()).position(|window| window == key)?;
+    let number: String = bytes[at + key.len()..]
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit() || **b == b'.')
+        .map(|&b| char::from(b))
+        .collect();
+    number.parse().ok()
+}
+
+// A decompressed object stream, where PDF 1.5 and later keep most dictionaries.
+pub(crate) struct ObjectStream {
+    // Where the first object starts; the objects' numbers and offsets come before it
+    pub first: usize,
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn object_streams(bytes: &[u8]) -> impl Iterator<Item = ObjectStream> + '_ {
+    const KEY: &[u8] = b"/ObjStm";
+    let mut from = 0;
+    std::iter::from_fn(move || loop {
+        let at = from
+            + bytes[from..]
+                .windows(KEY.len())
+                .position(|window| window == KEY)?;
+        from = at + KEY.len();
+        let start = from
+            + bytes[from..]
+                .windows(6)
+                .position(|window| window == b"stream")?
+            + 6;
+        // The stream's dictionary, which `/First` can come before or after `/ObjStm` in
+        let dict_start = bytes[..at]
+            .windows(3)
+            .rposition(|window| window == b"obj")
+            .unwrap_or(0);
+        let first = number_after(&bytes[dict_start..start], b"/First");
+        let data = bytes[start..]
+            .strip_prefix(b"\r")
+            .unwrap_or(&bytes[start..]);
+        let data = data.strip_prefix(b"\n").unwrap_or(data);
+        let mut inflated = Vec::new();
+        // Streams with other filters are left out
+        if let (Some(first), Ok(_)) = (first, ZlibDecoder::new(data).read_to_end(&mut inflated)) {
+            return Some(ObjectStream {
+                first: first as usize,
+                data: inflated,
+            });
+        }
+    })
+}
+
+// The UserUnit the pages of a PDF declare; 1 when none does, or when they disagree,
+// as it can't be told which page has which.
+pub(crate) fn user_unit(bytes: &[u8]) -> f32 {
+    let mut units = Vec::new();
+    user_units(bytes, &mut units);
+    for stream in object_streams(bytes) {
+        user_units(&stream.data, &mut units);
+    }
+    match units.split_first() {
+        Some((&unit, others)) if unit > 0.0 && others.iter().all(|&other| other == unit) => unit,
+        _ => 1.0,
+    }
+}
+
+// Width and height in millimetres as the page is shown, i.e. after its rotation.
+pub(crate) fn size_mm(page: &PdfPage, user_unit: f32) -> (f32, f32) {
+    (
+        to_mm(page.width().value, user_unit),
+        to_mm(page.height().value, user_unit),
+    )
+}
+
+fn to_mm(points: f32, user_unit: f32) -> f32 {
+    points * user_unit * MM_PER_POINT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn filter(sizes: &[&str], min: Option<&str>, max: Option<&str>) -> SizeFilter {
+        SizeFilter {
+            sizes: sizes.iter().map(|size| size.to_string()).collect(),
+            min: min.map(Into::into),
+            max: max.map(Into::into),
+        }
+    }
+
+    fn object_stream(objects: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(objects).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut pdf = format!(
+            "%PDF-1.5\n5 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Filter /FlateDecode /Length {} >>\nstream\n",
+            data.len()
+        )
+        .into_bytes();
+        pdf.extend_from_slice(&data);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n%%EOF\n");
+        pdf
+    }
+
+    #[test]
+    fn parses_paper_names_and_dimensions() {
+        assert_eq!(parse_size("A4"), Some((210.0, 297.0)));
+        assert_eq!(parse_size(" Letter "), Some((215.9, 279.4)));
+        assert_eq!(parse_size("420x297"), Some((297.0, 420.0)));
+        assert_eq!(parse_size("100 x 50.5"), Some((50.5, 100.0)));
+        assert_eq!(parse_size("a7"), None);
+        assert_eq!(parse_size("0x100"), None);
+        assert_eq!(parse_size("-5x100"), None);
+    }
+
+    #[test]
+    fn matches_sizes_either_way_round() {
+        let a4 = filter(&["A4"], None, None);
+        assert!(a4.matches((210.0, 297.0)));
+        assert!(a4.matches((297.0, 210.0)));
+        // A4 drawn in whole points
+        assert!(a4.matches((595.0 * MM_PER_POINT, 842.0 * MM_PER_POINT)));
+        assert!(!a4.matches((215.9, 279.4)));
+        assert!(filter(&["A4", "letter"], None, None).matches((279.4, 215.9)));
+        assert!(filter(&[], None, None).matches((1.0, 1.0)));
+    }
+
+    #[test]
+    fn matches_bounds() {
+        let a3_and_up = filter(&[], Some("A3"), None);
+        assert!(a3_and_up.matches((297.0, 420.0)));
+        assert!(a3_and_up.matches((841.0, 1189.0)));
+        assert!(!a3_and_up.matches((210.0, 297.0)));
+        // Long enough but too narrow
+        assert!(!a3_and_up.matches((210.0, 500.0)));
+
+        let a4_to_a3 = filter(&[], Some("A4"), Some("A3"));
+        assert!(a4_to_a3.matches((297.0, 210.0)));
+        assert!(a4_to_a3.matches((420.0, 297.0)));
+        assert!(!a4_to_a3.matches((148.0, 210.0)));
+        assert!(!a4_to_a3.matches((420.0, 594.0)));
+    }
+
+    #[test]
+    fn reports_invalid_sizes() {
+        assert_eq!(filter(&["A4", "B9"], None, None).invalid_size(), Some("B9"));
+        assert_eq!(filter(&[], None, Some("huge")).invalid_size(), Some("huge"));
+        assert_eq!(
+            filter(&["A4"], Some("A5"), Some("10x10")).invalid_size(),
+            None
+        );
+    }
+
+    #[test]
+    fn tells_orientation_from_size() {
+        assert_eq!(Orientation::of_size(842.0, 595.0), Orientation::Landscape);
+        assert_eq!(Orientation::of_size(595.0, 842.0), Orientation::Portrait);
+        assert_eq!(Orientation::of_size(600.0, 600.0), Orientation::Portrait);
+    }
+
+    #[test]
+    fn reads_user_units() {
+        assert_eq!(user_unit(b"%PDF-1.4\n<< /Type /Page >>"), 1.0);
+        assert_eq!(user_unit(b"<< /Type /Page /UserUnit 10 >>"), 10.0);
+        assert_eq!(user_unit(b"<< /UserUnit 2.5 >> << /UserUnit 2.5 >>"), 2.5);
+        // Pages that disagree can't be told apart
+        assert_eq!(user_unit(b"<< /UserUnit 2 >> << /UserUnit 3 >>"), 1.0);
+        assert_eq!(user_unit(b"<< /UserUnit 0 >>"), 1.0);
+        assert_eq!(
+            user_unit(&object_stream(b"1 0 << /Type /Page /UserUnit 4 >>")),
+            4.0
+        );
+    }
+
+    #[test]
+    fn scales_sizes_by_user_unit() {
+        // 14400 points is the most a page can measure without a UserUnit
+        assert!((to_mm(14400.0, 1.0) - 5080.0).abs() < 0.01);
+        assert!((to_mm(14400.0, 2.0) - 10160.0).abs() < 0.01);
+        assert!((to_mm(72.0, 10.0) - 254.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::error::ConvertError;
+use crate::ConvertOptions;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Preset {
+    pub name: String,
+    pub options: ConvertOptions,
+}
+
+// Named option sets for recurring jobs, kept in `store_path` as one JSON object
+// keyed by name.
+pub(crate) struct PresetStore {
+    presets: Mutex<BTreeMap<String, ConvertOptions>>,
+    store_path: PathBuf,
+}
+
+impl PresetStore {
+    pub fn load(store_path: PathBuf) -> Self {
+        let presets = std::fs::read(&store_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        PresetStore {
+            presets: Mutex::new(presets),
+            store_path,
+        }
+    }
+
+    fn persist(&self, presets: &BTreeMap<String, ConvertOptions>) -> Result<(), ConvertError> {
+        if let Some(parent) = self.store_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_vec_pretty(presets).map_err(|e| ConvertError::Internal {
+            message: e.to_string(),
+        })?;
+        std::fs::write(&self.store_path, json).map_err(|e| ConvertError::io(&self.store_path, e))
+    }
+
+    pub fn list(&self) -> Vec<Preset> {
+        self.presets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, options)| Preset {
+                name: name.clone(),
+                options: options.clone(),
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<ConvertOptions, ConvertError> {
+        self.presets
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConvertError::UnknownPreset {
+                name: name.to_string(),
+            })
+    }
+
+    // Replaces any preset of the same name.
+    pub fn save(&self, name: String, options: ConvertOptions) -> Result<(), ConvertError> {
+        let mut presets = self.presets.lock().unwrap();
+        presets.insert(name, options);
+        self.persist(&presets)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<bool, ConvertError> {
+        let mut presets = self.presets.lock().unwrap();
+        if presets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.persist(&presets).map(|()| true)
+    }
+}
@@ -0,0 +1,42 @@
+use crate::error::ConvertError;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn print_failed(message: impl ToString) -> ConvertError {
+    ConvertError::PrintFailed {
+        message: message.to_string(),
+    }
+}
+
+// Queues the images on the default printer through CUPS.
+#[cfg(not(windows))]
+pub(crate) fn send_to_printer(paths: &[PathBuf]) -> Result<(), ConvertError> {
+    let output = Command::new("lp")
+        .arg("--")
+        .args(paths)
+        .output()
+        .map_err(print_failed)?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(print_failed(if stderr.is_empty() {
+        output.status.to_string()
+    } else {
+        stderr
+    }))
+}
+
+// Opens the Windows print dialog for each image.
+#[cfg(windows)]
+pub(crate) fn send_to_printer(paths: &[PathBuf]) -> Result<(), ConvertError> {
+    for path in paths {
+        let path = path.to_string_lossy().replace('\'', "''");
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command"])
+            .arg(format!("Start-Process -FilePath '{}' -Verb Print", path))
+            .spawn()
+            .map_err(print_failed)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// Runs the current thread at background priority until dropped, so a giant batch
+// leaves the rest of the machine responsive. Restoring is best effort: Linux only
+// lets unprivileged threads raise their niceness back within RLIMIT_NICE.
+pub(crate) struct BackgroundPriority {
+    #[cfg(target_os = "linux")]
+    previous_nice: libc::c_int,
+}
+
+impl BackgroundPriority {
+    pub fn enter() -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            // With `who == 0` Linux applies the niceness to the calling thread only
+            let previous_nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 10) };
+            BackgroundPriority { previous_nice }
+        }
+        #[cfg(target_os = "macos")]
+        {
+            unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) };
+            BackgroundPriority {}
+        }
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::System::Threading::{
+                GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+            };
+            unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) };
+            BackgroundPriority {}
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+        BackgroundPriority {}
+    }
+}
+
+impl Drop for BackgroundPriority {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::setpriority(libc::PRIO_PROCESS, 0, self.previous_nice);
+        }
+        #[cfg(target_os = "macos")]
+        unsafe {
+            libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, 0);
+        }
+        #[cfg(windows)]
+        unsafe {
+            use windows_sys::Win32::System::Threading::{
+                GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_END,
+            };
+            SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_END);
+        }
+    }
+}
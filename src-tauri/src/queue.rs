@@ -0,0 +1,293 @@
+use crate::ConvertOptions;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct QueueEntry {
+    pub id: u64,
+    pub input_path: String,
+    // 0-based pages already written by an interrupted run
+    #[serde(default)]
+    pub done_pages: Vec<usize>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct QueueState {
+    pub entries: Vec<QueueEntry>,
+    pub running: bool,
+    // Settings of the last run, used to resume work restored from a previous session
+    pub output_dir: Option<String>,
+    pub options: Option<ConvertOptions>,
+    // Unix seconds at which the queue starts by itself
+    pub scheduled_at: Option<u64>,
+    // Entries the previous session left unfinished, which the window offers to resume
+    pub restored: Vec<u64>,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct PersistedQueue {
+    entries: Vec<QueueEntry>,
+    output_dir: Option<String>,
+    options: Option<ConvertOptions>,
+    #[serde(default)]
+    scheduled_at: Option<u64>,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    entries: VecDeque<QueueEntry>,
+    // Popped but not yet finished; written to disk so a crash doesn't lose it
+    current: Option<QueueEntry>,
+    output_dir: Option<String>,
+    options: Option<ConvertOptions>,
+    scheduled_at: Option<u64>,
+}
+
+// Pending input files. The conversion loop pops from the front, so entries can be
+// added, removed and reordered while a batch is running. Every change is mirrored
+// to `store_path` so unfinished work survives an app restart.
+#[derive(Default)]
+pub(crate) struct ConversionQueue {
+    next_id: AtomicU64,
+    inner: Mutex<QueueInner>,
+    running: AtomicBool,
+    store_path: Option<PathBuf>,
+    restored: Vec<u64>,
+}
+
+impl ConversionQueue {
+    pub fn load(store_path: PathBuf) -> Self {
+        let persisted: PersistedQueue = std::fs::read(&store_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let max_id = persisted.entries.iter().map(|e| e.id).max().unwrap_or(0);
+        let restored = persisted.entries.iter().map(|e| e.id).collect();
+        ConversionQueue {
+            next_id: AtomicU64::new(max_id),
+            inner: Mutex::new(QueueInner {
+                entries: persisted.entries.into(),
+                current: None,
+                output_dir: persisted.output_dir,
+                options: persisted.options,
+                scheduled_at: persisted.scheduled_at,
+            }),
+            running: AtomicBool::new(false),
+            store_path: Some(store_path),
+            restored,
+        }
+    }
+
+    fn persist(&self, inner: &QueueInner) {
+        let Some(path) = &self.store_path else {
+            return;
+        };
+        let persisted = PersistedQueue {
+            entries: inner
+                .current
+                .iter()
+                .chain(inner.entries.iter())
+                .cloned()
+                .collect(),
+            output_dir: inner.output_dir.clone(),
+            options: inner.options.clone(),
+            scheduled_at: inner.scheduled_at,
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        // Written beside it and renamed over it, so a crash mid-write keeps the old one
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp_path = path.with_file_name(format!(".{}.part", file_name));
+        if let Ok(json) = serde_json::to_vec(&persisted) {
+            if std::fs::write(&tmp_path, json).is_ok() {
+                let _ = std::fs::rename(&tmp_path, path);
+            }
+        }
+    }
+
+    pub fn enqueue(&self, input_paths: Vec<String>) -> Vec<QueueEntry> {
+        let added: Vec<QueueEntry> = input_paths
+            .into_iter()
+            .map(|input_path| QueueEntry {
+                id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+                input_path,
+                done_pages: Vec::new(),
+            })
+            .collect();
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.extend(added.iter().cloned());
+        self.persist(&inner);
+        added
+    }
+
+    pub fn remove(&self, id: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.entries.len();
+        inner.entries.retain(|e| e.id != id);
+        let removed = inner.entries.len() != before;
+        self.persist(&inner);
+        removed
+    }
+
+    // Moves the given ids to the front in the given order; unknown ids are ignored and
+    // unlisted entries keep their relative order behind them.
+    pub fn reorder(&self, ids: &[u64]) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut reordered: VecDeque<QueueEntry> = ids
+            .iter()
+            .filter_map(|id| inner.entries.iter().find(|e| e.id == *id).cloned())
+            .collect();
+        reordered.extend(
+            inner
+                .entries
+                .iter()
+                .filter(|e| !ids.contains(&e.id))
+                .cloned(),
+        );
+        inner.entries = reordered;
+        self.persist(&inner);
+    }
+
+    // Marks the previous entry as done and hands out the next one.
+    pub fn pop_front(&self) -> Option<QueueEntry> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.current = inner.entries.pop_front();
+        self.persist(&inner);
+        inner.current.clone()
+    }
+
+    // Hands out entries one at a time until `stopped` says so; entries that weren't
+    // handed out stay queued.
+    pub fn drain<'a>(
+        &'a self,
+        stopped: impl Fn() -> bool + 'a,
+    ) -> impl Iterator<Item = QueueEntry> + 'a {
+        std::iter::from_fn(move || match stopped() {
+            true => None,
+            false => self.pop_front(),
+        })
+    }
+
+    // Records a finished page of the entry being converted so a restart can pick up
+    // after it.
+    pub fn checkpoint(&self, page_index: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(current) = inner.current.as_mut() else {
+            return;
+        };
+        current.done_pages.push(page_index);
+        self.persist(&inner);
+    }
+
+    pub fn state(&self) -> QueueState {
+        let inner = self.inner.lock().unwrap();
+        QueueState {
+            entries: inner.entries.iter().cloned().collect(),
+            running: self.running.load(Ordering::Relaxed),
+            output_dir: inner.output_dir.clone(),
+            options: inner.options.clone(),
+            scheduled_at: inner.scheduled_at,
+            restored: inner
+                .entries
+                .iter()
+                .map(|e| e.id)
+                .filter(|id| self.restored.contains(id))
+                .collect(),
+        }
+    }
+
+    pub fn saved_settings(&self) -> Option<(String, ConvertOptions)> {
+        let inner = self.inner.lock().unwrap();
+        Some((inner.output_dir.clone()?, inner.options.clone()?))
+    }
+
+    pub fn schedule(&self, start_at: u64, output_dir: &str, options: &ConvertOptions) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.scheduled_at = Some(start_at);
+        inner.output_dir = Some(output_dir.to_string());
+        inner.options = Some(options.clone());
+        self.persist(&inner);
+    }
+
+    pub fn cancel_schedule(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.scheduled_at = None;
+        self.persist(&inner);
+    }
+
+    // Clears and reports a schedule whose start time has passed.
+    pub fn take_due(&self, now: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.scheduled_at.is_none_or(|at| at > now) {
+            return false;
+        }
+        inner.scheduled_at = None;
+        self.persist(&inner);
+        true
+    }
+
+    // Returns false if the queue is already being drained.
+    pub fn try_start(&self, output_dir: &str, options: &ConvertOptions) -> bool {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.output_dir = Some(output_dir.to_string());
+        inner.options = Some(options.clone());
+        self.persist(&inner);
+        true
+    }
+
+    pub fn finish(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.current = None;
+        self.persist(&inner);
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(entries: &[QueueEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.input_path.as_str()).collect()
+    }
+
+    #[test]
+    fn drains_in_order() {
+        let queue = ConversionQueue::default();
+        queue.enqueue(vec!["a.pdf".into(), "b.pdf".into()]);
+        let drained: Vec<QueueEntry> = queue.drain(|| false).collect();
+        assert_eq!(paths(&drained), ["a.pdf", "b.pdf"]);
+        assert!(queue.state().entries.is_empty());
+    }
+
+    #[test]
+    fn stopping_keeps_entries_that_did_not_start() {
+        let queue = ConversionQueue::default();
+        queue.enqueue(vec!["a.pdf".into(), "b.pdf".into(), "c.pdf".into()]);
+        let cancelled = AtomicBool::new(false);
+        let mut drain = queue.drain(|| cancelled.load(Ordering::Relaxed));
+        assert_eq!(drain.next().unwrap().input_path, "a.pdf");
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(drain.next().is_none());
+        drop(drain);
+        queue.finish();
+        assert_eq!(paths(&queue.state().entries), ["b.pdf", "c.pdf"]);
+    }
+
+    #[test]
+    fn entries_picked_up_meanwhile_are_drained() {
+        let queue = ConversionQueue::default();
+        queue.enqueue(vec!["a.pdf".into()]);
+        let mut drain = queue.drain(|| false);
+        assert_eq!(drain.next().unwrap().input_path, "a.pdf");
+        queue.enqueue(vec!["b.pdf".into()]);
+        assert_eq!(drain.next().unwrap().input_path, "b.pdf");
+        assert!(drain.next().is_none());
+    }
+}
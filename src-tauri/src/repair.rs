@@ -0,0 +1,124 @@
+// Last-resort fixes for PDFs pdfium refuses to open. Pdfium already rebuilds broken
+// cross-reference tables itself; what it can't get past is junk around the file
+// (mail headers, download padding) and a missing trailer, e.g. in truncated files.
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+pub(crate) fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+// Reads the integer that ends right before `end`, skipping whitespace, and returns it
+// with the position where it starts.
+fn number_before(bytes: &[u8], end: usize) -> Option<(u64, usize)> {
+    let end = bytes[..end]
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())?
+        + 1;
+    let start = bytes[..end]
+        .iter()
+        .rposition(|b| !b.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    let number = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+    Some((number, start))
+}
+
+// The `num gen` of the object holding the document catalog.
+fn catalog_reference(bytes: &[u8]) -> Option<(u64, u64)> {
+    let catalog = rfind(bytes, b"/Catalog")?;
+    let obj = rfind(&bytes[..catalog], b"obj")?;
+    let (generation, start) = number_before(bytes, obj)?;
+    let (number, _) = number_before(bytes, start)?;
+    Some((number, generation))
+}
+
+// A copy of the file cut down to its `%PDF-` ... `%%EOF` span, with a trailer pointing
+// at the catalog appended when the end of the file is missing. `None` if there is
+// nothing to fix or it isn't recognisably a PDF.
+pub(crate) fn recover(bytes: &[u8]) -> Option<Vec<u8>> {
+    let start = find(bytes, b"%PDF-")?;
+    let body = &bytes[start..];
+    let fixed = match rfind(body, b"%%EOF") {
+        Some(eof) if find(body, b"startxref").is_some() => body[..eof + 5].to_vec(),
+        _ => {
+            let (number, generation) = catalog_reference(body)?;
+            let mut fixed = body.to_vec();
+            fixed.extend_from_slice(
+                format!(
+                    "\ntrailer\n<< /Root {} {} R >>\n%%EOF\n",
+                    number, generation
+                )
+                .as_bytes(),
+            );
+            fixed
+        }
+    };
+    (fixed.as_slice() != bytes).then_some(fixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n\
+                          2 0 obj\n<< /Type /Pages /Kids [] /Count 0 >>\nendobj\n";
+    const TAIL: &[u8] = b"xref\n0 1\n0000000000 65535 f \ntrailer\n<< /Size 3 /Root 1 0 R >>\n\
+                          startxref\n95\n%%EOF";
+
+    fn complete() -> Vec<u8> {
+        [BODY, TAIL].concat()
+    }
+
+    #[test]
+    fn leaves_intact_files_alone() {
+        assert_eq!(recover(&complete()), None);
+    }
+
+    #[test]
+    fn strips_junk_around_the_file() {
+        let wrapped = [
+            &b"Content-Type: application/pdf\r\n\r\n"[..],
+            &complete(),
+            b"\n\0\0\0padding",
+        ]
+        .concat();
+        assert_eq!(recover(&wrapped), Some(complete()));
+    }
+
+    #[test]
+    fn appends_a_trailer_to_truncated_files() {
+        let fixed = recover(BODY).unwrap();
+        assert!(fixed.starts_with(BODY));
+        assert!(fixed.ends_with(b"\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n"));
+    }
+
+    #[test]
+    fn finds_the_catalog_in_later_objects() {
+        let truncated = b"%PDF-1.7\n3 0 obj\n<< /Type /Pages >>\nendobj\n12 2 obj\n\
+                          << /Type /Catalog /Pages 3 0 R >>\nendobj\n";
+        let fixed = recover(truncated).unwrap();
+        assert!(fixed.ends_with(b"<< /Root 12 2 R >>\n%%EOF\n"));
+    }
+
+    #[test]
+    fn gives_up_on_what_isnt_a_pdf() {
+        assert_eq!(recover(b""), None);
+        assert_eq!(recover(b"<html>not found</html>"), None);
+        // A truncated file without a catalog can't be given a trailer
+        assert_eq!(recover(b"%PDF-1.4\n1 0 obj\n<< /Type /Pages >>"), None);
+        assert_eq!(recover(b"%PDF-1.4\n/Catalog obj"), None);
+    }
+
+    #[test]
+    fn reads_numbers_before_a_position() {
+        assert_eq!(number_before(b"12 0 obj", 4), Some((0, 3)));
+        assert_eq!(number_before(b"12 0 obj", 2), Some((12, 0)));
+        assert_eq!(number_before(b"x obj", 2), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::error::{ConvertError, ErrorPayload};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportFormat {
+    Json,
+    Csv,
+}
+
+// Where a page's time went, to spot pathological pages. For merged output the
+// encode time is the page's copy into the combined image.
+#[derive(Clone, Default, serde::Serialize)]
+pub(crate) struct PageMetrics {
+    pub page: usize, // 1-based
+    pub render_ms: u64,
+    pub encode_ms: u64,
+    pub output_bytes: Option<u64>,
+    pub cached: bool,
+}
+
+// A page that looked like one converted earlier in the batch.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct DuplicatePage {
+    pub page: usize, // 1-based
+    // Output of the earlier page
+    pub duplicate_of: String,
+}
+
+#[derive(Clone, Default, serde::Serialize)]
+pub(crate) struct FileReport {
+    pub input_path: String,
+    pub filename: String,
+    pub status: String, // "success", "error", "skipped", "cancelled"
+    pub pages_converted: usize,
+    pub pages_up_to_date: usize,
+    pub outputs: Vec<String>,
+    pub failed_pages: Vec<usize>, // 1-based
+    pub duration_ms: u64,
+    pub errors: Vec<ErrorPayload>,
+    pub pages: Vec<PageMetrics>,
+    // Fonts pdfium had to substitute
+    pub missing_fonts: Vec<String>,
+    // Where the source PDF was moved after converting
+    pub source_moved_to: Option<String>,
+    pub repaired: bool,
+    pub duplicate_pages: Vec<DuplicatePage>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct BatchReport {
+    pub job_id: u64,
+    pub started_at: u64, // unix seconds
+    pub duration_ms: u64,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub aborted: bool,
+    pub files: Vec<FileReport>,
+    pub report_path: Option<String>,
+    pub webhook_error: Option<ErrorPayload>,
+    // The job whose failures this one retried
+    pub retry_of: Option<u64>,
+}
+
+impl BatchReport {
+    pub fn new(job_id: u64, started_at: u64, duration_ms: u64, files: Vec<FileReport>) -> Self {
+        let count = |status: &str| files.iter().filter(|f| f.status == status).count();
+        let (succeeded, failed) = (count("success"), count("error"));
+        let skipped = count("skipped") + count("cancelled");
+        BatchReport {
+            job_id,
+            started_at,
+            duration_ms,
+            succeeded,
+            failed,
+            skipped,
+            aborted: false,
+            files,
+            report_path: None,
+            webhook_error: None,
+            retry_of: None,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("input_path,status,pages_converted,outputs,duration_ms,errors\n");
+        for file in &self.files {
+            let errors: Vec<&str> = file.errors.iter().map(|e| e.message.as_str()).collect();
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_field(&file.input_path),
+                csv_field(&file.status),
+                file.pages_converted,
+                csv_field(&file.outputs.join(";")),
+                file.duration_ms,
+                csv_field(&errors.join("; ")),
+            ));
+        }
+        csv
+    }
+
+    // Writes `conversion_report_<started_at>.<ext>` into `dir` and remembers the path.
+    // The path is set first so the JSON report names itself.
+    pub fn write(&mut self, dir: &Path, format: ReportFormat) -> Result<PathBuf, ConvertError> {
+        let ext = match format {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+        };
+        let path = dir.join(format!("conversion_report_{}.{}", self.started_at, ext));
+        self.report_path = Some(path.to_string_lossy().to_string());
+        let contents = match format {
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| ConvertError::Internal {
+                    message: e.to_string(),
+                })
+            }
+            ReportFormat::Csv => Ok(self.to_csv()),
+        };
+        let written = contents.and_then(|contents| {
+            std::fs::write(&path, contents).map_err(|e| ConvertError::io(&path, e))
+        });
+        if let Err(e) = written {
+            self.report_path = None;
+            return Err(e);
+        }
+        Ok(path)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
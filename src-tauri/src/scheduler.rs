@@ -0,0 +1,27 @@
+use crate::queue::ConversionQueue;
+use crate::unix_now;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+// Starts the queue once its scheduled time has come. The time is stored with the
+// queue, so a schedule set before a restart still fires, late if the app was closed.
+pub(crate) fn spawn(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let queue = app.state::<ConversionQueue>();
+        if !queue.take_due(unix_now()) {
+            continue;
+        }
+        let _ = app.emit("queue_changed", queue.state());
+        match crate::drain_queue(&app, None, None) {
+            Ok(report) => {
+                let _ = app.emit("scheduled_batch_finished", report);
+            }
+            Err(e) => {
+                let _ = app.emit("scheduled_batch_failed", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use crate::error::ConvertError;
+use crate::report::FileReport;
+use crate::ConvertOptions;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+// Failures from every batch run since the app started, kept for bug reports.
+#[derive(Default)]
+pub(crate) struct SessionLog {
+    entries: Mutex<Vec<SessionEntry>>,
+    // Problems found while the app started, before any batch ran
+    startup: Mutex<Vec<String>>,
+}
+
+struct SessionEntry {
+    started_at: u64,
+    pdfium_version: String,
+    options: ConvertOptions,
+    failures: Vec<FileReport>,
+}
+
+impl SessionLog {
+    pub fn record_startup(&self, error: &ConvertError) {
+        self.startup
+            .lock()
+            .unwrap()
+            .push(format!("[{}] {}", error.code(), error));
+    }
+
+    pub fn record(
+        &self,
+        started_at: u64,
+        pdfium_version: String,
+        options: &ConvertOptions,
+        files: &[FileReport],
+    ) {
+        let failures: Vec<FileReport> = files
+            .iter()
+            .filter(|f| !f.errors.is_empty())
+            .cloned()
+            .collect();
+        if failures.is_empty() {
+            return;
+        }
+        self.entries.lock().unwrap().push(SessionEntry {
+            started_at,
+            pdfium_version,
+            options: options.clone(),
+            failures,
+        });
+    }
+
+    pub fn render(&self) -> String {
+        let entries = self.entries.lock().unwrap();
+        let startup = self.startup.lock().unwrap();
+        let mut log = String::new();
+        let _ = writeln!(log, "PDF to Image error log");
+        let _ = writeln!(log, "App version: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(
+            log,
+            "Platform: {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        if entries.is_empty() && startup.is_empty() {
+            let _ = writeln!(log, "\nNo errors recorded in this session.");
+        }
+        if !startup.is_empty() {
+            let _ = writeln!(log, "\n== Startup ==");
+            for error in startup.iter() {
+                let _ = writeln!(log, "  {}", error);
+            }
+        }
+        for entry in entries.iter() {
+            let _ = writeln!(log, "\n== Batch started at {} ==", entry.started_at);
+            let _ = writeln!(log, "PDFium: {}", entry.pdfium_version);
+            let _ = writeln!(
+                log,
+                "Settings: {}",
+                serde_json::to_string(&entry.options).unwrap_or_default()
+            );
+            for file in &entry.failures {
+                let _ = writeln!(log, "File: {}", file.input_path);
+                for error in &file.errors {
+                    let _ = writeln!(log, "  [{}] {}", error.error.code(), error.message);
+                }
+            }
+        }
+        log
+    }
+}
@@ -0,0 +1,83 @@
+use crate::error::ConvertError;
+use crate::hooks;
+use crate::i18n::{self, Locale};
+use crate::ConvertOptions;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Settings {
+    pub output_dir: Option<String>,
+    pub options: Option<ConvertOptions>,
+    // Language of error messages and notifications, e.g. "vi"; the system's when unset
+    #[serde(default)]
+    pub locale: Option<String>,
+    // Shell command run after each output, e.g. `oxipng {path}`. Only set by editing
+    // the settings file; `set` keeps the stored one whatever the webview sends.
+    #[serde(default)]
+    pub post_command: Option<String>,
+}
+
+fn apply(settings: &Settings) {
+    i18n::set_locale(Locale::resolve(settings.locale.as_deref()));
+    hooks::set_post_command(settings.post_command.clone());
+}
+
+// The most recent conversion settings, kept in `store_path` so backend-started work
+// such as the queue or a folder watch can run without the frontend supplying them.
+pub(crate) struct SettingsStore {
+    settings: Mutex<Settings>,
+    store_path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn load(store_path: PathBuf) -> Self {
+        let settings = std::fs::read(&store_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        apply(&settings);
+        SettingsStore {
+            settings: Mutex::new(settings),
+            store_path,
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) -> Result<(), ConvertError> {
+        let settings = Settings {
+            post_command: self.get().post_command,
+            ..settings
+        };
+        if let Some(parent) = self.store_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_vec_pretty(&settings).map_err(|e| ConvertError::Internal {
+            message: e.to_string(),
+        })?;
+        std::fs::write(&self.store_path, json)
+            .map_err(|e| ConvertError::io(&self.store_path, e))?;
+        apply(&settings);
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    // Called for every started conversion; a failed write only loses the convenience.
+    pub fn remember(&self, output_dir: Option<&str>, options: &ConvertOptions) {
+        let current = self.get();
+        let _ = self.set(Settings {
+            output_dir: output_dir.map(Into::into).or(current.output_dir),
+            options: Some(options.clone()),
+            ..current
+        });
+    }
+
+    // Both halves, as needed to start a batch.
+    pub fn saved(&self) -> Option<(String, ConvertOptions)> {
+        let settings = self.get();
+        Some((settings.output_dir?, settings.options?))
+    }
+}
@@ -0,0 +1,62 @@
+use crate::i18n;
+use crate::jobs::JobRegistry;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{App, AppHandle, Manager};
+use tauri_plugin_opener::OpenerExt;
+
+pub(crate) fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// Tray menu for batches running while the window is hidden; pausing and cancelling
+// act on every running batch.
+pub(crate) fn setup(app: &App) -> tauri::Result<()> {
+    // Labelled in the locale set at startup
+    let item = |id: &str| {
+        MenuItem::with_id(
+            app,
+            id,
+            i18n::text(&format!("tray.{}", id), &[]),
+            true,
+            None::<&str>,
+        )
+    };
+    let show = item("show")?;
+    let pause = item("pause")?;
+    let cancel = item("cancel")?;
+    let open_output = item("open_output")?;
+    let quit = item("quit")?;
+    let menu = Menu::with_items(app, &[&show, &pause, &cancel, &open_output, &quit])?;
+
+    let mut tray = TrayIconBuilder::new()
+        .menu(&menu)
+        .on_menu_event(move |app, event| {
+            let jobs = app.state::<JobRegistry>();
+            match event.id.as_ref() {
+                "show" => show_main_window(app),
+                "pause" => {
+                    let paused = !jobs.is_paused();
+                    jobs.set_paused(None, paused);
+                    let label = if paused { "tray.resume" } else { "tray.pause" };
+                    let _ = pause.set_text(i18n::text(label, &[]));
+                }
+                "cancel" => jobs.cancel_batch(None),
+                "open_output" => {
+                    if let Some(dir) = jobs.output_dir() {
+                        let _ = app.opener().open_path(dir, None::<&str>);
+                    }
+                }
+                "quit" => app.exit(0),
+                _ => {}
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::discover::is_pdf;
+use crate::error::ConvertError;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+// How long a file's size has to stay unchanged before it counts as fully copied.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct WatchConfig {
+    pub folder: String,
+    pub output_dir: String,
+    // Looked up for each file, so editing the preset changes what the watch does
+    pub preset: String,
+}
+
+// A PDF to convert, and whether it was in the folder before the watch started.
+type Arrival = (PathBuf, bool);
+
+// Converts every PDF dropped into a folder. The config is saved to `store_path` so
+// the watch picks up again on the next launch.
+pub(crate) struct FolderWatch {
+    active: Mutex<Option<(WatchConfig, RecommendedWatcher)>>,
+    store_path: PathBuf,
+}
+
+fn watch_error(folder: &str, err: notify::Error) -> ConvertError {
+    ConvertError::WatchFailed {
+        path: folder.to_string(),
+        message: err.to_string(),
+    }
+}
+
+impl FolderWatch {
+    pub fn new(store_path: PathBuf) -> Self {
+        FolderWatch {
+            active: Mutex::new(None),
+            store_path,
+        }
+    }
+
+    pub fn saved_config(&self) -> Option<WatchConfig> {
+        let bytes = std::fs::read(&self.store_path).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn config(&self) -> Option<WatchConfig> {
+        self.active
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(config, _)| config.clone())
+    }
+
+    // Replaces any running watch.
+    pub fn start(&self, app: AppHandle, config: WatchConfig) -> Result<(), ConvertError> {
+        let (tx, rx) = mpsc::channel();
+        let tx_existing = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            // Only arrivals: writes to a file already seen are caught by waiting for
+            // its size to settle, and a converted file touched later stays converted
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                for path in event.paths {
+                    let _ = tx.send((path, false));
+                }
+            }
+        })
+        .map_err(|e| watch_error(&config.folder, e))?;
+        watcher
+            .watch(Path::new(&config.folder), RecursiveMode::NonRecursive)
+            .map_err(|e| watch_error(&config.folder, e))?;
+        // Watching starts first so nothing slips in between listing and watching
+        let existing = std::fs::read_dir(&config.folder)
+            .map_err(|e| ConvertError::io(Path::new(&config.folder), e))?;
+        for entry in existing.flatten() {
+            let _ = tx_existing.send((entry.path(), true));
+        }
+
+        let worker_config = config.clone();
+        std::thread::spawn(move || watch_loop(&app, &worker_config, rx));
+
+        if let Some(parent) = self.store_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_vec(&config) {
+            let _ = std::fs::write(&self.store_path, json);
+        }
+        *self.active.lock().unwrap() = Some((config, watcher));
+        Ok(())
+    }
+
+    // Dropping the watcher closes the channel, which ends the worker thread.
+    pub fn stop(&self) {
+        self.active.lock().unwrap().take();
+        let _ = std::fs::remove_file(&self.store_path);
+    }
+}
+
+// Waits for new PDFs to stop growing, then converts each as its own batch.
+fn watch_loop(app: &AppHandle, config: &WatchConfig, rx: Receiver<Arrival>) {
+    let mut pending: HashMap<PathBuf, (u64, Instant, bool)> = HashMap::new();
+    loop {
+        match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok((path, existing)) => {
+                if is_pdf(&path) {
+                    pending
+                        .entry(path)
+                        .or_insert((u64::MAX, Instant::now(), existing));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let mut settled = Vec::new();
+        pending.retain(|path, (size, since, existing)| {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            if metadata.len() != *size {
+                *size = metadata.len();
+                *since = Instant::now();
+                return true;
+            }
+            if since.elapsed() < SETTLE_TIME {
+                return true;
+            }
+            settled.push((path.clone(), *existing));
+            false
+        });
+
+        for (path, existing) in settled {
+            let path = path.to_string_lossy().to_string();
+            if let Err(e) = crate::convert_watched_file(app, config, path, existing) {
+                let _ = app.emit("watch_error", e);
+            }
+        }
+    }
+}
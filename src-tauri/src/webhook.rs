@@ -0,0 +1,18 @@
+use crate::error::ConvertError;
+use crate::report::BatchReport;
+use std::time::Duration;
+
+// Posts the batch summary as JSON so automated pipelines can pick up the outputs.
+pub(crate) fn post_report(url: &str, report: &BatchReport) -> Result<(), ConvertError> {
+    let failed = |message: String| ConvertError::WebhookFailed {
+        url: url.to_string(),
+        message,
+    };
+    let body = serde_json::to_string(report).map_err(|e| failed(e.to_string()))?;
+    ureq::post(url)
+        .timeout(Duration::from_secs(30))
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map(|_| ())
+        .map_err(|e| failed(e.to_string()))
+}
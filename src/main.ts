@@ -6,27 +6,102 @@ interface ProgressPayload {
   filename: string;
   current: number;
   total: number;
+  page: number;
+  width: number;
+  height: number;
+  output_path?: string;
+  pages_per_sec?: number;
+  eta_ms?: number;
+}
+
+type FileStatus = "queued" | "processing" | "success" | "error" | "skipped" | "cancelled";
+
+interface ErrorPayload {
+  code: string;
+  context?: Record<string, unknown>;
+  message?: string;
 }
 
 interface FileStatusPayload {
   filename: string;
-  status: "queued" | "processing" | "success" | "error";
+  status: FileStatus;
   error?: string;
   output_path?: string;
+  conflict_action?: string;
+  error_detail?: ErrorPayload;
+  repaired?: boolean;
+}
+
+interface MissingFontsPayload {
+  filename: string;
+  fonts: string[];
+}
+
+interface PageClampedPayload {
+  filename: string;
+  page: number;
+  requested_width: number;
+  requested_height: number;
+  width: number;
+  height: number;
+}
+
+interface BatchFailedPayload {
+  job_id: number;
+  error: ErrorPayload;
+}
+
+interface QueueEntry {
+  id: number;
+  input_path: string;
+}
+
+interface QueueState {
+  entries: QueueEntry[];
+  running: boolean;
+  scheduled_at: number | null;
+  restored: number[];
+}
+
+interface DeepLinkRequest {
+  id: number;
+  url: string;
+  paths: string[];
+  output_dir: string;
+}
+
+interface DeepLinkFailedPayload {
+  url: string;
+  error: ErrorPayload;
+}
+
+interface FileInspection {
+  path: string;
+  error?: ErrorPayload;
+}
+
+interface Settings {
+  locale?: string | null;
+  [key: string]: unknown;
 }
 
 interface FileState {
   filename: string;
-  status: "queued" | "processing" | "success" | "error";
+  status: FileStatus;
   progressCurrent: number;
   progressTotal: number;
   error?: string;
+  warning?: string;
+  outputPath?: string;
+  repaired?: boolean;
 }
 
 let selectedFiles: string[] = [];
 let outputDirectory: string | null = null;
 let unlistenProgress: (() => void) | null = null;
 let unlistenStatus: (() => void) | null = null;
+let unlistenFonts: (() => void) | null = null;
+let unlistenClamped: (() => void) | null = null;
 let fileStates = new Map<string, FileState>();
 
 // Elements
@@ -46,25 +121,43 @@ const qualitySlider = document.getElementById("quality-slider") as HTMLInputElem
 const qualityVal = document.getElementById("quality-val") as HTMLSpanElement;
 const qualitySection = document.getElementById("quality-section") as HTMLDivElement;
 const mergeCheckbox = document.getElementById("merge-checkbox") as HTMLInputElement;
+const repairCheckbox = document.getElementById("repair-checkbox") as HTMLInputElement;
+const losslessSection = document.getElementById("lossless-section") as HTMLDivElement;
+const losslessCheckbox = document.getElementById("lossless-checkbox") as HTMLInputElement;
+const localeSelect = document.getElementById("locale-select") as HTMLSelectElement;
 
 // Handle Quality Visibility and Label
-formatSelect.addEventListener("change", () => {
-  if (formatSelect.value === "jpg") {
-    qualitySection.style.display = "block";
-  } else {
-    qualitySection.style.display = "none";
-  }
-});
+function updateFormatSections() {
+  const isWebp = formatSelect.value === "webp";
+  const isLossy = formatSelect.value === "jpg" || (isWebp && !losslessCheckbox.checked);
+  qualitySection.style.display = isLossy ? "block" : "none";
+  losslessSection.style.display = isWebp ? "block" : "none";
+}
+
+formatSelect.addEventListener("change", updateFormatSections);
+losslessCheckbox.addEventListener("change", updateFormatSections);
 
 // Initial show/hide quality
-if (formatSelect.value === "jpg") {
-  qualitySection.style.display = "block";
-}
+updateFormatSections();
 
 qualitySlider.addEventListener("input", () => {
   qualityVal.textContent = qualitySlider.value;
 });
 
+// Language of the backend's messages; saved with the other settings, so the command
+// line follows it too
+invoke<Settings>("get_settings").then((settings) => {
+  localeSelect.value = settings.locale ?? "";
+});
+localeSelect.addEventListener("change", async () => {
+  const settings = await invoke<Settings>("get_settings");
+  await invoke("set_settings", { settings: { ...settings, locale: localeSelect.value || null } });
+});
+
+// Commands reject with an ErrorPayload; fall back to the raw value otherwise
+const describeError = (error: unknown) =>
+  (error as ErrorPayload | null)?.message ?? String(error);
+
 // Helper to get basenames
 const getBasename = (path: string) => path.split(/[\\/]/).pop() || "unknown";
 // ... (renderTable and updateUI remain the same, adding them inside replace_file_content if needed)
@@ -99,6 +192,10 @@ function renderTable() {
     // Column 1: File
     const tdName = document.createElement("td");
     tdName.innerHTML = `<span class="icon">📄</span> ${filename}`;
+    if (state.warning) {
+      tdName.append(" ⚠️");
+      tdName.title = state.warning;
+    }
     tr.appendChild(tdName);
 
     // Column 2: Status
@@ -114,7 +211,16 @@ function renderTable() {
     if (state.status === "processing") {
       tdProgress.textContent = `${state.progressCurrent} / ${state.progressTotal}`;
     } else if (state.status === "success") {
-      tdProgress.textContent = "Done";
+      tdProgress.textContent = state.repaired ? "Done (repaired)" : "Done";
+      if (state.outputPath) {
+        const revealBtn = document.createElement("button");
+        revealBtn.className = "btn secondary";
+        revealBtn.textContent = "Show";
+        revealBtn.title = state.outputPath;
+        const outputPath = state.outputPath;
+        revealBtn.addEventListener("click", () => invoke("reveal_file", { path: outputPath }));
+        tdProgress.append(" ", revealBtn);
+      }
     } else if (state.status === "error") {
       tdProgress.textContent = state.error || "Failed";
       tdProgress.style.color = "#f87171";
@@ -143,6 +249,8 @@ function updateUI() {
 async function setupListeners() {
   if (unlistenProgress) unlistenProgress();
   if (unlistenStatus) unlistenStatus();
+  if (unlistenFonts) unlistenFonts();
+  if (unlistenClamped) unlistenClamped();
 
   unlistenProgress = await listen<ProgressPayload>("progress", (event) => {
     const { filename, current, total } = event.payload;
@@ -156,20 +264,126 @@ async function setupListeners() {
   });
 
   unlistenStatus = await listen<FileStatusPayload>("file_status", (event) => {
-    const { filename, status, error } = event.payload;
+    const { filename, status, error, output_path, repaired } = event.payload;
     const state = fileStates.get(filename);
     if (state) {
       state.status = status;
       if (error) state.error = error;
+      if (output_path) state.outputPath = output_path;
+      if (repaired) state.repaired = true;
       renderTable();
       if (status === "success") {
         openOutputBtn.classList.remove("hidden");
       }
     }
   });
+
+  unlistenFonts = await listen<MissingFontsPayload>("missing_fonts", (event) => {
+    const { filename, fonts } = event.payload;
+    const state = fileStates.get(filename);
+    if (state) {
+      state.warning = `Fonts not embedded, rendered with substitutes: ${fonts.join(", ")}`;
+      renderTable();
+    }
+  });
+
+  unlistenClamped = await listen<PageClampedPayload>("page_clamped", (event) => {
+    const { filename, page, requested_width, requested_height, width, height } = event.payload;
+    const state = fileStates.get(filename);
+    if (state) {
+      state.warning = `Page ${page} too large at ${requested_width}×${requested_height}, rendered at ${width}×${height}`;
+      renderTable();
+    }
+  });
 }
 setupListeners();
 
+// PDFs the app was opened with land in the backend queue; list them so the next
+// conversion includes them.
+const queuedIds = new Map<string, number>();
+function addQueuedFiles(queue: QueueState) {
+  let added = false;
+  for (const entry of queue.entries) {
+    queuedIds.set(entry.input_path, entry.id);
+    if (!selectedFiles.includes(entry.input_path)) {
+      selectedFiles.push(entry.input_path);
+      added = true;
+    }
+  }
+  if (added) updateUI();
+}
+listen<QueueState>("queue_changed", (event) => addQueuedFiles(event.payload));
+
+// Files the last session didn't get to; a scheduled queue starts by itself instead
+invoke<QueueState>("get_queue").then(async (queue) => {
+  addQueuedFiles(queue);
+  if (queue.running || queue.scheduled_at !== null || queue.restored.length === 0) return;
+  const count = queue.restored.length;
+  if (!window.confirm(`${count} file(s) from the last session weren't converted. Resume now?`)) return;
+  try {
+    await invoke("process_queue");
+  } catch (error) {
+    window.alert(`Resuming failed: ${describeError(error)}`);
+  }
+});
+
+// Links can come from any web page, so nothing they ask for runs until confirmed here
+async function confirmDeepLink(request: DeepLinkRequest) {
+  const accept = window.confirm(
+    `A link asks to convert:\n${request.paths.join("\n")}\n\ninto ${request.output_dir}\n\nConvert now?`
+  );
+  try {
+    await invoke("answer_deep_link", { id: request.id, accept });
+  } catch (error) {
+    window.alert(`Link conversion failed: ${describeError(error)}`);
+  }
+}
+listen<DeepLinkRequest>("deep_link_request", (event) => confirmDeepLink(event.payload));
+listen<DeepLinkFailedPayload>("deep_link_failed", (event) => {
+  window.alert(describeError(event.payload.error));
+});
+invoke<DeepLinkRequest[]>("pending_deep_links").then(async (requests) => {
+  for (const request of requests) await confirmDeepLink(request);
+});
+
+// A PDFium that failed to load at startup; conversions fail until it's installed
+invoke<string>("pdfium_status").catch((error) => {
+  window.alert(`PDFium is not available: ${describeError(error)}`);
+});
+
+// Conversions run in the background: the command only returns the job id, and the
+// batch's outcome arrives as an event. Listening starts before invoking so a batch
+// that finishes instantly isn't missed.
+async function runBatch(command: string, args: Record<string, unknown>): Promise<void> {
+  const outcomes = new Map<number, ErrorPayload | null>();
+  let onOutcome: (() => void) | null = null;
+  const unlisteners = await Promise.all([
+    listen<{ job_id: number }>("batch_finished", (event) => {
+      outcomes.set(event.payload.job_id, null);
+      onOutcome?.();
+    }),
+    listen<BatchFailedPayload>("batch_failed", (event) => {
+      outcomes.set(event.payload.job_id, event.payload.error);
+      onOutcome?.();
+    }),
+  ]);
+
+  try {
+    const { job_id } = JSON.parse(await invoke<string>(command, args));
+    await new Promise<void>((resolve, reject) => {
+      onOutcome = () => {
+        if (!outcomes.has(job_id)) return;
+        const error = outcomes.get(job_id);
+        if (error) reject(error);
+        else resolve();
+      };
+      onOutcome();
+    });
+  } finally {
+    unlisteners.forEach((unlisten) => unlisten());
+  }
+}
+
 selectFilesBtn.addEventListener("click", async () => {
   const result = await open({
     multiple: true,
@@ -182,9 +396,20 @@ selectFilesBtn.addEventListener("click", async () => {
     openOutputBtn.classList.add("hidden");
     statusContainer.classList.add("hidden");
     updateUI();
+    flagBadFiles();
   }
 });
 
+// Marks files that can't be converted before the batch starts
+async function flagBadFiles() {
+  const inspections = await invoke<FileInspection[]>("inspect_files", { paths: selectedFiles });
+  for (const { path, error } of inspections) {
+    const state = fileStates.get(getBasename(path).replace(/\.pdf$/i, ""));
+    if (state && error) state.warning = describeError(error);
+  }
+  renderTable();
+}
+
 selectDirBtn.addEventListener("click", async () => {
   const result = await open({
     directory: true,
@@ -211,24 +436,33 @@ convertBtn.addEventListener("click", async () => {
   statusMsg.textContent = "Processing...";
   statusMsg.style.color = "var(--text-muted)";
 
-  fileStates.forEach(s => { s.status = "queued"; s.error = undefined; });
+  fileStates.forEach(s => { s.status = "queued"; s.error = undefined; s.warning = undefined; s.outputPath = undefined; s.repaired = undefined; });
   renderTable();
 
   try {
-    await invoke("convert_pdf", {
+    // Converted now, so they mustn't stay queued for another run
+    const queued = selectedFiles.filter((path) => queuedIds.has(path));
+    await Promise.all(queued.map((path) => invoke("remove_from_queue", { id: queuedIds.get(path) })));
+    queued.forEach((path) => queuedIds.delete(path));
+
+    await runBatch("convert_pdf", {
       inputPaths: selectedFiles,
       outputDir: outputDirectory,
-      format: formatSelect.value,
-      scale: parseFloat(scaleSelect.value),
-      pageRange: pageRangeInp.value,
-      merge: mergeCheckbox.checked,
-      quality: parseInt(qualitySlider.value)
+      options: {
+        format: formatSelect.value,
+        scale: parseFloat(scaleSelect.value),
+        page_range: pageRangeInp.value,
+        merge: mergeCheckbox.checked,
+        quality: parseInt(qualitySlider.value),
+        webp_lossless: losslessCheckbox.checked,
+        repair: repairCheckbox.checked
+      }
     });
     statusMsg.textContent = "Batch Completed! ✅";
     statusMsg.style.color = "#4ade80";
   } catch (error) {
     console.error(error);
-    statusMsg.textContent = `Error: ${error} ❌`;
+    statusMsg.textContent = `Error: ${describeError(error)} ❌`;
     statusMsg.style.color = "#f87171";
   } finally {
     convertBtn.disabled = false;